@@ -139,9 +139,138 @@ M.status = function(silent)
   local msg = status_map[cord.status] or 'Unknown'
 
   if silent == true then return msg end
-  require('cord.api.log').notify('Status: ' .. msg, vim.log.levels.INFO)
+  if not cord:supports 'query' or not cord.client or cord.client:is_closing() then
+    require('cord.api.log').notify('Status: ' .. msg, vim.log.levels.INFO)
+    return msg
+  end
+
+  require('cord.core.async').run(function()
+    local state, err = cord.tx:query('state'):await()
+    if not state then
+      require('cord.api.log').notify(
+        'Status: ' .. msg .. '\nFailed to query server state: ' .. tostring(err),
+        vim.log.levels.WARN
+      )
+      return
+    end
+
+    local lines = { 'Status: ' .. msg }
+    local status = state.status or {}
+    lines[#lines + 1] = 'Discord: '
+      .. tostring(status.status)
+      .. (type(status.pipe_path) == 'string' and (' (' .. status.pipe_path .. ')') or '')
+      .. (status.is_reconnecting and ', reconnecting' or '')
+
+    local sync = state.config and state.config.sync
+    if sync then
+      lines[#lines + 1] = 'Sync: '
+        .. (sync.enabled and (sync.mode .. ', every ' .. sync.interval .. 'ms') or 'disabled')
+    end
+
+    local activity = type(state.activity) == 'table' and state.activity or nil
+    lines[#lines + 1] = 'Activity: '
+      .. (activity and (activity.details or activity.state or activity.type) or 'none')
+      .. (state.is_paused and ' (paused)' or '')
+
+    lines[#lines + 1] = 'Sessions:'
+    for _, session in ipairs(state.sessions or {}) do
+      lines[#lines + 1] = string.format(
+        '  %s%d %s%s',
+        session.is_active and '*' or ' ',
+        session.id,
+        type(session.workspace) == 'string' and session.workspace or '-',
+        (session.is_idle and ' (idle)' or '') .. (session.is_pinned and ' (pinned)' or '')
+      )
+    end
+
+    require('cord.api.log').notify(table.concat(lines, '\n'), vim.log.levels.INFO)
+  end)
+
   return msg
 end
+local function parse_duration(input)
+  local value, unit = input:match '^(%d+)([smh]?)$'
+  if not value then return end
+  local scale = ({ [''] = 1000, s = 1000, m = 60000, h = 3600000 })[unit]
+  return tonumber(value) * scale
+end
+
+M.pin = function(ttl)
+  local cord = require 'cord.server'
+  local log = require 'cord.api.log'
+  if not cord:supports 'pin' or not cord.client or cord.client:is_closing() then
+    return log.notify('Pinning is not supported by the running server', vim.log.levels.WARN)
+  end
+
+  local ms
+  if ttl then
+    ms = parse_duration(ttl)
+    if not ms then
+      return log.notify('Invalid duration: \'' .. ttl .. '\'', vim.log.levels.ERROR)
+    end
+  end
+
+  require('cord.core.async').run(function()
+    local _, err = cord.tx:pin(ms):await()
+    if err then return log.notify('Failed to pin: ' .. tostring(err), vim.log.levels.WARN) end
+    log.notify('Pinned presence to this instance' .. (ttl and (' for ' .. ttl) or ''), vim.log.levels.INFO)
+  end)
+end
+M.unpin = function()
+  local cord = require 'cord.server'
+  local log = require 'cord.api.log'
+  if not cord:supports 'pin' or not cord.client or cord.client:is_closing() then
+    return log.notify('Pinning is not supported by the running server', vim.log.levels.WARN)
+  end
+
+  require('cord.core.async').run(function()
+    local _, err = cord.tx:unpin():await()
+    if err then return log.notify('Failed to unpin: ' .. tostring(err), vim.log.levels.WARN) end
+    log.notify('Unpinned presence', vim.log.levels.INFO)
+  end)
+end
+M.dnd = function(enabled)
+  local cord = require 'cord.server'
+  local log = require 'cord.api.log'
+  if not cord:supports 'pause' or not cord.client or cord.client:is_closing() then
+    return log.notify('Pausing is not supported by the running server', vim.log.levels.WARN)
+  end
+
+  require('cord.core.async').run(function()
+    local _, err = (enabled and cord.tx:pause() or cord.tx:resume()):await()
+    if err then
+      return log.notify(
+        'Failed to ' .. (enabled and 'pause' or 'resume') .. ': ' .. tostring(err),
+        vim.log.levels.WARN
+      )
+    end
+    log.notify(
+      enabled and 'Paused presence for all instances' or 'Resumed presence',
+      vim.log.levels.INFO
+    )
+  end)
+end
+M.share = function(allow)
+  local cord = require 'cord.server'
+  local log = require 'cord.api.log'
+  if not cord:supports 'confirm_share' or not cord.client or cord.client:is_closing() then
+    return log.notify(
+      'Sharing decisions are not supported by the running server',
+      vim.log.levels.WARN
+    )
+  end
+
+  require('cord.core.async').run(function()
+    local _, err = cord.tx:share_decision(allow):await()
+    if err then
+      return log.notify('Failed to save sharing decision: ' .. tostring(err), vim.log.levels.WARN)
+    end
+    log.notify(
+      allow and 'Workspace will be shown on Discord' or 'Workspace will be hidden from Discord',
+      vim.log.levels.INFO
+    )
+  end)
+end
 M.check = function()
   require('cord.core.async').run(
     function() require('cord.server.update').check_version():unwrap() end
@@ -228,6 +357,24 @@ M.commands = {
     },
   },
   status = M.status,
+  pin = {
+    default = function() M.pin() end,
+    action = function(ttl) M.pin(ttl) end,
+  },
+  unpin = M.unpin,
+  dnd = {
+    default = function() M.dnd(true) end,
+    subcommands = {
+      on = function() M.dnd(true) end,
+      off = function() M.dnd(false) end,
+    },
+  },
+  share = {
+    subcommands = {
+      allow = function() M.share(true) end,
+      deny = function() M.share(false) end,
+    },
+  },
   version = M.version,
   restart = M.restart,
   reconnect = M.reconnect,
@@ -6,6 +6,7 @@ local logger = require 'cord.api.log'
 ---@field reset_on_idle? boolean Whether to reset timestamp when idle
 ---@field reset_on_change? boolean Whether to reset timestamp when changing activities
 ---@field shared? boolean Whether to share timestamps between clients
+---@field scope? 'global'|'workspace'|'session'|'file' Which clients share a timestamp when `shared` is enabled
 
 ---@class CordEditorConfig
 ---@field client? 'vim'|'neovim'|'lunarvim'|'nvchad'|'astronvim'|'lazyvim'|string Editor client name, one of 'vim', 'neovim', 'lunarvim', 'nvchad', 'astronvim', 'lazyvim' or a custom Discord application ID
@@ -69,10 +70,18 @@ local logger = require 'cord.api.log'
 ---@field idle_leave? CordHook
 ---@field workspace_change? CordHook
 ---@field buf_enter? CordManagerHook
+---@field presence_change? CordPresenceHook
 
 ---@alias CordHook fun(opts: CordOpts):nil | {fun: fun(opts: CordOpts):nil, priority: number}
 ---@alias CordManagerHook fun(manager: ActivityManager):nil | {fun: fun(manager: ActivityManager):nil, priority: number}
 ---@alias CordEmptyHook fun():nil | {fun: fun():nil, priority: number}
+---@alias CordPresenceHook fun(presence: CordPresence):nil | {fun: fun(presence: CordPresence):nil, priority: number}
+
+---@class CordPresence
+---@field activity? table Activity displayed on Discord, or nil if none is
+---@field session? integer Server session that owns the displayed activity
+---@field reason 'update'|'idle_takeover'|'disconnect_fallback'|'clear'|'focus'|'pin'|'unpin'|'override'|'rotation'|'aggregate'|'restore'|'away'|'resume'|'pause'|'share'|'stats'|'reconnect' Why the presence changed
+---@field is_own boolean Whether this Neovim instance owns the displayed activity
 ---@alias CordActivityHook fun(opts: CordOpts, activity: Activity):nil | {fun: fun(opts: CordOpts, activity: Activity):nil, priority: number}
 
 ---@class CordAdvancedConfig
@@ -101,10 +110,95 @@ local logger = require 'cord.api.log'
 ---@field pipe_path? string Path to the server's pipe
 ---@field executable_path? string Path to the server's executable
 ---@field timeout? integer Timeout in milliseconds
+---@field heartbeat? CordAdvancedServerHeartbeatConfig Heartbeat settings
+---@field selection? CordAdvancedServerSelectionConfig Which instance's activity is shown when several have one
+---@field persistence? CordAdvancedServerPersistenceConfig Keep timers and presence state across server restarts
+---@field away? CordAdvancedServerAwayConfig Replace the presence when no instance has sent anything for a while
+---@field resume? CordAdvancedServerResumeConfig What the server does after a suspend or clock jump
+---@field dnd? CordAdvancedServerDndConfig Hide the presence during scheduled time windows
+---@field privacy? CordAdvancedServerPrivacyConfig Rules the server applies to every activity before it reaches Discord
+---@field confirm_share? CordAdvancedServerConfirmShareConfig Ask before a workspace is shown on Discord for the first time
+---@field history? CordAdvancedServerHistoryConfig Log what is sent to Discord to a file under `$XDG_STATE_HOME`
+---@field stats? CordAdvancedServerStatsConfig Track active time per workspace, filetype and day
+---@field wakatime? CordAdvancedServerWakaTimeConfig Export WakaTime heartbeats to a file or a WakaTime-compatible server
+
+---@class CordAdvancedServerHeartbeatConfig
+---@field enabled? boolean Whether to send heartbeats so that the server can drop this instance if it stops responding
+---@field interval? integer Interval in milliseconds between heartbeats
+---@field timeout? integer Time in milliseconds without a heartbeat after which the server drops this instance
+
+---@class CordAdvancedServerSelectionConfig
+---@field policy? 'most_recent'|'focused'|'priority'|'pinned' Policy used to pick the instance whose activity is shown
+---@field priorities? table<string, integer> Workspace globs mapped to their priority, used by the 'priority' policy
+---@field focus_grace? integer Time in milliseconds an instance must stay focused before it takes over the presence
+---@field rotation? CordAdvancedServerRotationConfig Cycle the presence between active instances
+
+---@class CordAdvancedServerPersistenceConfig
+---@field enabled? boolean Whether the server saves its state to a file under `$XDG_STATE_HOME` and restores it when it restarts
+---@field freshness? integer Time in milliseconds after which a saved state is too old to be restored
+
+---@class CordAdvancedServerAwayConfig
+---@field enabled? boolean Whether the server replaces the presence once every instance has gone without events for a while
+---@field timeout? integer Time in milliseconds every instance must go without events before the presence is replaced
+---@field activity? table Activity shown while away (`details`, `state`, `assets`, ...), the presence is cleared if nil
+
+---@class CordAdvancedServerResumeConfig
+---@field reconnect? boolean Whether to reconnect to Discord once the machine resumes from suspend or the clock jumps
+---@field timestamps? 'keep'|'shift'|'reset' Whether shared timestamps keep the time the machine was suspended for, leave it out or restart
+
+---@class CordAdvancedServerDndConfig
+---@field enabled? boolean Whether the presence is hidden while any rule's window is open
+---@field utc_offset? integer Minutes to add to UTC to get the time the rules are in, the system's local time if nil
+---@field rules? CordAdvancedServerDndRule[] Weekly windows during which the presence is hidden
+
+---@class CordAdvancedServerDndRule
+---@field days? ('mon'|'tue'|'wed'|'thu'|'fri'|'sat'|'sun')[] Days the window opens on, every day if nil
+---@field from? string Time the window opens at, as `HH:MM` (defaults to `00:00`)
+---@field to? string Time the window closes at, as `HH:MM` (defaults to `00:00`), on the next day if before `from` and the whole day if equal
+
+---@class CordAdvancedServerPrivacyConfig
+---@field workspaces? CordAdvancedServerPrivacyWorkspaceRule[] Workspaces whose presence is hidden or replaced, the first matching rule applies
+---@field redact? CordAdvancedServerPrivacyRedaction[] Patterns removed from the details, state and tooltips
+---@field private_hosts? string[] Host globs, e.g. `*.corp.example`, whose URLs are removed from buttons and links
+
+---@class CordAdvancedServerConfirmShareConfig
+---@field enabled? boolean Whether a workspace is hidden until you allow it, the decision being remembered across restarts
+
+---@class CordAdvancedServerHistoryConfig
+---@field enabled? boolean Whether every activity sent to Discord, and every clear, is appended to `history.jsonl`
+---@field max_size? integer Size in bytes the log may grow to before it is rotated
+---@field max_files? integer Number of rotated logs kept besides the current one
+
+---@class CordAdvancedServerStatsConfig
+---@field enabled? boolean Whether time spent in non-idle instances is added up in `$XDG_STATE_HOME/cord/stats`
+---@field utc_offset? integer Minutes to add to UTC to get the time days are counted in, the system's local time if nil
+
+---@class CordAdvancedServerWakaTimeConfig
+---@field enabled? boolean Whether heartbeats are exported for the files being edited
+---@field api_url? string Base URL of a WakaTime-compatible API, e.g. `http://localhost:3000/api` for Wakapi (plain HTTP only)
+---@field api_key? string API key sent to `api_url`
+---@field file? string File heartbeats are appended to, one JSON object per line, instead of being sent to `api_url`
+---@field batch_size? integer Most heartbeats sent at once
+---@field flush_interval? integer Time in milliseconds after which queued heartbeats are sent even if fewer than `batch_size`
+---@field max_queue? integer Most heartbeats kept while they cannot be sent, the oldest being dropped first
+
+---@class CordAdvancedServerPrivacyWorkspaceRule
+---@field glob string Workspace path glob, `~` expands to the home directory
+---@field activity? table Activity shown instead (`details`, `state`, `assets`, ...), the presence is cleared if nil
+
+---@class CordAdvancedServerPrivacyRedaction
+---@field pattern string Regular expression to redact, case-insensitive if prefixed with `(?i)`
+---@field replacement? string Text the matches are replaced with (defaults to an empty string)
+
+---@class CordAdvancedServerRotationConfig
+---@field enabled? boolean Whether to cycle the presence between active instances instead of showing a single one
+---@field interval? integer Time in milliseconds each active instance is shown for (at least 5000)
 
 ---@class CordAdvancedDiscordConfig
 ---@field pipe_paths? string[] Custom IPC pipe paths to use when connecting to Discord
 ---@field reconnect? CordAdvancedDiscordReconnectConfig Reconnection settings
+---@field handshake? CordAdvancedDiscordHandshakeConfig Handshake settings
+---@field write_timeout? integer Time in milliseconds a write to Discord may take before the connection is considered lost, 0 to disable
 ---@field sync? CordAdvancedSyncConfig Synchronization settings
 
 ---@class CordAdvancedDiscordReconnectConfig
@@ -112,6 +206,11 @@ local logger = require 'cord.api.log'
 ---@field interval? integer Reconnection interval in milliseconds, 0 to disable
 ---@field initial? boolean Whether to reconnect if initial connection fails
 
+---@class CordAdvancedDiscordHandshakeConfig
+---@field version? integer RPC version sent in the handshake
+---@field fields? table<string, string|number|boolean> Extra fields to include in the handshake payload
+---@field timeout? integer Time in milliseconds to wait for Discord to acknowledge the handshake, 0 to disable
+
 ---@class CordAdvancedSyncConfig
 ---@field enabled? boolean Whether synchronization logic is enabled
 ---@field mode? 'periodic'|'defer' Synchronization mode
@@ -161,6 +260,7 @@ local defaults = {
     reset_on_idle = false,
     reset_on_change = false,
     shared = false,
+    scope = 'global',
   },
   idle = {
     enabled = true,
@@ -204,6 +304,7 @@ local defaults = {
     idle_leave = nil,
     workspace_change = nil,
     buf_enter = nil,
+    presence_change = nil,
   },
   extensions = nil,
   advanced = {
@@ -222,6 +323,64 @@ local defaults = {
       pipe_path = nil,
       executable_path = nil,
       timeout = 300000,
+      heartbeat = {
+        enabled = false,
+        interval = 10000,
+        timeout = 30000,
+      },
+      selection = {
+        policy = 'most_recent',
+        priorities = nil,
+        focus_grace = 1000,
+        rotation = {
+          enabled = false,
+          interval = 30000,
+        },
+      },
+      persistence = {
+        enabled = true,
+        freshness = 3600000,
+      },
+      away = {
+        enabled = false,
+        timeout = 900000,
+        activity = nil,
+      },
+      resume = {
+        reconnect = true,
+        timestamps = 'keep',
+      },
+      dnd = {
+        enabled = false,
+        utc_offset = nil,
+        rules = {},
+      },
+      privacy = {
+        workspaces = {},
+        redact = {},
+        private_hosts = {},
+      },
+      confirm_share = {
+        enabled = false,
+      },
+      history = {
+        enabled = false,
+        max_size = 1048576,
+        max_files = 3,
+      },
+      stats = {
+        enabled = false,
+        utc_offset = nil,
+      },
+      wakatime = {
+        enabled = false,
+        api_url = nil,
+        api_key = nil,
+        file = nil,
+        batch_size = 25,
+        flush_interval = 30000,
+        max_queue = 1000,
+      },
     },
     discord = {
       pipe_paths = nil,
@@ -230,6 +389,12 @@ local defaults = {
         interval = 5000,
         initial = true,
       },
+      handshake = {
+        version = 1,
+        fields = nil,
+        timeout = 10000,
+      },
+      write_timeout = 5000,
       sync = {
         enabled = false,
         mode = 'periodic',
@@ -334,6 +499,46 @@ function M.verify(new_config)
     end
   end
 
+  if
+    not vim.tbl_contains({ 'keep', 'shift', 'reset' }, final_config.advanced.server.resume.timestamps)
+  then
+    logger.notify(
+      'Resume timestamps must be either `keep`, `shift` or `reset`',
+      vim.log.levels.ERROR
+    )
+    return
+  end
+
+  local days = { 'mon', 'tue', 'wed', 'thu', 'fri', 'sat', 'sun' }
+  for _, rule in ipairs(final_config.advanced.server.dnd.rules) do
+    for _, time in ipairs { rule.from or '00:00', rule.to or '00:00' } do
+      local hours, minutes = time:match '^(%d%d?):(%d%d)$'
+      if not hours or tonumber(hours) > 23 or tonumber(minutes) > 59 then
+        logger.notify('Invalid do-not-disturb time: `' .. time .. '`', vim.log.levels.ERROR)
+        return
+      end
+    end
+    for _, day in ipairs(rule.days or {}) do
+      if not vim.tbl_contains(days, day) then
+        logger.notify('Invalid do-not-disturb day: `' .. tostring(day) .. '`', vim.log.levels.ERROR)
+        return
+      end
+    end
+  end
+
+  for _, rule in ipairs(final_config.advanced.server.privacy.workspaces) do
+    if type(rule.glob) ~= 'string' then
+      logger.notify('Privacy workspace rules must have a `glob`', vim.log.levels.ERROR)
+      return
+    end
+  end
+  for _, rule in ipairs(final_config.advanced.server.privacy.redact) do
+    if type(rule.pattern) ~= 'string' then
+      logger.notify('Privacy redactions must have a `pattern`', vim.log.levels.ERROR)
+      return
+    end
+  end
+
   if user_config.text and user_config.text.default then
     local default_text = user_config.text.default
     for key, _ in pairs(final_config.text) do
@@ -369,6 +574,7 @@ local rules = {
     ['timestamp.reset_on_idle'] = { 'boolean' },
     ['timestamp.reset_on_change'] = { 'boolean' },
     ['timestamp.shared'] = { 'boolean' },
+    ['timestamp.scope'] = { 'string' },
 
     ['idle'] = { 'table' },
     ['idle.enabled'] = { 'boolean' },
@@ -416,6 +622,7 @@ local rules = {
     ['hooks.idle_enter'] = { 'function', 'table' },
     ['hooks.idle_leave'] = { 'function', 'table' },
     ['hooks.workspace_change'] = { 'function', 'table' },
+    ['hooks.presence_change'] = { 'function', 'table' },
 
     ['advanced'] = { 'table' },
     ['advanced.plugin'] = { 'table' },
@@ -431,12 +638,97 @@ local rules = {
     ['advanced.server.pipe_path'] = { 'string' },
     ['advanced.server.executable_path'] = { 'string' },
     ['advanced.server.timeout'] = { 'number' },
+    ['advanced.server.heartbeat'] = { 'table' },
+    ['advanced.server.heartbeat.enabled'] = { 'boolean' },
+    ['advanced.server.heartbeat.interval'] = { 'number' },
+    ['advanced.server.heartbeat.timeout'] = { 'number' },
+    ['advanced.server.selection'] = { 'table' },
+    ['advanced.server.selection.policy'] = { 'string' },
+    ['advanced.server.selection.priorities'] = { 'table' },
+    ['advanced.server.selection.focus_grace'] = { 'number' },
+    ['advanced.server.selection.rotation'] = { 'table' },
+    ['advanced.server.selection.rotation.enabled'] = { 'boolean' },
+    ['advanced.server.selection.rotation.interval'] = { 'number' },
+    ['advanced.server.persistence'] = { 'table' },
+    ['advanced.server.persistence.enabled'] = { 'boolean' },
+    ['advanced.server.persistence.freshness'] = { 'number' },
+    ['advanced.server.away'] = { 'table' },
+    ['advanced.server.away.enabled'] = { 'boolean' },
+    ['advanced.server.away.timeout'] = { 'number' },
+    ['advanced.server.away.activity'] = { 'table' },
+    ['advanced.server.away.activity.type'] = { 'string' },
+    ['advanced.server.away.activity.status_display_type'] = { 'string' },
+    ['advanced.server.away.activity.details'] = { 'string' },
+    ['advanced.server.away.activity.details_url'] = { 'string' },
+    ['advanced.server.away.activity.state'] = { 'string' },
+    ['advanced.server.away.activity.state_url'] = { 'string' },
+    ['advanced.server.away.activity.assets'] = { 'table' },
+    ['advanced.server.away.activity.assets.large_image'] = { 'string' },
+    ['advanced.server.away.activity.assets.large_text'] = { 'string' },
+    ['advanced.server.away.activity.assets.large_url'] = { 'string' },
+    ['advanced.server.away.activity.assets.small_image'] = { 'string' },
+    ['advanced.server.away.activity.assets.small_text'] = { 'string' },
+    ['advanced.server.away.activity.assets.small_url'] = { 'string' },
+    ['advanced.server.resume'] = { 'table' },
+    ['advanced.server.resume.reconnect'] = { 'boolean' },
+    ['advanced.server.resume.timestamps'] = { 'string' },
+    ['advanced.server.dnd'] = { 'table' },
+    ['advanced.server.dnd.enabled'] = { 'boolean' },
+    ['advanced.server.dnd.utc_offset'] = { 'number' },
+    ['advanced.server.dnd.rules'] = { 'table' },
+    ['advanced.server.dnd.rules.*.days'] = { 'table' },
+    ['advanced.server.dnd.rules.*.days.*'] = { 'string' },
+    ['advanced.server.dnd.rules.*.from'] = { 'string' },
+    ['advanced.server.dnd.rules.*.to'] = { 'string' },
+    ['advanced.server.privacy'] = { 'table' },
+    ['advanced.server.privacy.workspaces'] = { 'table' },
+    ['advanced.server.privacy.workspaces.*.glob'] = { 'string' },
+    ['advanced.server.privacy.workspaces.*.activity'] = { 'table' },
+    ['advanced.server.privacy.workspaces.*.activity.type'] = { 'string' },
+    ['advanced.server.privacy.workspaces.*.activity.status_display_type'] = { 'string' },
+    ['advanced.server.privacy.workspaces.*.activity.details'] = { 'string' },
+    ['advanced.server.privacy.workspaces.*.activity.details_url'] = { 'string' },
+    ['advanced.server.privacy.workspaces.*.activity.state'] = { 'string' },
+    ['advanced.server.privacy.workspaces.*.activity.state_url'] = { 'string' },
+    ['advanced.server.privacy.workspaces.*.activity.assets'] = { 'table' },
+    ['advanced.server.privacy.workspaces.*.activity.assets.large_image'] = { 'string' },
+    ['advanced.server.privacy.workspaces.*.activity.assets.large_text'] = { 'string' },
+    ['advanced.server.privacy.workspaces.*.activity.assets.large_url'] = { 'string' },
+    ['advanced.server.privacy.workspaces.*.activity.assets.small_image'] = { 'string' },
+    ['advanced.server.privacy.workspaces.*.activity.assets.small_text'] = { 'string' },
+    ['advanced.server.privacy.workspaces.*.activity.assets.small_url'] = { 'string' },
+    ['advanced.server.privacy.redact'] = { 'table' },
+    ['advanced.server.privacy.redact.*.pattern'] = { 'string' },
+    ['advanced.server.privacy.redact.*.replacement'] = { 'string' },
+    ['advanced.server.privacy.private_hosts'] = { 'table' },
+    ['advanced.server.confirm_share'] = { 'table' },
+    ['advanced.server.confirm_share.enabled'] = { 'boolean' },
+    ['advanced.server.history'] = { 'table' },
+    ['advanced.server.history.enabled'] = { 'boolean' },
+    ['advanced.server.history.max_size'] = { 'number' },
+    ['advanced.server.history.max_files'] = { 'number' },
+    ['advanced.server.stats'] = { 'table' },
+    ['advanced.server.stats.enabled'] = { 'boolean' },
+    ['advanced.server.stats.utc_offset'] = { 'number' },
+    ['advanced.server.wakatime'] = { 'table' },
+    ['advanced.server.wakatime.enabled'] = { 'boolean' },
+    ['advanced.server.wakatime.api_url'] = { 'string' },
+    ['advanced.server.wakatime.api_key'] = { 'string' },
+    ['advanced.server.wakatime.file'] = { 'string' },
+    ['advanced.server.wakatime.batch_size'] = { 'number' },
+    ['advanced.server.wakatime.flush_interval'] = { 'number' },
+    ['advanced.server.wakatime.max_queue'] = { 'number' },
     ['advanced.discord'] = { 'table' },
     ['advanced.discord.pipe_paths'] = { 'table' },
     ['advanced.discord.reconnect'] = { 'table' },
     ['advanced.discord.reconnect.enabled'] = { 'boolean' },
     ['advanced.discord.reconnect.interval'] = { 'number' },
     ['advanced.discord.reconnect.initial'] = { 'boolean', 'table' },
+    ['advanced.discord.handshake'] = { 'table' },
+    ['advanced.discord.handshake.version'] = { 'number' },
+    ['advanced.discord.handshake.fields'] = { 'table' },
+    ['advanced.discord.handshake.timeout'] = { 'number' },
+    ['advanced.discord.write_timeout'] = { 'number' },
     ['advanced.discord.sync'] = { 'table' },
     ['advanced.discord.sync.enabled'] = { 'boolean' },
     ['advanced.discord.sync.mode'] = { 'string' },
@@ -453,6 +745,10 @@ local rules = {
     ['plugins'] = true,
     ['advanced.discord.pipe_paths'] = true,
     ['advanced.workspace.root_markers'] = true,
+    ['advanced.server.dnd.rules'] = true,
+    ['advanced.server.privacy.workspaces'] = true,
+    ['advanced.server.privacy.redact'] = true,
+    ['advanced.server.privacy.private_hosts'] = true,
   },
   skip_subtrees = {
     ['extensions'] = true,
@@ -463,6 +759,8 @@ local rules = {
   },
   dict_paths = {
     ['assets'] = { 'string', 'table' },
+    ['advanced.discord.handshake.fields'] = { 'string', 'number', 'boolean' },
+    ['advanced.server.selection.priorities'] = { 'number' },
     ['variables'] = { 'string', 'function' },
     ['extensions'] = { 'boolean', 'table' },
     ['plugins'] = { 'boolean', 'table' },
@@ -522,6 +820,7 @@ M.validate = function(user_config)
           (rules.array_paths[prefix] and type(k) == 'number')
           or (rules.array_paths[base_path] and type(k) == 'number')
           or (rules.dict_paths[base_path] and type(k) == 'string')
+          or (rules.dict_paths[prefix] and type(k) == 'string')
           or is_extension_config
         ) and not utils.is_valid_path(rules.fields, rules.dict_paths, full_path)
       then
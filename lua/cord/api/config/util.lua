@@ -38,7 +38,13 @@ M.is_valid_path = function(validation_rules, dict_paths, path)
       },
       '.'
     )
-    return validation_rules[wildcard_path] ~= nil
+    if validation_rules[wildcard_path] ~= nil then return true end
+
+    local indexed_path = table.concat(
+      vim.tbl_map(function(part) return part:match '^%d+$' and '*' or part end, parts),
+      '.'
+    )
+    return validation_rules[indexed_path] ~= nil
   end
   return false
 end
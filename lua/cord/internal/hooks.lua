@@ -21,6 +21,8 @@ local hooks = {
   workspace_change = {},
 
   buf_enter = {},
+
+  presence_change = {},
 }
 
 ---Constants for common priority levels
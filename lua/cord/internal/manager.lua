@@ -677,6 +677,7 @@ function EventHandler:on_focus_gained()
   mgr.is_focused = true
   if mgr.opts then mgr.opts.is_focused = true end
   logger.trace 'EventHandler.on_focus_gained'
+  mgr.tx:focus(true)
 
   async.run(function() hooks.run('focus_gained', mgr) end)
 
@@ -690,6 +691,7 @@ function EventHandler:on_focus_lost()
   mgr.is_focused = false
   if mgr.opts then mgr.opts.is_focused = false end
   logger.trace 'EventHandler.on_focus_lost'
+  mgr.tx:focus(false)
 
   async.run(function() hooks.run('focus_lost', mgr) end)
 end
@@ -812,6 +814,8 @@ function ActivityManager:run()
   logger.debug 'ActivityManager.run'
   self.is_ready = true
   self.idle_timer:record_activity()
+  -- A freshly started instance is almost always the one in front
+  if self.is_focused then self.tx:focus(true) end
 
   async.run(function()
     hooks.run('ready', self)
@@ -892,7 +896,14 @@ function ActivityManager:set_activity(activity, force)
     return
   end
 
-  self.tx:update_activity(activity)
+  local file = vim.api.nvim_buf_get_name(0)
+  self.tx:update_activity(
+    activity,
+    nil,
+    self.opts and self.opts.workspace_dir,
+    file ~= '' and file or nil,
+    self.opts and self.opts.filetype
+  )
 end
 
 ---@param force? boolean
@@ -4,6 +4,57 @@ local logger = require 'cord.api.log'
 
 local M = {}
 
+local function get_tty()
+  local tty = os.getenv 'TTY'
+  if tty then return tty end
+  if vim.fn.has 'linux' == 0 then return end
+
+  -- The UI process holds the terminal, the embedded server only has pipes
+  for _, pid in ipairs { vim.uv.os_getppid(), vim.uv.os_getpid() } do
+    local path = vim.uv.fs_readlink('/proc/' .. pid .. '/fd/0')
+    if path and path:match '^/dev/' and path ~= '/dev/null' then return path end
+  end
+end
+
+-- Start time of the process, field 22 of its stat, which tells it apart from
+-- a later process reusing its PID
+local function get_start_time(pid)
+  if vim.fn.has 'linux' == 0 then return end
+
+  local file = io.open('/proc/' .. pid .. '/stat')
+  if not file then return end
+  local stat = file:read '*a'
+  file:close()
+
+  -- The command name may contain spaces, fields are counted from its end
+  local fields = vim.split(stat:match '.*%)%s*(.*)' or '', '%s+', { trimempty = true })
+  return tonumber(fields[20])
+end
+
+---Describes this editor instance to the server, see `ClientInfo`.
+---@param plugin_version? string
+---@return table
+function M.client_info(plugin_version)
+  local multiplexer, pane
+  if os.getenv 'TMUX' then
+    multiplexer, pane = 'tmux', os.getenv 'TMUX_PANE'
+  elseif os.getenv 'ZELLIJ' then
+    multiplexer, pane = 'zellij', os.getenv 'ZELLIJ_PANE_ID'
+  end
+
+  return {
+    name = 'nvim',
+    version = tostring(vim.version()),
+    plugin_version = plugin_version,
+    pid = vim.uv.os_getpid(),
+    start_time = get_start_time(vim.uv.os_getpid()),
+    tty = get_tty(),
+    hostname = vim.uv.os_gethostname(),
+    multiplexer = multiplexer,
+    pane = pane,
+  }
+end
+
 function M:connect(path, retried)
   return async.wrap(function()
     if M.is_updating then
@@ -55,8 +106,81 @@ function M:run()
   return async.wrap(function()
     M.tx = require('cord.server.ipc.sender').new(M.client)
     M.rx = require('cord.server.ipc.receiver').new(M.client)
+    M:stop_heartbeat()
+    M.server_info = nil
+    M.session_id = nil
+    M.presence = nil
+    M.rx.on_response = function(event) M.tx:on_response(event) end
+    M.rx:register(
+      'hello',
+      false,
+      vim.schedule_wrap(function(data)
+        M.server_info = data
+        M.session_id = data.session_id
+        M.tx.features = data.features or {}
+        M:start_heartbeat()
+        logger.debug(
+          function()
+            return 'Server: v'
+              .. tostring(data.version)
+              .. ', protocol '
+              .. tostring(data.protocol_version)
+              .. ', features: '
+              .. table.concat(data.features or {}, ', ')
+          end
+        )
+      end)
+    )
+
+    M.rx:register(
+      'presence_changed',
+      false,
+      vim.schedule_wrap(function(data)
+        local session = data.session ~= vim.NIL and data.session or nil
+        M.presence = {
+          activity = data.activity ~= vim.NIL and data.activity or nil,
+          session = session,
+          reason = data.reason,
+          is_own = session ~= nil and session == M.session_id,
+        }
+        logger.trace(
+          function()
+            return 'presence_changed: reason='
+              .. tostring(data.reason)
+              .. ', session='
+              .. tostring(session)
+          end
+        )
+        async.run(function() require('cord.internal.hooks').run('presence_change', M.presence) end)
+      end)
+    )
+
+    M.rx:register(
+      'error',
+      false,
+      vim.schedule_wrap(function(data)
+        if data.code == 'incompatible_protocol' then
+          self.status = 'disconnected'
+          M.client:close()
+          logger.error(data.message .. '. Run `:Cord update` to install a compatible server')
+          return
+        end
+        if data.code == 'unshareable_workspace' then
+          logger.warn(data.message)
+          return
+        end
+
+        logger.error('Server error: ' .. tostring(data.message))
+      end)
+    )
+
+    -- Older servers reject `hello` as an unknown message and keep going,
+    -- so initialize is sent right away instead of waiting for the reply.
+    logger.debug 'Server: sending hello event'
+    M.tx:hello()
     logger.debug 'Server: sending initialize event'
-    M.tx:initialize(config.get())
+    local metadata = require('cord.server.update').compatible_metadata():await()
+    M.tx:initialize(config.get(), M.client_info(metadata and metadata.version))
     logger.debug 'Server: registering ready handler'
     M.rx:register(
       'status_update',
@@ -90,6 +214,8 @@ function M:run()
             M.client.on_close = vim.schedule_wrap(function()
               logger.debug 'client: on_close'
               M.status = 'disconnected'
+              M.tx:reject_pending 'Connection to server closed'
+              M:stop_heartbeat()
               M.manager:cleanup()
 
               if not self.is_shut_down then
@@ -133,6 +259,44 @@ function M:run()
       end)
     )
 
+    M.rx:register(
+      'handoff',
+      false,
+      vim.schedule_wrap(function()
+        logger.debug 'Server handed over to a new executable; reconnecting'
+        -- The connection closes once the previous server exits, which must not
+        -- tear down the session on the new one
+        M.client.on_close = nil
+        M:stop_heartbeat()
+        if M.manager then M.manager:cleanup() end
+        M:initialize()
+      end)
+    )
+
+    M.rx:register(
+      'confirm_share',
+      false,
+      vim.schedule_wrap(function(data)
+        local workspace = data.workspace
+        vim.ui.select({ 'Allow', 'Deny' }, {
+          prompt = 'Show workspace ' .. workspace .. ' on Discord?',
+        }, function(choice)
+          if not choice then
+            logger.notify(
+              'Workspace hidden until you run `:Cord share allow` or `:Cord share deny`',
+              vim.log.levels.INFO
+            )
+            return
+          end
+
+          async.run(function()
+            local _, err = M.tx:share_decision(choice == 'Allow', workspace):await()
+            if err then logger.warn('Failed to save sharing decision: ' .. tostring(err)) end
+          end)
+        end)
+      end)
+    )
+
     logger.debug 'Server initialized; starting receiver'
     M.rx:run()
   end)()
@@ -159,7 +323,34 @@ function M:initialize()
   end)
 end
 
+function M:supports(feature) return M.tx ~= nil and M.tx:supports(feature) end
+
+function M:start_heartbeat()
+  M:stop_heartbeat()
+  local heartbeat = config.advanced.server.heartbeat
+  if not heartbeat.enabled or not M:supports 'heartbeat' then return end
+
+  logger.trace(function() return 'Starting heartbeat; interval=' .. heartbeat.interval end)
+  M.heartbeat_timer = vim.uv.new_timer()
+  M.heartbeat_timer:start(
+    heartbeat.interval,
+    heartbeat.interval,
+    vim.schedule_wrap(function()
+      if not M.client or M.client:is_closing() then return M:stop_heartbeat() end
+      M.tx:heartbeat()
+    end)
+  )
+end
+
+function M:stop_heartbeat()
+  if not M.heartbeat_timer then return end
+  M.heartbeat_timer:stop()
+  if not M.heartbeat_timer:is_closing() then M.heartbeat_timer:close() end
+  M.heartbeat_timer = nil
+end
+
 function M:cleanup()
+  M:stop_heartbeat()
   if self.client then self.client:close() end
 end
 
@@ -85,7 +85,11 @@ function Handler:run()
       end
 
       logger.trace(function() return 'Receiver:decoded event: type=' .. tostring(event.type) end)
-      self:on_event(event.type, event.data)
+      if event.id and self.on_response then
+        self.on_response(event)
+      else
+        self:on_event(event.type, event.data)
+      end
 
       ::continue::
     end
@@ -1,12 +1,36 @@
 local mpack = vim.mpack
+local Future = require 'cord.core.async.future'
 local logger = require 'cord.api.log'
 
 local Producer = {}
 local mt = { __index = Producer }
 
+---Minutes to add to UTC to get the local time
+local function utc_offset()
+  local now = os.time()
+  local utc = os.date('!*t', now)
+  utc.isdst = os.date('*t', now).isdst
+  return math.floor(os.difftime(now, os.time(utc)) / 60)
+end
+
+Producer.PROTOCOL_VERSION = 1
+Producer.CAPABILITIES = {
+  'log',
+  'log_batch',
+  'status_update',
+  'restart',
+  'error',
+  'presence_changed',
+  'handoff',
+  'confirm_share',
+}
+
 function Producer.new(client)
   local self = setmetatable({}, mt)
   self.client = client
+  self.features = {}
+  self.next_id = 0
+  self.pending = {}
   logger.trace 'Sender.new: client created'
   return self
 end
@@ -17,16 +41,136 @@ function Producer:send_event(type, data)
   self.client:write(mpack.encode { type = type, data = data })
 end
 
-function Producer:initialize(config)
+function Producer:supports(feature) return vim.tbl_contains(self.features, feature) end
+
+---Sends an event and returns a Future that resolves with the data of the
+---server's `ack`, or rejects with the message of its `error`. Servers without
+---request ids resolve immediately with nil.
+---@param type string
+---@param data any
+---@return Future
+function Producer:request(type, data)
+  return Future.new(function(resolve, reject)
+    if self.client:is_closing() then return reject 'Connection to server closed' end
+    if not self:supports 'request_id' then
+      self:send_event(type, data)
+      return resolve(nil)
+    end
+
+    self.next_id = self.next_id + 1
+    local id = self.next_id
+    self.pending[id] = { resolve = resolve, reject = reject }
+    logger.trace(
+      function() return 'Sender:request: type=' .. tostring(type) .. ', id=' .. tostring(id) end
+    )
+    self.client:write(mpack.encode { type = type, data = data, id = id })
+  end)
+end
+
+function Producer:on_response(event)
+  local pending = self.pending[event.id]
+  if not pending then return end
+  self.pending[event.id] = nil
+
+  local data = event.data ~= vim.NIL and event.data or nil
+  if event.type == 'ack' then
+    pending.resolve(data)
+  else
+    pending.reject(data and data.message or 'Request failed')
+  end
+end
+
+function Producer:reject_pending(reason)
+  local pending = self.pending
+  self.pending = {}
+  for _, p in pairs(pending) do
+    p.reject(reason)
+  end
+end
+
+function Producer:hello()
+  logger.debug 'Sender:hello called'
+  self:send_event('hello', {
+    protocol_version = Producer.PROTOCOL_VERSION,
+    capabilities = Producer.CAPABILITIES,
+  })
+end
+
+---@param config CordConfig
+---@param client? table Metadata describing this editor instance
+function Producer:initialize(config, client)
   logger.debug 'Sender:initialize called'
   self:send_event('initialize', {
+    client = client,
     log_level = config.log_level,
     timestamp = {
       shared = config.timestamp.shared,
+      scope = config.timestamp.scope,
     },
     advanced = {
+      server = {
+        heartbeat = {
+          enabled = config.advanced.server.heartbeat.enabled,
+          timeout = config.advanced.server.heartbeat.timeout,
+        },
+        selection = {
+          policy = config.advanced.server.selection.policy,
+          priorities = config.advanced.server.selection.priorities,
+          focus_grace = config.advanced.server.selection.focus_grace,
+          rotation = {
+            enabled = config.advanced.server.selection.rotation.enabled,
+            interval = config.advanced.server.selection.rotation.interval,
+          },
+        },
+        away = {
+          enabled = config.advanced.server.away.enabled,
+          timeout = config.advanced.server.away.timeout,
+          activity = config.advanced.server.away.activity,
+        },
+        resume = {
+          reconnect = config.advanced.server.resume.reconnect,
+          timestamps = config.advanced.server.resume.timestamps,
+        },
+        dnd = {
+          enabled = config.advanced.server.dnd.enabled,
+          utc_offset = config.advanced.server.dnd.utc_offset or utc_offset(),
+          rules = config.advanced.server.dnd.rules,
+        },
+        confirm_share = {
+          enabled = config.advanced.server.confirm_share.enabled,
+        },
+        history = {
+          enabled = config.advanced.server.history.enabled,
+          max_size = config.advanced.server.history.max_size,
+          max_files = config.advanced.server.history.max_files,
+        },
+        stats = {
+          enabled = config.advanced.server.stats.enabled,
+          utc_offset = config.advanced.server.stats.utc_offset or utc_offset(),
+        },
+        wakatime = {
+          enabled = config.advanced.server.wakatime.enabled,
+          api_url = config.advanced.server.wakatime.api_url,
+          api_key = config.advanced.server.wakatime.api_key,
+          file = config.advanced.server.wakatime.file,
+          batch_size = config.advanced.server.wakatime.batch_size,
+          flush_interval = config.advanced.server.wakatime.flush_interval,
+          max_queue = config.advanced.server.wakatime.max_queue,
+        },
+        privacy = {
+          workspaces = config.advanced.server.privacy.workspaces,
+          redact = config.advanced.server.privacy.redact,
+          private_hosts = config.advanced.server.privacy.private_hosts,
+        },
+      },
       discord = {
         pipe_paths = config.advanced.discord.pipe_paths,
+        handshake = {
+          version = config.advanced.discord.handshake.version,
+          fields = config.advanced.discord.handshake.fields,
+          timeout = config.advanced.discord.handshake.timeout,
+        },
+        write_timeout = config.advanced.discord.write_timeout,
         sync = {
           enabled = config.advanced.discord.sync.enabled,
           mode = config.advanced.discord.sync.mode,
@@ -39,18 +183,98 @@ function Producer:initialize(config)
   })
 end
 
-function Producer:update_activity(activity, force)
-  self:send_event('update_activity', { activity = activity, force = force })
+function Producer:update_activity(activity, force, workspace, file, filetype)
+  self:send_event('update_activity', {
+    activity = activity,
+    force = force,
+    workspace = workspace,
+    file = file,
+    filetype = filetype,
+  })
 end
 
 function Producer:clear_activity(force) self:send_event('clear_activity', force or false) end
 
 function Producer:disconnect() self:send_event 'disconnect' end
 
+---@param focused boolean
+function Producer:focus(focused)
+  if not self:supports 'focus' then return end
+  self:send_event(focused and 'focus_gained' or 'focus_lost')
+end
+
+function Producer:heartbeat() self:send_event 'heartbeat' end
+
 function Producer:shutdown() self:send_event 'shutdown' end
 
 function Producer:restart() self:send_event 'restart' end
 
 function Producer:reconnect() self:send_event 'reconnect' end
 
+---@param ttl? integer Milliseconds after which the pin is removed
+---@return Future
+function Producer:pin(ttl) return self:request('pin_session', ttl and { ttl = ttl } or vim.NIL) end
+
+---@return Future
+function Producer:unpin() return self:request('unpin', vim.NIL) end
+
+---@return Future
+function Producer:pause() return self:request('pause', vim.NIL) end
+
+---@return Future
+function Producer:resume() return self:request('resume', vim.NIL) end
+
+---@param allow boolean Whether the workspace may be shown on Discord
+---@param workspace? string Workspace path, this instance's workspace if nil
+---@return Future
+function Producer:share_decision(allow, workspace)
+  return self:request('share_decision', { allow = allow, workspace = workspace })
+end
+
+---@class CordOverrideOpts
+---@field priority? integer Layers with a higher priority are applied last
+---@field ttl? integer Milliseconds after which the layer is removed
+---@field end_time? integer Unix timestamp (seconds) at which the layer is removed
+
+---@param id string Identifies the layer; pushing the same id again replaces it
+---@param activity table Activity fields to show instead of the displayed session's
+---@param opts? CordOverrideOpts
+---@return Future
+function Producer:push_override(id, activity, opts)
+  opts = opts or {}
+  return self:request('push_override', {
+    id = id,
+    activity = activity,
+    priority = opts.priority,
+    ttl = opts.ttl,
+    ['end'] = opts.end_time,
+  })
+end
+
+---@param id string
+---@return Future
+function Producer:pop_override(id) return self:request('pop_override', { id = id }) end
+
+---@class CordHistoryFilter
+---@field session? integer Only entries of this session
+---@field workspace? string Only entries whose workspace matches this glob
+---@field since? integer Unix timestamp (milliseconds) before which entries are left out
+---@field limit? integer Most entries to return, 20 if nil, 0 for all
+
+---@param filter? CordHistoryFilter
+---@return Future
+function Producer:history(filter) return self:request('history', filter or vim.NIL) end
+
+---@class CordStatsFilter
+---@field days? integer Days to sum up, counting back from today, 7 if nil, 0 for all
+---@field workspace? string Only time spent in workspaces matching this glob
+
+---@param filter? CordStatsFilter
+---@return Future
+function Producer:stats(filter) return self:request('stats', filter or vim.NIL) end
+
+---@param topic? 'sessions'|'activity'|'status'|'config'|'state'
+---@return Future
+function Producer:query(topic) return self:request('query', topic or 'state') end
+
 return Producer
@@ -2,7 +2,9 @@ local async = require 'cord.core.async'
 local Future = require 'cord.core.async.future'
 local M = {}
 
-M.spawn = async.wrap(function(config, pipe_path)
+---Spawns the server executable. With `handoff`, it takes over from the server
+---already running on `pipe_path` instead of failing.
+M.spawn = async.wrap(function(config, pipe_path, handoff)
   return Future.new(function(resolve, reject)
     local logger = require 'cord.api.log'
     local update_strategy = config.advanced.server.update
@@ -25,21 +27,27 @@ M.spawn = async.wrap(function(config, pipe_path)
       return resolve(false, false)
     end
 
+    local args = {
+      '-p',
+      pipe_path,
+      '-c',
+      client_id,
+      '-t',
+      config.advanced.server.timeout,
+      '-r',
+      config.advanced.discord.reconnect.enabled and config.advanced.discord.reconnect.interval
+        or 0,
+      '-s',
+      config.advanced.server.persistence.enabled and config.advanced.server.persistence.freshness
+        or 0,
+    }
+    if config.advanced.discord.reconnect.initial then table.insert(args, '-i') end
+    if handoff then table.insert(args, '--handoff') end
+
     local process = require 'cord.core.uv.process'
     process.spawn_daemon {
       cmd = exec_path,
-      args = {
-        '-p',
-        pipe_path,
-        '-c',
-        client_id,
-        '-t',
-        config.advanced.server.timeout,
-        '-r',
-        config.advanced.discord.reconnect.enabled and config.advanced.discord.reconnect.interval
-          or 0,
-        config.advanced.discord.reconnect.initial and '-i' or nil,
-      },
+      args = args,
       on_stdout = function(data)
         if data:match 'Ready' then
           logger.debug 'Spawn: server signaled Ready'
@@ -33,6 +33,50 @@ local request_shutdown = async.wrap(function(pipe_path)
   return true
 end)
 
+local function download(url, path)
+  return require('cord.core.uv.process').spawn {
+    cmd = 'curl',
+    args = {
+      url,
+      '--create-dirs',
+      '--fail',
+      '--location',
+      '--remote-time',
+      '--silent',
+      '--show-error',
+      '-o',
+      path,
+      '-H',
+      'Accept: application/octet-stream',
+    },
+  }
+end
+
+---Downloads the executable next to the running one, moves it in place and
+---has it take over from the running server, which keeps the presence up.
+---Only servers on Unix support this; on Windows the server is shut down and
+---the new executable spawned instead.
+local hand_off = async.wrap(function(url, executable_path)
+  local fs = require 'cord.core.uv.fs'
+  local staged_path = executable_path .. '.new'
+
+  local res = download(url, staged_path):unwrap()
+  if res.code ~= 0 then
+    if res.stderr and res.stderr ~= '' then logger.debug('curl stderr: ' .. res.stderr) end
+    error('Failed to download executable; code: ' .. res.code .. ', path: ' .. url, 0)
+  end
+  fs.chmod(staged_path, '755'):unwrap()
+  fs.rename(staged_path, executable_path):unwrap()
+
+  local server = require 'cord.server'
+  local config = require 'cord.api.config'
+  local pipe_path = config.advanced.server.pipe_path or require('cord.core.util').get_pipe_path()
+  -- The running server tells its clients to reconnect once the new one took
+  -- over, which must not be refused as an update in progress
+  server.is_updating = false
+  require('cord.server.spawn').spawn(config.get(), pipe_path, true):unwrap()
+end)
+
 M.install = async.wrap(function()
   local server = require 'cord.server'
   if server.is_updating then return end
@@ -330,7 +374,6 @@ M.fetch_executable = async.wrap(function(tag, pipe_path)
 
   local executable_path =
     require('cord.server.fs').get_executable_path(require('cord.api.config').get())
-  local process = require 'cord.core.uv.process'
 
   local schedule_fetch = vim.schedule_wrap(function(tag)
     local base_url
@@ -357,23 +400,7 @@ M.fetch_executable = async.wrap(function(tag, pipe_path)
           request_shutdown(pipe_path):unwrap()
         end
 
-        process
-          .spawn({
-            cmd = 'curl',
-            args = {
-              url,
-              '--create-dirs',
-              '--fail',
-              '--location',
-              '--remote-time',
-              '--silent',
-              '--show-error',
-              '-o',
-              executable_path,
-              '-H',
-              'Accept: application/octet-stream',
-            },
-          })
+        download(url, executable_path)
           :next(function(res)
             if res.code ~= 0 then
               server.is_updating = false
@@ -397,6 +424,20 @@ M.fetch_executable = async.wrap(function(tag, pipe_path)
       end)
     end
 
+    if server:supports 'handoff' and not server.client:is_closing() then
+      async.run(function()
+        local _, err = hand_off(url, executable_path):await()
+        if not err then
+          logger.notify('Successfully updated executable', vim.log.levels.INFO)
+          return
+        end
+
+        server.is_updating = false
+        logger.error(err)
+      end)
+      return
+    end
+
     if server.manager then server.manager:cleanup() end
     if server.tx and server.client and not server.client:is_closing() then
       if server.client.on_close then server.client.on_close() end
@@ -1,10 +1,12 @@
 use std::env;
 
+use super::command::Command;
 use super::error::CliError;
 use crate::echo;
 
 const DEFAULT_TIMEOUT: u64 = 60000;
 const DEFAULT_RECONNECT_INTERVAL: u64 = 0;
+const DEFAULT_STATE_FRESHNESS: u64 = 0;
 #[cfg(target_os = "windows")]
 const DEFAULT_PIPE_NAME: &str = "\\\\.\\pipe\\cord-ipc";
 #[cfg(not(target_os = "windows"))]
@@ -17,6 +19,14 @@ pub struct Args {
     pub timeout: u64,
     pub reconnect_interval: u64,
     pub initial_reconnect: bool,
+    /// Milliseconds a persisted server state stays valid for, 0 to not
+    /// persist it.
+    pub state_freshness: u64,
+    /// Take over from the server running on the pipe instead of failing
+    /// because it is running.
+    pub handoff: bool,
+    /// Request to send to a running server instead of starting one.
+    pub command: Option<Command>,
 }
 
 impl Args {
@@ -28,8 +38,77 @@ impl Args {
         let mut timeout = None;
         let mut reconnect_interval = None;
         let mut initial_reconnect = false;
+        let mut handoff = false;
+        let mut state_freshness = None;
+        let mut command = None;
+        let mut ttl = None;
 
         let mut i = 1;
+        if let Some(name) = args.get(1).filter(|a| !a.starts_with('-')) {
+            command = Some(match name.as_str() {
+                "pin" => Command::Pin {
+                    session: None,
+                    ttl: None,
+                },
+                "unpin" => Command::Unpin,
+                "aggregate" => Command::Aggregate {
+                    enabled: true,
+                    details: None,
+                    state: None,
+                },
+                "pause" => Command::Pause,
+                "resume" => Command::Resume,
+                "history" => Command::History {
+                    session: None,
+                    workspace: None,
+                    since: None,
+                    limit: None,
+                },
+                "stats" => Command::Stats {
+                    days: None,
+                    workspace: None,
+                },
+                other => {
+                    return Err(CliError::Unknown(other.to_string()).into());
+                }
+            });
+            i += 1;
+
+            if let Some(Command::Pin { session, .. }) = &mut command
+                && let Some(id) = args.get(i).filter(|a| !a.starts_with('-'))
+            {
+                match id.parse() {
+                    Ok(id) => *session = Some(id),
+                    _ => {
+                        return Err(CliError::Invalid(
+                            "pin",
+                            "session id must be a valid u32",
+                        )
+                        .into());
+                    }
+                }
+                i += 1;
+            }
+
+            if let Some(Command::Aggregate { enabled, .. }) = &mut command
+                && let Some(toggle) =
+                    args.get(i).filter(|a| !a.starts_with('-'))
+            {
+                match toggle.as_str() {
+                    "on" => *enabled = true,
+                    "off" => *enabled = false,
+                    _ => {
+                        return Err(CliError::Invalid(
+                            "aggregate",
+                            "expected `on` or `off`",
+                        )
+                        .into());
+                    }
+                }
+                i += 1;
+            }
+        }
+
         while i < args.len() {
             match args[i].as_str() {
                 "--version" | "-v" => {
@@ -91,24 +170,188 @@ impl Args {
                         i += 2;
                     }
                 }
+                "--state-freshness" | "-s" => {
+                    if i + 1 < args.len() {
+                        match args[i + 1].parse() {
+                            Ok(t) => state_freshness = Some(t),
+                            _ => {
+                                return Err(CliError::Invalid(
+                                    "--state-freshness",
+                                    "state freshness must be a valid u64",
+                                )
+                                .into());
+                            }
+                        }
+                        i += 2;
+                    }
+                }
                 "--initial-reconnect" | "-i" => {
                     initial_reconnect = true;
                     i += 1;
                 }
+                "--handoff" => {
+                    if cfg!(target_os = "windows") {
+                        return Err(CliError::Invalid(
+                            "--handoff",
+                            "handoff is not supported on Windows",
+                        )
+                        .into());
+                    }
+                    handoff = true;
+                    i += 1;
+                }
+                "--ttl" if matches!(command, Some(Command::Pin { .. })) => {
+                    match args.get(i + 1).and_then(|d| parse_duration(d)) {
+                        Some(d) => ttl = Some(d),
+                        None => {
+                            return Err(CliError::Invalid(
+                                "--ttl",
+                                "ttl must be a duration such as 90, 90s, 30m or 2h",
+                            )
+                            .into());
+                        }
+                    }
+                    i += 2;
+                }
+                "--details" | "--state"
+                    if matches!(command, Some(Command::Aggregate { .. })) =>
+                {
+                    if let Some(Command::Aggregate { details, state, .. }) =
+                        &mut command
+                    {
+                        let (flag, target) = match args[i].as_str() {
+                            "--details" => ("--details", details),
+                            _ => ("--state", state),
+                        };
+                        let template =
+                            args.get(i + 1).ok_or(CliError::Missing(flag))?;
+                        *target = Some(template.clone());
+                    }
+                    i += 2;
+                }
+                "--session"
+                    if matches!(command, Some(Command::History { .. })) =>
+                {
+                    if let Some(Command::History { session, .. }) = &mut command
+                    {
+                        match args.get(i + 1).and_then(|id| id.parse().ok()) {
+                            Some(id) => *session = Some(id),
+                            None => {
+                                return Err(CliError::Invalid(
+                                    "--session",
+                                    "session id must be a valid u32",
+                                )
+                                .into());
+                            }
+                        }
+                    }
+                    i += 2;
+                }
+                "--workspace"
+                    if matches!(
+                        command,
+                        Some(Command::History { .. } | Command::Stats { .. })
+                    ) =>
+                {
+                    if let Some(
+                        Command::History { workspace, .. }
+                        | Command::Stats { workspace, .. },
+                    ) = &mut command
+                    {
+                        let glob = args
+                            .get(i + 1)
+                            .ok_or(CliError::Missing("--workspace"))?;
+                        *workspace = Some(glob.clone());
+                    }
+                    i += 2;
+                }
+                "--days" if matches!(command, Some(Command::Stats { .. })) => {
+                    if let Some(Command::Stats { days, .. }) = &mut command {
+                        match args.get(i + 1).and_then(|n| n.parse().ok()) {
+                            Some(n) => *days = Some(n),
+                            None => {
+                                return Err(CliError::Invalid(
+                                    "--days",
+                                    "days must be a valid u64",
+                                )
+                                .into());
+                            }
+                        }
+                    }
+                    i += 2;
+                }
+                "--since"
+                    if matches!(command, Some(Command::History { .. })) =>
+                {
+                    if let Some(Command::History { since, .. }) = &mut command {
+                        match args.get(i + 1).and_then(|d| parse_duration(d)) {
+                            Some(d) => *since = Some(d),
+                            None => {
+                                return Err(CliError::Invalid(
+                                    "--since",
+                                    "since must be a duration such as 90, 90s, 30m or 2h",
+                                )
+                                .into());
+                            }
+                        }
+                    }
+                    i += 2;
+                }
+                "--limit"
+                    if matches!(command, Some(Command::History { .. })) =>
+                {
+                    if let Some(Command::History { limit, .. }) = &mut command {
+                        match args.get(i + 1).and_then(|n| n.parse().ok()) {
+                            Some(n) => *limit = Some(n),
+                            None => {
+                                return Err(CliError::Invalid(
+                                    "--limit",
+                                    "limit must be a valid u64",
+                                )
+                                .into());
+                            }
+                        }
+                    }
+                    i += 2;
+                }
                 other => {
                     return Err(CliError::Unknown(other.to_string()).into());
                 }
             }
         }
 
+        if let Some(Command::Pin { ttl: pin_ttl, .. }) = &mut command {
+            *pin_ttl = ttl;
+        }
+        let client_id = match command {
+            Some(_) => client_id.unwrap_or_default(),
+            None => client_id.ok_or(CliError::Missing("--client-id"))?,
+        };
+
         Ok(Args {
             pipe_name: pipe_name
                 .unwrap_or_else(|| DEFAULT_PIPE_NAME.to_string()),
-            client_id: client_id.ok_or(CliError::Missing("--client-id"))?,
+            client_id,
             timeout: timeout.unwrap_or(DEFAULT_TIMEOUT),
             reconnect_interval: reconnect_interval
                 .unwrap_or(DEFAULT_RECONNECT_INTERVAL),
             initial_reconnect,
+            state_freshness: state_freshness.unwrap_or(DEFAULT_STATE_FRESHNESS),
+            handoff,
+            command,
         })
     }
 }
+
+/// Parses a duration given in seconds, optionally suffixed with `s`, `m` or
+/// `h`, into milliseconds.
+fn parse_duration(input: &str) -> Option<u64> {
+    let (value, unit) = match input.char_indices().last()? {
+        (i, 's') => (&input[..i], 1000),
+        (i, 'm') => (&input[..i], 60_000),
+        (i, 'h') => (&input[..i], 3_600_000),
+        _ => (input, 1000),
+    };
+
+    value.parse::<u64>().ok()?.checked_mul(unit)
+}
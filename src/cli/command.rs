@@ -0,0 +1,494 @@
+use std::collections::HashMap;
+use std::io::{Read, Write};
+#[cfg(target_os = "windows")]
+use std::{io, ptr, time::Duration};
+
+use crate::echoln;
+#[cfg(target_os = "windows")]
+use crate::ipc::bindings::{
+    BOOL, CancelIoEx, CloseHandle, ERROR_IO_PENDING, FILE_FLAG_OVERLAPPED,
+    GetLastError, GetOverlappedResult, HANDLE, INFINITE, Overlapped, ReadFile,
+    WAIT_TIMEOUT, WaitForSingleObject, WriteFile,
+};
+use crate::protocol::msgpack::{
+    MsgPack, Serialize, SerializeFn, SerializeState, Value, ValueRef,
+};
+use crate::session::stats::format_duration;
+use crate::util::now;
+
+/// How long to wait for the server to answer a command.
+const RESPONSE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+const REQUEST_ID: u64 = 1;
+
+/// A one-shot request sent to a running server instead of starting one.
+#[derive(Debug)]
+pub enum Command {
+    /// Pins a session, the displayed one if none is given.
+    Pin {
+        session: Option<u32>,
+        ttl: Option<u64>,
+    },
+    Unpin,
+    /// Summarizes every session instead of showing one, or stops doing so.
+    Aggregate {
+        enabled: bool,
+        details: Option<String>,
+        state: Option<String>,
+    },
+    /// Hides the presence until resumed.
+    Pause,
+    Resume,
+    /// Lists what Discord was recently sent, newest first.
+    History {
+        session: Option<u32>,
+        /// Glob the workspace must match.
+        workspace: Option<String>,
+        /// Milliseconds to look back.
+        since: Option<u64>,
+        limit: Option<u64>,
+    },
+    /// Reports the time tracked per workspace, filetype and day.
+    Stats {
+        /// Days to include, 0 for all of them.
+        days: Option<u64>,
+        /// Glob the workspace must match.
+        workspace: Option<String>,
+    },
+}
+
+impl Command {
+    fn request(&self) -> Request {
+        match self {
+            Command::Pin { session, ttl } => {
+                let mut fields = vec![(
+                    "session",
+                    match session {
+                        Some(id) => Field::UInteger(*id as u64),
+                        None => Field::Str("active"),
+                    },
+                )];
+                if let Some(ttl) = ttl {
+                    fields.push(("ttl", Field::UInteger(*ttl)));
+                }
+                Request::new("pin_session", fields)
+            }
+            Command::Unpin => Request::new("unpin", Vec::new()),
+            Command::Aggregate { enabled: false, .. } => {
+                Request::without_data("set_aggregate")
+            }
+            Command::Aggregate { details, state, .. } => {
+                let mut fields = Vec::new();
+                if let Some(details) = details {
+                    fields.push(("details", Field::String(details.clone())));
+                }
+                if let Some(state) = state {
+                    fields.push(("state", Field::String(state.clone())));
+                }
+                Request::new("set_aggregate", fields)
+            }
+            Command::Pause => Request::without_data("pause"),
+            Command::Resume => Request::without_data("resume"),
+            Command::History {
+                session,
+                workspace,
+                since,
+                limit,
+            } => {
+                let mut fields = Vec::new();
+                if let Some(id) = session {
+                    fields.push(("session", Field::UInteger(*id as u64)));
+                }
+                if let Some(workspace) = workspace {
+                    fields
+                        .push(("workspace", Field::String(workspace.clone())));
+                }
+                if let Some(since) = since {
+                    let now = now().as_millis() as u64;
+                    fields.push((
+                        "since",
+                        Field::UInteger(now.saturating_sub(*since)),
+                    ));
+                }
+                if let Some(limit) = limit {
+                    fields.push(("limit", Field::UInteger(*limit)));
+                }
+                Request::new("history", fields)
+            }
+            Command::Stats { days, workspace } => {
+                let mut fields = Vec::new();
+                if let Some(days) = days {
+                    fields.push(("days", Field::UInteger(*days)));
+                }
+                if let Some(workspace) = workspace {
+                    fields
+                        .push(("workspace", Field::String(workspace.clone())));
+                }
+                Request::new("stats", fields)
+            }
+        }
+    }
+
+    fn report(&self, data: Value) {
+        let shown = data
+            .as_map()
+            .and_then(|data| data.get("activity"))
+            .and_then(|activity| activity.as_map())
+            .and_then(|activity| {
+                activity
+                    .get("details")
+                    .or_else(|| activity.get("state"))
+                    .and_then(|v| v.as_str())
+                    .map(str::to_string)
+            });
+
+        let verb = match self {
+            Command::Pin { .. } => "Pinned",
+            Command::Unpin => "Unpinned",
+            Command::Aggregate { enabled: true, .. } => "Aggregating sessions",
+            Command::Aggregate { enabled: false, .. } => "Stopped aggregating",
+            Command::Pause => "Paused",
+            Command::Resume => "Resumed",
+            Command::History { .. } => return report_history(data),
+            Command::Stats { .. } => return report_stats(data),
+        };
+        match shown {
+            Some(shown) => echoln!("{}, showing: {}", verb, shown),
+            None => echoln!("{}", verb),
+        }
+    }
+
+    /// Sends the command to the server listening on `pipe_name` and prints
+    /// its answer.
+    pub fn run(self, pipe_name: &str) -> crate::Result<()> {
+        let mut stream = connect(pipe_name)?;
+        let data = self.request().send(&mut stream)?;
+        self.report(data);
+
+        Ok(())
+    }
+}
+
+/// Prints one line per history entry, oldest last.
+fn report_history(data: Value) {
+    let entries = data
+        .take_map()
+        .and_then(|mut data| data.remove("entries"))
+        .and_then(|entries| entries.take_array())
+        .unwrap_or_default();
+    if entries.is_empty() {
+        return echoln!("No history entries");
+    }
+
+    let now = now().as_millis() as u64;
+    for entry in entries.iter().filter_map(|entry| entry.as_map()) {
+        let field = |key| entry.get(key).and_then(|v| v.as_str());
+        let age = entry
+            .get("timestamp")
+            .and_then(|v| v.as_uinteger())
+            .map_or_else(
+                || "?".to_string(),
+                |at| format_age(now - at.min(now)),
+            );
+        let session = entry
+            .get("session")
+            .and_then(|v| v.as_uinteger())
+            .map_or_else(|| "-".to_string(), |id| id.to_string());
+
+        let shown = match entry.get("activity").and_then(|v| v.as_map()) {
+            Some(activity) => {
+                let text = |key| activity.get(key).and_then(|v| v.as_str());
+                let mut shown = [text("details"), text("state")]
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<_>>()
+                    .join(" | ");
+                if activity.get("is_idle").and_then(|v| v.as_bool())
+                    == Some(true)
+                {
+                    shown.push_str(" (idle)");
+                }
+                shown
+            }
+            None => "(cleared)".to_string(),
+        };
+
+        echoln!(
+            "{:>8} ago  {:>4}  {}  {}",
+            age,
+            session,
+            field("workspace").unwrap_or("-"),
+            shown.replace(['\n', '\r'], " ")
+        );
+    }
+}
+
+/// Prints the tracked time today and in total, followed by a breakdown per
+/// workspace, filetype and day.
+fn report_stats(data: Value) {
+    let Some(mut data) = data.take_map() else {
+        return echoln!("No statistics");
+    };
+    let time = |data: &HashMap<String, Value>, key: &str| {
+        format_duration(
+            data.get(key)
+                .and_then(|v| v.as_uinteger())
+                .unwrap_or_default(),
+        )
+    };
+
+    echoln!("Today: {}", time(&data, "today"));
+    echoln!("Total: {}", time(&data, "total"));
+    for (key, title) in [
+        ("workspaces", "By workspace"),
+        ("filetypes", "By filetype"),
+        ("days", "By day"),
+    ] {
+        let totals = data
+            .remove(key)
+            .and_then(|v| v.take_array())
+            .unwrap_or_default();
+        if totals.is_empty() {
+            continue;
+        }
+
+        echoln!("\n{}:", title);
+        for total in totals.iter().filter_map(|total| total.as_map()) {
+            let key = match total.get("key").and_then(|v| v.as_str()) {
+                Some("") | None => "-",
+                Some(key) => key,
+            };
+            echoln!("{:>10}  {}", time(total, "time"), key);
+        }
+    }
+}
+
+/// Formats milliseconds as the largest two units, e.g. `3h 05m`.
+fn format_age(ms: u64) -> String {
+    let secs = ms / 1000;
+    match secs {
+        0..60 => format!("{}s", secs),
+        60..3600 => format!("{}m {:02}s", secs / 60, secs % 60),
+        3600..86400 => format!("{}h {:02}m", secs / 3600, secs % 3600 / 60),
+        _ => format!("{}d {:02}h", secs / 86400, secs % 86400 / 3600),
+    }
+}
+
+pub trait Stream: Read + Write {}
+impl<T: Read + Write> Stream for T {}
+
+#[cfg(not(target_os = "windows"))]
+pub fn connect(pipe_name: &str) -> crate::Result<Box<dyn Stream>> {
+    let stream = std::os::unix::net::UnixStream::connect(pipe_name)?;
+    stream.set_read_timeout(Some(RESPONSE_TIMEOUT))?;
+
+    Ok(Box::new(stream))
+}
+
+#[cfg(target_os = "windows")]
+pub fn connect(pipe_name: &str) -> crate::Result<Box<dyn Stream>> {
+    use std::os::windows::fs::OpenOptionsExt;
+
+    let pipe = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(FILE_FLAG_OVERLAPPED)
+        .open(pipe_name)?;
+
+    Ok(Box::new(Pipe(pipe)))
+}
+
+/// A pipe opened for overlapped I/O, so that reads give up after
+/// [`RESPONSE_TIMEOUT`] like the socket does on Unix.
+#[cfg(target_os = "windows")]
+struct Pipe(std::fs::File);
+
+#[cfg(target_os = "windows")]
+impl Pipe {
+    /// Runs the `ReadFile` or `WriteFile` call made by `start` and waits up
+    /// to `timeout` for it to complete, returning the bytes transferred.
+    fn overlapped(
+        &self,
+        timeout: Option<Duration>,
+        start: impl FnOnce(HANDLE, *mut Overlapped) -> BOOL,
+    ) -> io::Result<usize> {
+        use std::os::windows::io::AsRawHandle;
+
+        let handle = self.0.as_raw_handle();
+        let mut overlapped = Overlapped::default();
+        if overlapped.h_event.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        let timeout = timeout.map_or(INFINITE, |timeout| {
+            timeout.as_millis().min(INFINITE as u128 - 1) as u32
+        });
+
+        let mut transferred = 0;
+        let result = unsafe {
+            if start(handle, &raw mut overlapped) == 0
+                && GetLastError() != ERROR_IO_PENDING
+            {
+                Err(io::Error::last_os_error())
+            } else if WaitForSingleObject(overlapped.h_event, timeout)
+                == WAIT_TIMEOUT
+            {
+                // The buffer is only released once the cancelled operation
+                // completed
+                CancelIoEx(handle, &mut overlapped);
+                GetOverlappedResult(
+                    handle,
+                    &mut overlapped,
+                    &mut transferred,
+                    1,
+                );
+                Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "The server did not answer in time",
+                ))
+            } else if GetOverlappedResult(
+                handle,
+                &mut overlapped,
+                &mut transferred,
+                1,
+            ) == 0
+            {
+                Err(io::Error::last_os_error())
+            } else {
+                Ok(transferred as usize)
+            }
+        };
+        unsafe { CloseHandle(overlapped.h_event) };
+
+        result
+    }
+}
+
+#[cfg(target_os = "windows")]
+impl Read for Pipe {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = buf.len().min(u32::MAX as usize) as u32;
+        let result =
+            self.overlapped(Some(RESPONSE_TIMEOUT), |handle, o| unsafe {
+                ReadFile(handle, buf.as_mut_ptr(), len, ptr::null_mut(), o)
+            });
+
+        match result {
+            // The server closed its end
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(0),
+            result => result,
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+impl Write for Pipe {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = buf.len().min(u32::MAX as usize) as u32;
+        self.overlapped(None, |handle, o| unsafe {
+            WriteFile(handle, buf.as_ptr(), len, ptr::null_mut(), o)
+        })
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum Field {
+    Str(&'static str),
+    String(String),
+    UInteger(u64),
+}
+
+#[derive(Debug)]
+pub struct Request {
+    ty: &'static str,
+    /// Sent as `nil` when `None`.
+    fields: Option<Vec<(&'static str, Field)>>,
+}
+
+impl Request {
+    pub fn new(ty: &'static str, fields: Vec<(&'static str, Field)>) -> Self {
+        Self {
+            ty,
+            fields: Some(fields),
+        }
+    }
+
+    pub fn without_data(ty: &'static str) -> Self {
+        Self { ty, fields: None }
+    }
+
+    /// Sends the request without waiting for an answer.
+    pub fn post(&self, stream: &mut impl Write) -> crate::Result<()> {
+        stream.write_all(&MsgPack::serialize(self)?)?;
+
+        Ok(())
+    }
+
+    /// Sends the request and returns the data of the server's `ack`, or the
+    /// message of its `error`.
+    pub fn send(&self, stream: &mut impl Stream) -> crate::Result<Value> {
+        self.post(stream)?;
+
+        let mut len = [0u8; 4];
+        loop {
+            stream.read_exact(&mut len)?;
+            let mut buf = vec![0u8; u32::from_be_bytes(len) as usize];
+            stream.read_exact(&mut buf)?;
+
+            let Some(mut message) = MsgPack::deserialize(&buf)?.take_map()
+            else {
+                continue;
+            };
+            if message.get("id").and_then(|v| v.as_uinteger())
+                != Some(REQUEST_ID)
+            {
+                continue;
+            }
+
+            let ty = message.remove("type").and_then(|v| v.take_string());
+            let data = message.remove("data").unwrap_or(Value::Nil);
+            return match ty.as_deref() {
+                Some("ack") => Ok(data),
+                _ => Err(data
+                    .take_map()
+                    .and_then(|mut data| data.remove("message"))
+                    .and_then(|v| v.take_string())
+                    .unwrap_or_else(|| "Request failed".to_string())
+                    .into()),
+            };
+        }
+    }
+}
+
+impl Serialize for Request {
+    fn serialize<'a>(
+        &'a self,
+        f: SerializeFn<'a>,
+        state: &mut SerializeState,
+    ) -> crate::Result<()> {
+        let data = match &self.fields {
+            Some(fields) => ValueRef::Map(
+                fields
+                    .iter()
+                    .map(|(key, field)| {
+                        let value = match field {
+                            Field::Str(s) => ValueRef::Str(s),
+                            Field::String(s) => ValueRef::Str(s),
+                            Field::UInteger(n) => ValueRef::UInteger(*n),
+                        };
+                        (*key, value)
+                    })
+                    .collect::<HashMap<_, _>>(),
+            ),
+            None => ValueRef::Nil,
+        };
+
+        f("type", ValueRef::Str(self.ty), state)?;
+        f("data", data, state)?;
+        f("id", ValueRef::UInteger(REQUEST_ID), state)?;
+
+        Ok(())
+    }
+}
@@ -1,2 +1,3 @@
 pub mod args;
+pub mod command;
 pub mod error;
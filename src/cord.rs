@@ -1,23 +1,75 @@
 use std::collections::VecDeque;
+use std::io::ErrorKind;
 use std::sync::Arc;
 use std::sync::mpsc::{self, Receiver, Sender};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
+use crate::handoff::{self, Handoff, HandoffState};
 use crate::ipc::discord::client::Connection;
 use crate::ipc::pipe::PipeServerImpl;
+use crate::ipc::pipe::platform::client::PipeClient;
 use crate::ipc::pipe::platform::server::PipeServer;
 use crate::messages::events::event::{Event, EventContext, OnEvent};
+use crate::messages::events::local::ClockJumpEvent;
+use crate::messages::events::server::status_update::Status;
 use crate::messages::events::server::{LogEvent, ServerEvent};
 use crate::messages::message::Message;
 use crate::presence::manager::ActivityManager;
 use crate::protocol::msgpack::Serialize;
 use crate::session::SessionManager;
+use crate::session::sharing::Sharing;
+use crate::session::state::{PersistedState, StateFile, state_dir};
+use crate::session::stats::Stats;
+use crate::session::timestamps::TimestampScope;
+use crate::types::config::ResumeConfig;
 use crate::types::reconnect::ReconnectState;
+use crate::util::clock::{self, ClockWatch};
 use crate::util::lockfile::ServerLock;
 use crate::util::logger::{self, LOGGER, LogLevel, Logger};
-use crate::{debug, trace};
+use crate::util::now;
+use crate::{debug, local_event, trace};
 
 pub const VERSION: &str = env!("CORD_VERSION");
+/// Version of the message protocol spoken over the server pipe.
+pub const PROTOCOL_VERSION: u32 = 1;
+/// Oldest protocol version the server still accepts in `hello`.
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+/// Optional features advertised to clients in reply to `hello`, see
+/// `features`.
+const FEATURES: &[&str] = &[
+    "handshake_config",
+    "write_timeout",
+    "request_id",
+    "query",
+    "presence_changed",
+    "heartbeat",
+    "focus",
+    "pin",
+    "override",
+    "aggregate",
+    "handoff",
+    "pause",
+    "confirm_share",
+    "history",
+    "stats",
+];
+/// The optional features supported on this platform. Handoff is left out on
+/// Windows, where updates shut the server down and spawn the new one.
+pub fn features() -> Vec<&'static str> {
+    FEATURES
+        .iter()
+        .copied()
+        .filter(|f| !cfg!(target_os = "windows") || *f != "handoff")
+        .collect()
+}
+
+/// How often the event loop is sent a `TickEvent`.
+const TICK_INTERVAL: Duration = Duration::from_secs(1);
+/// Smallest gap between the wall and monotonic clocks that is treated as a
+/// suspend or clock jump.
+const CLOCK_JUMP_THRESHOLD: Duration = Duration::from_secs(30);
+/// How long a handoff waits for the previous server to exit.
+const HANDOFF_TIMEOUT: Duration = Duration::from_secs(5);
 
 /// Core application managing configuration, sessions, IPC with Discord, and logging.
 ///
@@ -28,6 +80,8 @@ pub const VERSION: &str = env!("CORD_VERSION");
 /// * `pipe`: Server-side communication pipe.
 /// * `tx`, `rx`: Channels for message passing.
 /// * `logger`: Logs application events.
+/// * `state`: File the server state is persisted to, if enabled.
+/// * `handoff`: Connection of the server taking over from this one, if any.
 /// * `_lock`: Ensures single instance operation.
 pub struct Cord {
     pub config: Config,
@@ -38,20 +92,51 @@ pub struct Cord {
     pub rx: Receiver<Message>,
     pub log_buffer: VecDeque<LogEvent>,
     pub reconnect_state: ReconnectState,
-    _lock: ServerLock,
+    pub state: Option<StateFile>,
+    pub handoff: Option<(u32, PipeClient)>,
+    _lock: Option<ServerLock>,
 }
 
 impl Cord {
     /// Initializes the Cord application.
     pub fn new(config: Config) -> crate::Result<Self> {
-        let lock = ServerLock::new()?;
+        // When taking over, the lock is only acquired once the previous
+        // server exits
+        let lock = if config.handoff {
+            None
+        } else {
+            Some(ServerLock::new()?)
+        };
 
         let (tx, rx) = mpsc::channel::<Message>();
         let session_manager = Arc::new(SessionManager::default());
         let _ = logger::LOGGER.set(Logger::new(tx.clone(), LogLevel::Trace));
         debug!("cord server v{}", VERSION);
 
-        let activity_manager = ActivityManager::new(config.client_id, vec![]);
+        let state = (config.state_freshness > 0)
+            .then(StateFile::default_path)
+            .flatten()
+            .map(|path| {
+                StateFile::new(
+                    path,
+                    Duration::from_millis(config.state_freshness),
+                )
+            });
+        match state_dir().map(|dir| Sharing::load(dir.join("workspaces"))) {
+            Some(Ok(sharing)) => {
+                *session_manager.sharing.write().unwrap() = sharing
+            }
+            Some(Err(e)) => debug!("Failed to load workspace decisions: {}", e),
+            None => trace!("No state directory for workspace decisions"),
+        }
+        match state_dir().map(|dir| Stats::load(dir.join("stats"))) {
+            Some(Ok(stats)) => *session_manager.stats.write().unwrap() = stats,
+            Some(Err(e)) => debug!("Failed to load time statistics: {}", e),
+            None => trace!("No state directory for time statistics"),
+        }
+
+        let activity_manager =
+            ActivityManager::new(config.client_id, vec![], tx.clone());
 
         let server = PipeServer::new(
             &config.server_pipe,
@@ -59,7 +144,7 @@ impl Cord {
             Arc::clone(&session_manager),
         );
 
-        Ok(Cord {
+        let cord = Cord {
             config,
             session_manager,
             activity_manager,
@@ -68,18 +153,156 @@ impl Cord {
             rx,
             log_buffer: VecDeque::with_capacity(100),
             reconnect_state: ReconnectState::default(),
+            state,
+            handoff: None,
             _lock: lock,
-        })
+        };
+        // The state is handed over instead when taking over
+        if !cord.config.handoff {
+            cord.restore_state();
+        }
+
+        Ok(cord)
+    }
+
+    /// Restores the state saved by the previous server, if recent enough.
+    fn restore_state(&self) {
+        let Some(file) = &self.state else {
+            return;
+        };
+        match file.load() {
+            Ok(Some(state)) => {
+                debug!("Restoring state saved at {}", state.saved_at);
+                state.restore(&self.session_manager);
+            }
+            Ok(None) => trace!("No recent state to restore"),
+            Err(e) => debug!("Failed to load server state: {}", e),
+        }
     }
 
     /// Runs the application.
     pub fn run(&mut self) -> crate::Result<()> {
-        self.pipe.start()?;
+        if self.config.handoff {
+            self.take_over()?;
+        } else {
+            self.pipe.start()?;
+        }
+        self.start_ticks();
         self.start_event_loop()?;
 
         Ok(())
     }
 
+    /// Takes over from the server running on the pipe. Its state is restored
+    /// and Discord is connected to before clients are sent over, so that the
+    /// presence stays up. Replaces the server if it cannot hand over.
+    fn take_over(&mut self) -> crate::Result<()> {
+        let mut handoff = match Handoff::request(&self.config.server_pipe) {
+            Ok(handoff) => handoff,
+            Err(e) => {
+                debug!("Server did not hand over: {}", e);
+                return self.replace();
+            }
+        };
+
+        debug!("Taking over from the running server");
+        self.pipe.start_staged()?;
+        let HandoffState {
+            state,
+            discord_pipe,
+        } = std::mem::take(&mut handoff.state);
+        state.restore(&self.session_manager);
+        if let Some(pipe) = discord_pipe
+            && let Err(e) = self.connect_discord(&pipe)
+        {
+            debug!("Failed to connect to Discord before taking over: {}", e);
+        }
+
+        self.pipe.take_over()?;
+        handoff.complete()?;
+        match ServerLock::wait(HANDOFF_TIMEOUT) {
+            Ok(lock) => self._lock = Some(lock),
+            Err(e) => debug!("Previous server did not exit: {}", e),
+        }
+        // The previous server saved its time statistics when exiting
+        if let Err(e) = self.session_manager.stats.write().unwrap().reload() {
+            debug!("Failed to reload time statistics: {}", e);
+        }
+
+        Ok(())
+    }
+
+    /// Starts in place of the server running on the pipe, if any, which is
+    /// shut down first. Its clients reconnect as they would after a restart
+    /// and the state it saved on exit is restored.
+    fn replace(&mut self) -> crate::Result<()> {
+        let lock = match ServerLock::new() {
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                debug!("Shutting down the running server");
+                if let Err(e) = handoff::shut_down(&self.config.server_pipe) {
+                    debug!("Failed to shut down the running server: {}", e);
+                }
+                ServerLock::wait(HANDOFF_TIMEOUT)?
+            }
+            lock => lock?,
+        };
+        self._lock = Some(lock);
+        self.restore_state();
+        self.pipe.start()?;
+
+        Ok(())
+    }
+
+    /// Connects to the Discord pipe the previous server was connected to.
+    /// Discord being ready is only handled once the event loop runs, after
+    /// the previous server let go of its connection, so that the restored
+    /// activity is not shown while both servers are connected.
+    fn connect_discord(&mut self, pipe: &str) -> crate::Result<()> {
+        let mut client = self.activity_manager.client.write().unwrap();
+        if !client.try_connect(pipe)? {
+            return Err("Discord pipe is gone".into());
+        }
+        client.pipe_path = Some(pipe.to_string());
+        client.status = Status::Connected;
+        client.handshake()?;
+        client.start_read_thread(self.tx.clone())?;
+
+        Ok(())
+    }
+
+    /// Spawns a thread that periodically sends the event loop a tick, which
+    /// checks for dead sessions among other things, and tells it when the machine resumed from suspend or
+    /// the clock jumped. Nothing is sent while there are no sessions, so the
+    /// idle shutdown timeout still applies.
+    fn start_ticks(&self) {
+        let tx = self.tx.clone();
+        let session_manager = Arc::clone(&self.session_manager);
+        std::thread::spawn(move || {
+            let mut clock = ClockWatch::new(CLOCK_JUMP_THRESHOLD);
+            loop {
+                std::thread::sleep(TICK_INTERVAL);
+                let jump = clock.tick(clock::awake(), now());
+                if session_manager.sessions.read().unwrap().is_empty() {
+                    continue;
+                }
+                if let Some(jump) = jump
+                    && tx
+                        .send(local_event!(
+                            0,
+                            ClockJump,
+                            ClockJumpEvent::new(jump)
+                        ))
+                        .is_err()
+                {
+                    break;
+                }
+                if tx.send(local_event!(0, Tick)).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+
     /// Starts the event loop.
     pub fn start_event_loop(&mut self) -> crate::Result<()> {
         loop {
@@ -87,10 +310,11 @@ impl Cord {
                 .rx
                 .recv_timeout(Duration::from_millis(self.config.timeout))
             {
-                if let Err(e) = msg.event.on_event(&mut EventContext {
-                    cord: self,
-                    client_id: msg.client_id,
-                }) {
+                if let Err(e) = msg.event.on_event(&mut EventContext::new(
+                    self,
+                    msg.client_id,
+                    msg.request_id,
+                )) {
                     let sessions_empty = self
                         .session_manager
                         .sessions
@@ -117,10 +341,12 @@ impl Cord {
                                 Event::Server(sev)
                                     if matches!(sev, ServerEvent::Log(_)) =>
                                 {
-                                    let _ = sev.on_event(&mut EventContext {
-                                        cord: self,
-                                        client_id: msg.client_id,
-                                    });
+                                    let _ =
+                                        sev.on_event(&mut EventContext::new(
+                                            self,
+                                            msg.client_id,
+                                            None,
+                                        ));
                                 }
                                 _ => {}
                             }
@@ -145,9 +371,38 @@ impl Cord {
         Ok(())
     }
 
+    /// Saves the server state if persistence is enabled. Unchanged state is
+    /// only written again once in a while.
+    pub fn save_state(&mut self) {
+        if let Some(file) = &mut self.state
+            && let Err(e) = file.save(PersistedState::capture(
+                &self.session_manager,
+                self.activity_manager.displayed(),
+            ))
+        {
+            debug!("Failed to save server state: {}", e);
+        }
+    }
+
+    /// Writes the time statistics if they changed, right away if `force`
+    /// is set or else once in a while.
+    pub fn save_stats(&self, force: bool) {
+        let mut stats = self.session_manager.stats.write().unwrap();
+        if let Err(e) = stats.save(Instant::now(), force) {
+            debug!("Failed to save time statistics: {}", e);
+        }
+    }
+
     /// Cleans up before shutdown.
     pub fn cleanup(&mut self) {
         self.reconnect_state.cancel();
+        self.save_state();
+        self.save_stats(true);
+        if let Some(exporter) =
+            self.session_manager.wakatime.write().unwrap().take()
+        {
+            exporter.stop();
+        }
 
         if let Ok(mut client) = self.activity_manager.client.write() {
             client.close();
@@ -171,18 +426,29 @@ pub struct Config {
     pub timeout: u64,
     pub reconnect_interval: u64,
     pub initial_reconnect: bool,
-    pub shared_timestamps: bool,
+    /// Scope of shared timestamps, `None` if they are not shared.
+    pub shared_timestamps: Option<TimestampScope>,
+    /// Milliseconds a persisted state stays valid for, 0 if the state is
+    /// not persisted.
+    pub state_freshness: u64,
+    /// Whether to take over from the server running on the pipe.
+    pub handoff: bool,
+    /// What to do after a suspend or clock jump, set by clients.
+    pub resume: ResumeConfig,
 }
 
 impl Config {
     /// Creates a new configuration.
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         server_pipe: String,
         client_id: u64,
         timeout: u64,
         reconnect_interval: u64,
         initial_reconnect: bool,
-        shared_timestamps: bool,
+        shared_timestamps: Option<TimestampScope>,
+        state_freshness: u64,
+        handoff: bool,
     ) -> Self {
         Self {
             server_pipe,
@@ -191,6 +457,9 @@ impl Config {
             reconnect_interval,
             initial_reconnect,
             shared_timestamps,
+            state_freshness,
+            handoff,
+            resume: ResumeConfig::default(),
         }
     }
 }
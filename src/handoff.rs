@@ -0,0 +1,112 @@
+use crate::cli::command::{self, Field, Request, Stream};
+use crate::cord::VERSION;
+use crate::protocol::msgpack::{
+    Deserialize, Serialize, SerializeFn, SerializeState, Value, ValueRef,
+};
+use crate::session::state::PersistedState;
+
+/// What a running server hands over to the server replacing it.
+#[derive(Debug, Default)]
+pub struct HandoffState {
+    pub state: PersistedState,
+    /// The Discord pipe the server was connected to, so that the new server
+    /// connects to the same Discord client.
+    pub discord_pipe: Option<String>,
+}
+
+/// A handoff requested from the server running on a pipe. The running
+/// server keeps serving its clients until the handoff is completed.
+pub struct Handoff {
+    stream: Box<dyn Stream>,
+    pub state: HandoffState,
+}
+
+impl Handoff {
+    /// Asks the server running on `pipe_name` for its state.
+    pub fn request(pipe_name: &str) -> crate::Result<Self> {
+        let mut stream = command::connect(pipe_name)?;
+        let data =
+            Request::new("handoff", vec![("version", Field::Str(VERSION))])
+                .send(&mut stream)?;
+
+        Ok(Self {
+            stream,
+            state: HandoffState::deserialize(data)?,
+        })
+    }
+
+    /// Tells the running server that its pipe has been taken over, upon
+    /// which it sends its clients over and exits.
+    pub fn complete(mut self) -> crate::Result<()> {
+        Request::without_data("handoff_complete").post(&mut self.stream)
+    }
+}
+
+/// Asks the server running on `pipe_name` to shut down, for servers that
+/// cannot hand over.
+pub fn shut_down(pipe_name: &str) -> crate::Result<()> {
+    let mut stream = command::connect(pipe_name)?;
+    Request::without_data("shutdown").post(&mut stream)
+}
+
+impl Serialize for HandoffState {
+    fn serialize<'a>(
+        &'a self,
+        f: SerializeFn<'a>,
+        state: &mut SerializeState,
+    ) -> crate::Result<()> {
+        f("state", ValueRef::Object(&self.state), state)?;
+        if let Some(pipe) = &self.discord_pipe {
+            f("discord_pipe", ValueRef::Str(pipe), state)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Deserialize for HandoffState {
+    fn deserialize(input: Value) -> crate::Result<Self> {
+        let mut map = input.take_map().ok_or("Invalid handoff state")?;
+
+        Ok(Self {
+            state: PersistedState::deserialize(
+                map.remove("state")
+                    .ok_or("Missing or invalid 'state' field")?,
+            )?,
+            discord_pipe: map
+                .remove("discord_pipe")
+                .and_then(|v| v.take_string()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::msgpack::MsgPack;
+
+    #[test]
+    fn handoff_state_round_trips() {
+        let state = PersistedState {
+            saved_at: 1000,
+            timestamps: vec![("global".to_string(), 100)],
+            workspace: Some("/a".to_string()),
+            ..Default::default()
+        };
+
+        for discord_pipe in [None, Some("/run/user/1000/discord-ipc-0")] {
+            let handoff = HandoffState {
+                state: state.clone(),
+                discord_pipe: discord_pipe.map(str::to_string),
+            };
+            let bytes = MsgPack::serialize(&handoff).unwrap();
+            let decoded = HandoffState::deserialize(
+                MsgPack::deserialize(&bytes).unwrap(),
+            )
+            .unwrap();
+
+            assert_eq!(decoded.state, state);
+            assert_eq!(decoded.discord_pipe.as_deref(), discord_pipe);
+        }
+    }
+}
@@ -14,6 +14,8 @@ mod windows {
     pub const INVALID_HANDLE_VALUE: HANDLE = -1isize as HANDLE;
     pub const ERROR_PIPE_CONNECTED: DWORD = 535;
     pub const ERROR_IO_PENDING: DWORD = 997;
+    pub const WAIT_TIMEOUT: DWORD = 0x00000102;
+    pub const INFINITE: DWORD = 0xFFFFFFFF;
     pub const PIPE_ACCESS_DUPLEX: DWORD = 0x00000003;
     pub const FILE_FLAG_OVERLAPPED: DWORD = 0x40000000;
     pub const PIPE_TYPE_MESSAGE: DWORD = 0x00000004;
@@ -109,8 +111,30 @@ mod windows {
             lpNumberOfBytesTransferred: *mut DWORD,
             bWait: BOOL,
         ) -> BOOL;
+
+        pub fn WaitForSingleObject(
+            hHandle: HANDLE,
+            dwMilliseconds: DWORD,
+        ) -> DWORD;
+
+        pub fn CancelIoEx(hFile: HANDLE, lpOverlapped: *mut Overlapped)
+        -> BOOL;
+
+        pub fn QueryUnbiasedInterruptTime(UnbiasedTime: *mut u64) -> BOOL;
     }
 }
 
 #[cfg(target_os = "windows")]
 pub use windows::*;
+
+#[cfg(unix)]
+mod unix {
+    pub const SHUT_RD: i32 = 0;
+
+    unsafe extern "C" {
+        pub fn shutdown(socket: i32, how: i32) -> i32;
+    }
+}
+
+#[cfg(unix)]
+pub use unix::*;
@@ -1,13 +1,17 @@
-use std::sync::Arc;
+use std::collections::VecDeque;
 use std::sync::atomic::AtomicBool;
 use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
 use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 
-use crate::ipc::discord::error::DiscordError;
+use crate::ipc::discord::error::{DiscordError, is_write_timeout};
+use crate::ipc::discord::handshake::Handshake;
 use crate::messages::events::server::status_update::Status;
 use crate::messages::message::Message;
 use crate::presence::packet::Packet;
 use crate::protocol::json::Json;
+use crate::types::config::{DEFAULT_WRITE_TIMEOUT, HandshakeConfig};
 use crate::{debug, trace};
 
 /// Manages the connection to Discord for sending and receiving data.
@@ -17,6 +21,12 @@ use crate::{debug, trace};
 /// * `pipe`: The communication pipe (platform-specific).
 /// * `pid`: Process ID.
 /// * `is_ready`: Indicates if the client is ready.
+/// * `pipe_path`: The Discord pipe the client is connected to, if any.
+/// * `handshake`: Handshake parameters and the deadline for Discord's READY.
+/// * `write_timeout`: How long a single frame may take to be written, in
+///   milliseconds. A write that misses it tears the connection down.
+/// * `updates`: When recent presence updates were sent, to keep track of
+///   Discord's rate limit.
 pub struct RichClient {
     pub client_id: u64,
     pub pipe_paths: Vec<String>,
@@ -28,12 +38,20 @@ pub struct RichClient {
     pub write_pipe: Option<std::os::unix::net::UnixStream>,
     pub pid: u32,
     pub is_ready: Arc<AtomicBool>,
+    pub pipe_path: Option<String>,
     pub thread_handle: Option<JoinHandle<()>>,
     pub is_reconnecting: bool,
+    pub handshake: HandshakeConfig,
+    pub write_timeout: u64,
     /// Managed externally.
     pub status: Status,
+    pub updates: Mutex<VecDeque<Instant>>,
 }
 
+/// Discord accepts this many presence updates per [`UPDATE_WINDOW`].
+pub const UPDATE_LIMIT: usize = 5;
+pub const UPDATE_WINDOW: Duration = Duration::from_secs(20);
+
 /// Defines methods for connecting and closing the client.
 pub trait Connection {
     /// Connects to the given pipe.
@@ -59,12 +77,38 @@ impl RichClient {
             write_pipe: None,
             pid: std::process::id(),
             is_ready: Arc::new(AtomicBool::new(false)),
+            pipe_path: None,
             thread_handle: None,
             is_reconnecting: false,
+            handshake: HandshakeConfig::default(),
+            write_timeout: DEFAULT_WRITE_TIMEOUT,
             status: Status::Disconnected,
+            updates: Mutex::new(VecDeque::with_capacity(UPDATE_LIMIT)),
         }
     }
 
+    /// How many presence updates can still be sent without exceeding
+    /// Discord's rate limit.
+    pub fn update_budget(&self) -> usize {
+        let mut updates = self.updates.lock().unwrap();
+        while updates
+            .front()
+            .is_some_and(|at| at.elapsed() >= UPDATE_WINDOW)
+        {
+            updates.pop_front();
+        }
+
+        UPDATE_LIMIT.saturating_sub(updates.len())
+    }
+
+    fn record_update(&self) {
+        let mut updates = self.updates.lock().unwrap();
+        if updates.len() >= UPDATE_LIMIT {
+            updates.pop_front();
+        }
+        updates.push_back(Instant::now());
+    }
+
     /// Establishes a connection with Discord.
     pub fn connect(&mut self) -> crate::Result<()> {
         debug!("Attempting to connect to Discord IPC");
@@ -73,6 +117,7 @@ impl RichClient {
                 trace!("Trying Discord IPC pipe: {}", pipe);
                 if self.try_connect(&pipe)? {
                     debug!("Connected to Discord IPC pipe: {}", pipe);
+                    self.pipe_path = Some(pipe);
                     return Ok(());
                 }
             }
@@ -85,6 +130,7 @@ impl RichClient {
                 trace!("Trying custom Discord IPC pipe: {}", pipe);
                 if self.try_connect(pipe)? {
                     debug!("Connected to custom Discord IPC pipe: {}", pipe);
+                    self.pipe_path = Some(pipe.clone());
                     self.pipe_paths = pipes;
                     return Ok(());
                 }
@@ -98,26 +144,28 @@ impl RichClient {
     }
 
     /// Sends a handshake packet to Discord.
+    ///
+    /// The read thread fails the connection if Discord does not answer with
+    /// READY within `handshake.timeout` milliseconds.
     pub fn handshake(&self) -> crate::Result<()> {
         debug!(
-            "Sending handshake to Discord with client_id={}",
-            self.client_id
+            "Sending handshake to Discord with client_id={}, v={}",
+            self.client_id, self.handshake.version
         );
-        self.write(
-            0,
-            Some(
-                format!("{{\"v\": 1,\"client_id\":\"{}\"}}", self.client_id)
-                    .as_bytes(),
-            ),
-        )
+        let handshake = Handshake::new(self.client_id, &self.handshake);
+        let encoded = Json::serialize(&handshake)?;
+
+        self.write(0, Some(encoded.as_bytes()))
     }
 
     /// Updates the client's rich presence.
     pub fn update(&self, packet: &Packet) -> crate::Result<()> {
         trace!("Updating Discord rich presence");
         let encoded = Json::serialize(packet)?;
+        self.record_update();
 
         match self.write(1, Some(encoded.as_bytes())) {
+            Err(e) if is_write_timeout(&e) => Err(e),
             Err(_) => Err("The connection to Discord was lost".into()),
             _ => Ok(()),
         }
@@ -128,8 +176,10 @@ impl RichClient {
         debug!("Clearing Discord rich presence");
         let packet = Packet::empty();
         let encoded = Json::serialize(&packet)?;
+        self.record_update();
 
         match self.write(1, Some(encoded.as_bytes())) {
+            Err(e) if is_write_timeout(&e) => Err(e),
             Err(_) => Err("The connection to Discord was lost".into()),
             _ => Ok(()),
         }
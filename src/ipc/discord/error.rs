@@ -5,6 +5,8 @@ pub enum DiscordError {
     Io(io::Error),
     InvalidClientId(String),
     ConnectionClosed,
+    HandshakeTimeout(u64),
+    WriteTimeout(u64),
     PipeNotFound,
     Custom(String),
 }
@@ -19,6 +21,20 @@ impl fmt::Display for DiscordError {
             DiscordError::ConnectionClosed => {
                 write!(f, "The connection was forcibly closed")
             }
+            DiscordError::HandshakeTimeout(timeout) => {
+                write!(
+                    f,
+                    "Discord did not complete the handshake within {}ms",
+                    timeout
+                )
+            }
+            DiscordError::WriteTimeout(timeout) => {
+                write!(
+                    f,
+                    "Discord did not accept the data within {}ms",
+                    timeout
+                )
+            }
             DiscordError::PipeNotFound => {
                 write!(f, "Discord IPC pipe not found")
             }
@@ -46,3 +62,10 @@ impl From<String> for DiscordError {
         DiscordError::Custom(err)
     }
 }
+
+/// Whether `err` is a write Discord did not accept in time.
+pub fn is_write_timeout(err: &crate::error::CordError) -> bool {
+    std::error::Error::source(err)
+        .and_then(|source| source.downcast_ref::<DiscordError>())
+        .is_some_and(|e| matches!(e, DiscordError::WriteTimeout(_)))
+}
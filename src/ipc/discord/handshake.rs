@@ -0,0 +1,75 @@
+use crate::protocol::json;
+use crate::types::config::{HandshakeConfig, HandshakeValue};
+
+/// The payload sent to Discord with the handshake opcode.
+///
+/// Extra fields are appended after `v` and `client_id`; fields that would
+/// shadow either of them are skipped.
+pub struct Handshake<'a> {
+    pub client_id: String,
+    pub config: &'a HandshakeConfig,
+}
+
+impl<'a> Handshake<'a> {
+    pub fn new(client_id: u64, config: &'a HandshakeConfig) -> Self {
+        Self {
+            client_id: client_id.to_string(),
+            config,
+        }
+    }
+}
+
+impl json::Serialize for Handshake<'_> {
+    fn serialize<'a>(
+        &'a self,
+        f: json::SerializeFn<'a>,
+        state: &mut json::SerializeState,
+    ) -> crate::Result<()> {
+        f(
+            "v",
+            json::ValueRef::Number(self.config.version as f64),
+            state,
+        )?;
+        f("client_id", json::ValueRef::String(&self.client_id), state)?;
+
+        for (key, value) in &self.config.fields {
+            if key == "v" || key == "client_id" {
+                continue;
+            }
+
+            let value = match value {
+                HandshakeValue::String(s) => json::ValueRef::String(s),
+                HandshakeValue::Number(n) => json::ValueRef::Number(*n),
+                HandshakeValue::Boolean(b) => json::ValueRef::Boolean(*b),
+            };
+            f(key, value, state)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::json::Json;
+
+    #[test]
+    fn extra_fields_follow_and_cannot_shadow() {
+        let config = HandshakeConfig {
+            version: 2,
+            fields: vec![
+                ("client_id".to_string(), HandshakeValue::Number(1.0)),
+                ("nonce".to_string(), HandshakeValue::String("a".to_string())),
+                ("v".to_string(), HandshakeValue::Boolean(true)),
+                ("verbose".to_string(), HandshakeValue::Boolean(false)),
+            ],
+            timeout: 0,
+        };
+
+        assert_eq!(
+            Json::serialize(&Handshake::new(42, &config)).unwrap(),
+            r#"{"v":2,"client_id":"42","nonce":"a","verbose":false}"#
+        );
+    }
+}
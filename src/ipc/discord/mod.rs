@@ -1,5 +1,6 @@
 pub mod client;
 pub mod error;
+pub mod handshake;
 pub mod opcodes;
 pub mod platform;
 mod utils;
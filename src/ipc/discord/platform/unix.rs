@@ -3,6 +3,7 @@ use std::net::Shutdown;
 use std::os::unix::net::UnixStream;
 use std::sync::atomic::Ordering;
 use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant};
 
 use crate::ipc::discord::client::{Connection, RichClient};
 use crate::ipc::discord::error::DiscordError;
@@ -35,25 +36,24 @@ impl Connection for RichClient {
                 self.read_pipe = Some(read_pipe);
                 self.write_pipe = Some(pipe);
                 debug!("Successfully connected to Unix socket");
-                return Ok(true);
-            }
-            Err(e) => {
-                return match e.kind() {
-                    io::ErrorKind::NotFound => {
-                        trace!("Unix socket not found: {}", pipe);
-                        Ok(false)
-                    }
-                    _ => {
-                        debug!("Failed to connect to Unix socket: {}", e);
-                        Ok(false)
-                    }
-                };
+                Ok(true)
             }
+            Err(e) => match e.kind() {
+                io::ErrorKind::NotFound => {
+                    trace!("Unix socket not found: {}", pipe);
+                    Ok(false)
+                }
+                _ => {
+                    debug!("Failed to connect to Unix socket: {}", e);
+                    Ok(false)
+                }
+            },
         }
     }
 
     fn close(&mut self) {
         debug!("Closing Discord Unix socket connection");
+        self.pipe_path = None;
         if let Some(pipe) = self.read_pipe.take() {
             let _ = pipe.shutdown(Shutdown::Both);
         }
@@ -67,6 +67,15 @@ impl Connection for RichClient {
         if let Some(mut read_pipe) = self.read_pipe.take() {
             let client_id = self.client_id;
             let is_ready = self.is_ready.clone();
+            let handshake_timeout = self.handshake.timeout;
+
+            if handshake_timeout > 0 && !is_ready.load(Ordering::SeqCst) {
+                read_pipe
+                    .set_read_timeout(Some(Duration::from_millis(
+                        handshake_timeout,
+                    )))
+                    .map_err(DiscordError::Io)?;
+            }
 
             debug!("Starting Discord IPC read thread");
             let handle = std::thread::spawn(move || {
@@ -90,82 +99,101 @@ impl Connection for RichClient {
                                 "Received {} bytes from Discord IPC",
                                 bytes_transferred
                             );
-                            if bytes_transferred >= 8 {
-                                if let Some((opcode, size)) =
+                            if bytes_transferred >= 8
+                                && let Some((opcode, size)) =
                                     utils::decode(&buf[..bytes_transferred])
-                                {
-                                    if size > 0
-                                        && bytes_transferred
-                                            >= 8 + size as usize
-                                    {
-                                        let data = &buf[8..8 + size as usize];
-                                        let data_str =
-                                            String::from_utf8_lossy(data);
-                                        trace!(
-                                            "Received message from Discord: opcode={}, data={}",
-                                            opcode, data_str
-                                        );
+                                && size > 0
+                                && bytes_transferred >= 8 + size as usize
+                            {
+                                let data = &buf[8..8 + size as usize];
+                                let data_str = String::from_utf8_lossy(data);
+                                trace!(
+                                    "Received message from Discord: opcode={}, data={}",
+                                    opcode, data_str
+                                );
 
-                                        match Opcode::from(opcode) {
-                                            Opcode::Frame => {
-                                                if data_str.contains(
-                                                    "Invalid Client ID",
-                                                ) {
-                                                    debug!(
-                                                        "Discord reported invalid client ID"
-                                                    );
-                                                    tx.send(local_event!(
-                                                        0,
-                                                        Error,
-                                                        ErrorEvent::new(Box::new(
-                                                            DiscordError::InvalidClientId(
-                                                                client_id.to_string()
-                                                            )
-                                                        ))
-                                                    ))
-                                                    .ok();
-                                                    break;
-                                                }
-                                                if !is_ready.swap(
-                                                    true,
-                                                    Ordering::SeqCst,
-                                                ) {
-                                                    debug!(
-                                                        "Discord IPC connection is now ready"
-                                                    );
-                                                    tx.send(server_event!(
-                                                        0,
-                                                        StatusUpdate,
-                                                        StatusUpdateEvent::ready()
-                                                    ))
-                                                    .ok();
-                                                }
-                                            }
-                                            Opcode::Close => {
-                                                debug!(
-                                                    "Received close opcode from Discord"
-                                                );
-                                                tx.send(local_event!(
-                                                    0,
-                                                    Error,
-                                                    ErrorEvent::new(Box::new(
-                                                        DiscordError::ConnectionClosed
-                                                    ))
+                                match Opcode::from(opcode) {
+                                    Opcode::Frame => {
+                                        if data_str
+                                            .contains("Invalid Client ID")
+                                        {
+                                            debug!(
+                                                "Discord reported invalid client ID"
+                                            );
+                                            tx.send(local_event!(
+                                                0,
+                                                Error,
+                                                ErrorEvent::new(Box::new(
+                                                    DiscordError::InvalidClientId(
+                                                        client_id.to_string()
+                                                    )
                                                 ))
-                                                .ok();
-                                                break;
-                                            }
-                                            _ => {
-                                                trace!(
-                                                    "Received unhandled opcode: {:?}",
-                                                    Opcode::from(opcode)
-                                                );
-                                            }
+                                            ))
+                                            .ok();
+                                            break;
                                         }
+                                        if !is_ready
+                                            .swap(true, Ordering::SeqCst)
+                                        {
+                                            debug!(
+                                                "Discord IPC connection is now ready"
+                                            );
+                                            let _ = read_pipe
+                                                .set_read_timeout(None);
+                                            tx.send(server_event!(
+                                                0,
+                                                StatusUpdate,
+                                                StatusUpdateEvent::ready()
+                                            ))
+                                            .ok();
+                                        }
+                                    }
+                                    Opcode::Close => {
+                                        debug!(
+                                            "Received close opcode from Discord"
+                                        );
+                                        tx.send(local_event!(
+                                            0,
+                                            Error,
+                                            ErrorEvent::new(Box::new(
+                                                DiscordError::ConnectionClosed
+                                            ))
+                                        ))
+                                        .ok();
+                                        break;
+                                    }
+                                    _ => {
+                                        trace!(
+                                            "Received unhandled opcode: {:?}",
+                                            Opcode::from(opcode)
+                                        );
                                     }
                                 }
                             }
                         }
+                        Err(e)
+                            if matches!(
+                                e.kind(),
+                                io::ErrorKind::WouldBlock
+                                    | io::ErrorKind::TimedOut
+                            ) && !is_ready.load(Ordering::SeqCst) =>
+                        {
+                            debug!(
+                                "Discord did not send READY within {}ms",
+                                handshake_timeout
+                            );
+                            tx.send(local_event!(
+                                0,
+                                Error,
+                                ErrorEvent::new(Box::new(
+                                    DiscordError::HandshakeTimeout(
+                                        handshake_timeout
+                                    )
+                                ))
+                            ))
+                            .ok();
+                            break;
+                        }
                         Err(e) => {
                             debug!("Discord IPC read error: {}", e);
                             tx.send(local_event!(
@@ -191,7 +219,7 @@ impl Connection for RichClient {
     }
 
     fn write(&self, opcode: u32, data: Option<&[u8]>) -> crate::Result<()> {
-        self.write_pipe.as_ref().map_or(Ok(()), |mut pipe| {
+        self.write_pipe.as_ref().map_or(Ok(()), |pipe| {
             let payload = match data {
                 Some(packet) => {
                     let mut payload =
@@ -207,11 +235,27 @@ impl Connection for RichClient {
                 payload.len(),
                 opcode
             );
-            match pipe.write_all(&payload) {
+            match write_with_deadline(pipe, &payload, self.write_timeout) {
                 Ok(_) => {
                     trace!("Successfully wrote to Discord IPC");
                     Ok(())
                 }
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                    ) =>
+                {
+                    debug!(
+                        "Discord stopped reading from the socket, closing it: {}",
+                        e
+                    );
+                    // Unblocks the read thread, which then reports the
+                    // connection as closed and lets the reconnect logic run.
+                    self.is_ready.store(false, Ordering::SeqCst);
+                    let _ = pipe.shutdown(Shutdown::Both);
+                    Err(DiscordError::WriteTimeout(self.write_timeout).into())
+                }
                 Err(e) => {
                     debug!("Failed to write to Discord IPC: {}", e);
                     Err(DiscordError::ConnectionClosed.into())
@@ -220,3 +264,35 @@ impl Connection for RichClient {
         })
     }
 }
+
+/// Writes the whole buffer, failing with `TimedOut` once `timeout` milliseconds
+/// have passed. Partial progress does not extend the deadline, so a peer that
+/// drains the socket a few bytes at a time is treated as stalled too.
+fn write_with_deadline(
+    mut pipe: &UnixStream,
+    mut buf: &[u8],
+    timeout: u64,
+) -> io::Result<()> {
+    if timeout == 0 {
+        pipe.set_write_timeout(None)?;
+        return pipe.write_all(buf);
+    }
+
+    let deadline = Instant::now() + Duration::from_millis(timeout);
+    while !buf.is_empty() {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+
+        pipe.set_write_timeout(Some(remaining))?;
+        match pipe.write(buf) {
+            Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+            Ok(n) => buf = &buf[n..],
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(())
+}
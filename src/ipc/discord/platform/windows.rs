@@ -7,9 +7,10 @@ use std::sync::mpsc::Sender;
 use std::{io, ptr};
 
 use crate::ipc::bindings::{
-    CreateEventW, CreateFileW, ERROR_IO_PENDING, FILE_FLAG_OVERLAPPED,
-    GENERIC_READ, GENERIC_WRITE, GetLastError, GetOverlappedResult,
-    INVALID_HANDLE_VALUE, OPEN_EXISTING, Overlapped, ReadFile, WriteFile,
+    CancelIoEx, CreateEventW, CreateFileW, ERROR_IO_PENDING,
+    FILE_FLAG_OVERLAPPED, GENERIC_READ, GENERIC_WRITE, GetLastError,
+    GetOverlappedResult, INVALID_HANDLE_VALUE, OPEN_EXISTING, Overlapped,
+    ReadFile, WAIT_TIMEOUT, WaitForSingleObject, WriteFile,
 };
 use crate::ipc::discord::client::{Connection, RichClient};
 use crate::ipc::discord::error::DiscordError;
@@ -65,6 +66,7 @@ impl Connection for RichClient {
     fn close(&mut self) {
         debug!("Closing Discord Windows named pipe connection");
         self.pipe = None;
+        self.pipe_path = None;
         let _ = self.thread_handle.take();
     }
 
@@ -73,6 +75,7 @@ impl Connection for RichClient {
             let pipe = pipe.clone();
             let client_id = self.client_id;
             let is_ready = self.is_ready.clone();
+            let handshake_timeout = self.handshake.timeout;
 
             debug!("Starting Discord IPC read thread (Windows)");
             let handle = std::thread::spawn(move || {
@@ -124,6 +127,39 @@ impl Connection for RichClient {
                             }
                         }
 
+                        if handshake_timeout > 0
+                            && !is_ready.load(Ordering::SeqCst)
+                            && WaitForSingleObject(
+                                h_event,
+                                handshake_timeout.min(u32::MAX as u64 - 1)
+                                    as u32,
+                            ) == WAIT_TIMEOUT
+                        {
+                            debug!(
+                                "Discord did not send READY within {}ms",
+                                handshake_timeout
+                            );
+                            CancelIoEx(handle, &mut overlapped);
+                            let mut cancelled = 0;
+                            GetOverlappedResult(
+                                handle,
+                                &mut overlapped,
+                                &mut cancelled,
+                                1,
+                            );
+                            tx.send(local_event!(
+                                0,
+                                Error,
+                                ErrorEvent::new(Box::new(
+                                    DiscordError::HandshakeTimeout(
+                                        handshake_timeout
+                                    )
+                                ))
+                            ))
+                            .ok();
+                            break;
+                        }
+
                         let mut bytes_transferred = 0;
                         if GetOverlappedResult(
                             handle,
@@ -276,6 +312,30 @@ impl Connection for RichClient {
                     }
                 }
 
+                if self.write_timeout > 0
+                    && WaitForSingleObject(
+                        h_event,
+                        self.write_timeout.min(u32::MAX as u64 - 1) as u32,
+                    ) == WAIT_TIMEOUT
+                {
+                    debug!("Discord stopped reading from the pipe, closing it");
+                    // Cancelling every pending operation on the handle also
+                    // fails the read thread, which then reports the
+                    // connection as closed and lets the reconnect logic run.
+                    self.is_ready.store(false, Ordering::SeqCst);
+                    CancelIoEx(handle, ptr::null_mut());
+                    let mut cancelled = 0;
+                    GetOverlappedResult(
+                        handle,
+                        &mut overlapped,
+                        &mut cancelled,
+                        1,
+                    );
+                    return Err(
+                        DiscordError::WriteTimeout(self.write_timeout).into()
+                    );
+                }
+
                 let mut bytes_transferred = 0;
                 if GetOverlappedResult(
                     handle,
@@ -35,6 +35,19 @@ pub trait PipeServerImpl {
     /// Stops the pipe server.
     fn stop(&mut self);
 
+    /// Starts the pipe server while another server still serves the pipe.
+    /// Clients keep connecting to the other server until `take_over` is
+    /// called.
+    fn start_staged(&mut self) -> io::Result<()>;
+
+    /// Makes new clients connect to this server instead of the one the
+    /// pipe was taken over from.
+    fn take_over(&mut self) -> io::Result<()>;
+
+    /// Stops accepting clients after another server took the pipe over,
+    /// leaving the pipe to it.
+    fn hand_over(&mut self);
+
     /// Broadcasts data to all connected clients.
     fn broadcast(&self, data: &[u8]) -> io::Result<()>;
 
@@ -1,4 +1,5 @@
 use std::io::{self, Read, Write};
+use std::net::Shutdown;
 use std::os::unix::net::UnixStream;
 use std::sync::mpsc::Sender;
 use std::thread::JoinHandle;
@@ -6,8 +7,9 @@ use std::thread::JoinHandle;
 use crate::ipc::pipe::{PipeClientImpl, report_error};
 use crate::messages::events::client::ClientEvent;
 use crate::messages::events::event::Event;
+use crate::messages::events::server::ErrorEvent;
 use crate::messages::message::Message;
-use crate::{client_event, debug, error, trace};
+use crate::{client_event, debug, server_event, trace};
 
 pub struct PipeClient {
     id: u32,
@@ -63,25 +65,37 @@ impl PipeClientImpl for PipeClient {
                         }
                         Ok(n) => {
                             trace!("Received {} bytes from client {}", n, id);
-                            match ClientEvent::deserialize(&buf[..n]) {
+                            let (request_id, message) =
+                                ClientEvent::deserialize(&buf[..n]);
+                            match message {
                                 Ok(message) => {
                                     trace!(
                                         "Received event from client {}: {:?}",
                                         id, message
                                     );
-                                    tx.send(Message::new(
-                                        id,
-                                        Event::Client(message),
-                                    ))
+                                    tx.send(
+                                        Message::new(
+                                            id,
+                                            Event::Client(message),
+                                        )
+                                        .with_request_id(request_id),
+                                    )
                                     .ok();
                                 }
                                 Err(e) => {
-                                    error!(
-                                        id,
+                                    debug!(
                                         "Failed to deserialize message from client {}: {}",
-                                        id,
-                                        e
+                                        id, e
                                     );
+                                    tx.send(server_event!(
+                                        id,
+                                        Error,
+                                        ErrorEvent::invalid_message(
+                                            e.to_string()
+                                        )
+                                        .with_request_id(request_id)
+                                    ))
+                                    .ok();
                                 }
                             }
                         }
@@ -110,7 +124,11 @@ impl Drop for PipeClient {
         trace!("Dropping Unix pipe client {}", self.id);
         {
             let _ = self.read_pipe.take();
-            let _ = self.write_pipe.take();
+            // The read thread owns a clone of the socket, so shut it down
+            // explicitly to let the peer observe the disconnect.
+            if let Some(pipe) = self.write_pipe.take() {
+                let _ = pipe.shutdown(Shutdown::Both);
+            }
         }
         if let Some(handle) = self.thread_handle.take() {
             drop(handle);
@@ -1,4 +1,5 @@
 use std::io;
+use std::os::fd::AsRawFd;
 use std::os::unix::net::UnixListener;
 use std::path::Path;
 use std::sync::Arc;
@@ -7,6 +8,7 @@ use std::sync::mpsc::Sender;
 use std::thread::JoinHandle;
 
 use super::client::PipeClient;
+use crate::ipc::bindings::{SHUT_RD, shutdown};
 use crate::ipc::pipe::{PipeClientImpl, PipeServerImpl};
 use crate::messages::events::local::ErrorEvent;
 use crate::messages::message::Message;
@@ -19,6 +21,8 @@ pub struct PipeServer {
     tx: Sender<Message>,
     next_client_id: Arc<AtomicU32>,
     running: Arc<AtomicBool>,
+    /// Path the listener is bound to, removed when the server stops.
+    path: Option<String>,
     listener: Option<UnixListener>,
     thread_handle: Option<JoinHandle<()>>,
 }
@@ -36,25 +40,119 @@ impl PipeServerImpl for PipeServer {
             tx,
             next_client_id: Arc::new(AtomicU32::new(1)),
             running: Arc::new(AtomicBool::new(false)),
+            path: None,
             listener: None,
             thread_handle: None,
         }
     }
 
     fn start(&mut self) -> io::Result<()> {
+        self.listen(self.pipe_name.clone(), true)
+    }
+
+    fn start_staged(&mut self) -> io::Result<()> {
+        let path = format!("{}.{}", self.pipe_name, std::process::id());
+        self.listen(path, false)
+    }
+
+    fn take_over(&mut self) -> io::Result<()> {
+        if let Some(path) = self.path.as_ref().filter(|p| **p != self.pipe_name)
+        {
+            debug!("Moving Unix socket {} to {}", path, self.pipe_name);
+            std::fs::rename(path, &self.pipe_name)?;
+            self.path = Some(self.pipe_name.clone());
+            echoln!("Ready");
+        }
+
+        Ok(())
+    }
+
+    fn hand_over(&mut self) {
+        self.path = None;
+        self.stop();
+    }
+
+    fn stop(&mut self) {
+        debug!("Stopping Unix pipe server");
+        self.running.store(false, Ordering::SeqCst);
+        // The accept thread blocks on a clone of the listener, which only
+        // returns once the socket is shut down. Elsewhere than on Linux
+        // that may fail, leaving the thread to end with the process.
+        let woken = self.listener.take().is_some_and(|listener| unsafe {
+            shutdown(listener.as_raw_fd(), SHUT_RD) == 0
+        });
+        if let Some(handle) = self.thread_handle.take()
+            && woken
+        {
+            let _ = handle.join();
+        }
+        if let Some(path) = self.path.take() {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+
+    fn broadcast(&self, data: &[u8]) -> io::Result<()> {
+        let mut sessions = self.session_manager.sessions.write().unwrap();
+        let len = sessions.len();
+        let mut dead = Vec::new();
+        for (id, session) in sessions.iter_mut() {
+            if let Some(client) = session.get_pipe_client_mut()
+                && let Err(e) = client.write(data)
+            {
+                debug!("broadcast: write failed for client {}: {}", id, e);
+                dead.push(*id);
+            }
+        }
+        drop(sessions);
+        for id in &dead {
+            debug!("broadcast: disconnecting dead client {}", id);
+            self.tx.send(client_event!(*id, Disconnect)).ok();
+        }
+        if !dead.is_empty() {
+            debug!("broadcast: {} of {} clients dead", dead.len(), len);
+        }
+        Ok(())
+    }
+
+    fn write_to(&self, client_id: u32, data: &[u8]) -> io::Result<()> {
+        let mut sessions = self.session_manager.sessions.write().unwrap();
+        if let Some(session) = sessions.get_mut(&client_id)
+            && let Some(client) = session.get_pipe_client_mut()
+            && let Err(e) = client.write(data)
+        {
+            debug!("write_to: write failed for client {}: {}", client_id, e);
+            drop(sessions);
+            debug!("write_to: disconnecting dead client {}", client_id);
+            self.tx.send(client_event!(client_id, Disconnect)).ok();
+        }
+        Ok(())
+    }
+
+    fn disconnect(&self, client_id: u32) -> io::Result<()> {
+        debug!("Disconnecting client {}", client_id);
+        self.tx.send(client_event!(client_id, Disconnect)).ok();
+        Ok(())
+    }
+}
+
+impl PipeServer {
+    /// Binds the listener to `path` and starts accepting clients, printing
+    /// `Ready` first if `notify` is set.
+    fn listen(&mut self, path: String, notify: bool) -> io::Result<()> {
         if self.running.load(Ordering::SeqCst) {
             trace!("Unix pipe server already running");
             return Ok(());
         }
 
-        if Path::new(&self.pipe_name).exists() {
-            trace!("Removing existing Unix socket: {}", self.pipe_name);
-            std::fs::remove_file(&self.pipe_name)?;
+        if Path::new(&path).exists() {
+            trace!("Removing existing Unix socket: {}", path);
+            std::fs::remove_file(&path)?;
         }
 
-        debug!("Starting Unix pipe server on: {}", self.pipe_name);
-        let listener = UnixListener::bind(&self.pipe_name)?;
+        debug!("Starting Unix pipe server on: {}", path);
+        let listener = UnixListener::bind(&path)?;
         self.listener = Some(listener);
+        self.path = Some(path);
         self.running.store(true, Ordering::SeqCst);
 
         let tx = self.tx.clone();
@@ -64,7 +162,7 @@ impl PipeServerImpl for PipeServer {
         let listener = self.listener.as_ref().unwrap().try_clone()?;
 
         self.thread_handle = Some(std::thread::spawn(move || {
-            let mut notified = false;
+            let mut notified = !notify;
             while running.load(Ordering::SeqCst) {
                 if !notified {
                     echoln!("Ready");
@@ -82,6 +180,7 @@ impl PipeServerImpl for PipeServer {
                         session_manager.create_session(client_id, client);
                         tx.send(client_event!(client_id, Connect)).ok();
                     }
+                    Err(_) if !running.load(Ordering::SeqCst) => break,
                     Err(e) => {
                         debug!("Error accepting client connection: {}", e);
                         tx.send(local_event!(
@@ -97,65 +196,44 @@ impl PipeServerImpl for PipeServer {
 
         Ok(())
     }
+}
 
-    fn stop(&mut self) {
-        debug!("Stopping Unix pipe server");
-        self.running.store(false, Ordering::SeqCst);
-        if let Some(handle) = self.thread_handle.take() {
-            drop(handle);
-        }
-        if let Some(listener) = self.listener.take() {
-            drop(listener);
-        }
-        let _ = std::fs::remove_file(&self.pipe_name);
+impl Drop for PipeServer {
+    fn drop(&mut self) {
+        self.stop();
     }
+}
 
-    fn broadcast(&self, data: &[u8]) -> io::Result<()> {
-        let mut sessions = self.session_manager.sessions.write().unwrap();
-        let len = sessions.len();
-        let mut dead = Vec::new();
-        for (id, session) in sessions.iter_mut() {
-            if let Some(client) = session.get_pipe_client_mut()
-                && let Err(e) = client.write(data)
-            {
-                debug!("broadcast: write failed for client {}: {}", id, e);
-                dead.push(*id);
-            }
-        }
-        drop(sessions);
-        for id in &dead {
-            debug!("broadcast: removing dead session for client {}", id);
-            self.session_manager.remove_session(*id);
-        }
-        if !dead.is_empty() {
-            debug!("broadcast: {} of {} clients removed", dead.len(), len);
-        }
-        Ok(())
-    }
+#[cfg(test)]
+mod tests {
+    use std::os::unix::net::UnixStream;
+    use std::sync::mpsc;
 
-    fn write_to(&self, client_id: u32, data: &[u8]) -> io::Result<()> {
-        let mut sessions = self.session_manager.sessions.write().unwrap();
-        if let Some(session) = sessions.get_mut(&client_id)
-            && let Some(client) = session.get_pipe_client_mut()
-            && let Err(e) = client.write(data)
-        {
-            debug!("write_to: write failed for client {}: {}", client_id, e);
-            drop(sessions);
-            debug!("write_to: removing dead session for client {}", client_id);
-            self.session_manager.remove_session(client_id);
-        }
-        Ok(())
-    }
+    use super::*;
 
-    fn disconnect(&self, client_id: u32) -> io::Result<()> {
-        debug!("Disconnecting client {}", client_id);
-        self.session_manager.remove_session(client_id);
-        Ok(())
-    }
-}
+    #[test]
+    fn take_over_moves_the_staged_socket_onto_the_pipe() {
+        let pipe = std::env::temp_dir()
+            .join(format!("cord-handoff-{}", std::process::id()))
+            .to_string_lossy()
+            .into_owned();
+        let staged = format!("{}.{}", pipe, std::process::id());
+        let (tx, _rx) = mpsc::channel();
+        let mut server =
+            PipeServer::new(&pipe, tx, Arc::new(SessionManager::default()));
 
-impl Drop for PipeServer {
-    fn drop(&mut self) {
-        self.stop();
+        server.start_staged().unwrap();
+        assert!(Path::new(&staged).exists());
+        assert!(!Path::new(&pipe).exists());
+
+        server.take_over().unwrap();
+        assert!(!Path::new(&staged).exists());
+        UnixStream::connect(&pipe).unwrap();
+
+        // The pipe is left to the server it was handed over to
+        server.hand_over();
+        assert!(Path::new(&pipe).exists());
+        assert!(UnixStream::connect(&pipe).is_err());
+        std::fs::remove_file(&pipe).unwrap();
     }
 }
@@ -11,8 +11,9 @@ use crate::ipc::bindings::{
 use crate::ipc::pipe::{PipeClientImpl, report_error};
 use crate::messages::events::client::ClientEvent;
 use crate::messages::events::event::Event;
+use crate::messages::events::server::ErrorEvent;
 use crate::messages::message::Message;
-use crate::{client_event, debug, error, trace};
+use crate::{client_event, debug, server_event, trace};
 
 pub struct PipeClient {
     id: u32,
@@ -145,27 +146,33 @@ impl PipeClientImpl for PipeClient {
                             "Received {} bytes from client {}",
                             bytes_read, id
                         );
-                        match ClientEvent::deserialize(
+                        let (request_id, message) = ClientEvent::deserialize(
                             &buf[..bytes_read as usize],
-                        ) {
+                        );
+                        match message {
                             Ok(message) => {
                                 trace!(
                                     "Received event from client {}: {:?}",
                                     id, message
                                 );
-                                tx.send(Message::new(
-                                    id,
-                                    Event::Client(message),
-                                ))
+                                tx.send(
+                                    Message::new(id, Event::Client(message))
+                                        .with_request_id(request_id),
+                                )
                                 .ok();
                             }
                             Err(e) => {
-                                error!(
-                                    id,
+                                debug!(
                                     "Failed to deserialize message from client {}: {}",
-                                    id,
-                                    e
+                                    id, e
                                 );
+                                tx.send(server_event!(
+                                    id,
+                                    Error,
+                                    ErrorEvent::invalid_message(e.to_string())
+                                        .with_request_id(request_id)
+                                ))
+                                .ok();
                             }
                         }
                     }
@@ -185,6 +185,26 @@ impl PipeServerImpl for PipeServer {
         }
     }
 
+    // A named pipe cannot be served by two servers taking turns, so updates
+    // shut the server down and spawn the new one instead of handing over
+    fn start_staged(&mut self) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Handoff is not supported on Windows",
+        ))
+    }
+
+    fn take_over(&mut self) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Handoff is not supported on Windows",
+        ))
+    }
+
+    fn hand_over(&mut self) {
+        self.stop();
+    }
+
     fn broadcast(&self, data: &[u8]) -> io::Result<()> {
         let mut sessions = self.session_manager.sessions.write().unwrap();
         let len = sessions.len();
@@ -199,11 +219,11 @@ impl PipeServerImpl for PipeServer {
         }
         drop(sessions);
         for id in &dead {
-            debug!("broadcast: removing dead session for client {}", id);
-            self.session_manager.remove_session(*id);
+            debug!("broadcast: disconnecting dead client {}", id);
+            self.tx.send(client_event!(*id, Disconnect)).ok();
         }
         if !dead.is_empty() {
-            debug!("broadcast: {} of {} clients removed", dead.len(), len);
+            debug!("broadcast: {} of {} clients dead", dead.len(), len);
         }
         Ok(())
     }
@@ -219,11 +239,8 @@ impl PipeServerImpl for PipeServer {
                     client_id, e
                 );
                 drop(sessions);
-                debug!(
-                    "write_to: removing dead session for client {}",
-                    client_id
-                );
-                self.session_manager.remove_session(client_id);
+                debug!("write_to: disconnecting dead client {}", client_id);
+                self.tx.send(client_event!(client_id, Disconnect)).ok();
             }
         }
         Ok(())
@@ -231,7 +248,7 @@ impl PipeServerImpl for PipeServer {
 
     fn disconnect(&self, client_id: u32) -> io::Result<()> {
         debug!("Disconnecting client {}", client_id);
-        self.session_manager.remove_session(client_id);
+        self.tx.send(client_event!(client_id, Disconnect)).ok();
         Ok(())
     }
 }
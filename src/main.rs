@@ -4,6 +4,7 @@
 mod cli;
 mod cord;
 mod error;
+mod handoff;
 mod ipc;
 mod messages;
 mod presence;
@@ -18,13 +19,19 @@ use error::Result;
 
 fn main() -> Result<()> {
     let args = Args::parse()?;
+    if let Some(command) = args.command {
+        return command.run(&args.pipe_name);
+    }
+
     let config = Config::new(
         args.pipe_name,
         args.client_id,
         args.timeout,
         args.reconnect_interval,
         args.initial_reconnect,
-        false,
+        None,
+        args.state_freshness,
+        args.handoff,
     );
     let mut cord = Cord::new(config)?;
 
@@ -0,0 +1,61 @@
+use crate::messages::events::event::{EventContext, OnEvent};
+use crate::messages::events::server::PresenceReason;
+use crate::protocol::msgpack::{Deserialize, Value};
+use crate::session::aggregate::Aggregate;
+use crate::session::selection::refresh_presence;
+use crate::{debug, trace};
+
+/// Turns aggregate mode on with the given templates, or off.
+#[derive(Debug)]
+pub struct SetAggregateEvent {
+    /// Details and state templates, `None` to turn aggregate mode off.
+    templates: Option<(Option<String>, Option<String>)>,
+}
+
+impl OnEvent for SetAggregateEvent {
+    fn on_event(self, ctx: &mut EventContext) -> crate::Result<()> {
+        debug!(ctx.client_id, "Processing set_aggregate event: {:?}", self);
+
+        let aggregate = match self.templates {
+            Some((details, state)) => {
+                match Aggregate::new(details.as_deref(), state.as_deref()) {
+                    Ok(aggregate) => Some(aggregate),
+                    Err(e) => {
+                        trace!(
+                            ctx.client_id,
+                            "Invalid aggregate template: {}", e
+                        );
+                        ctx.fail(e);
+                        return Ok(());
+                    }
+                }
+            }
+            None => None,
+        };
+
+        *ctx.cord.session_manager.aggregate.write().unwrap() = aggregate;
+        let outcome = refresh_presence(ctx, PresenceReason::Aggregate, false)?;
+        ctx.respond(outcome);
+
+        Ok(())
+    }
+}
+
+impl Deserialize for SetAggregateEvent {
+    fn deserialize(input: Value) -> crate::Result<Self> {
+        let templates = match input {
+            Value::Nil | Value::Boolean(false) => None,
+            Value::Boolean(true) => Some((None, None)),
+            input => {
+                let mut map =
+                    input.take_map().ok_or("Invalid set_aggregate event")?;
+                Some((
+                    map.remove("details").and_then(|v| v.take_string()),
+                    map.remove("state").and_then(|v| v.take_string()),
+                ))
+            }
+        };
+
+        Ok(SetAggregateEvent { templates })
+    }
+}
@@ -1,8 +1,9 @@
-use std::sync::atomic::Ordering;
-
 use crate::messages::events::event::{EventContext, OnEvent};
-use crate::presence::activity::ActivityTimestamps;
+use crate::messages::events::server::{
+    ActivityOutcome, PresenceChangedEvent, PresenceReason,
+};
 use crate::protocol::msgpack::Deserialize;
+use crate::session::selection::refresh_presence;
 use crate::{debug, trace};
 
 #[derive(Debug, Default)]
@@ -33,79 +34,24 @@ impl OnEvent for ClearActivityEvent {
                 session.last_activity = None;
             }
 
+            ctx.cord.session_manager.set_active_session(None);
+            let mut sent = false;
             if global_last_activity.is_some() {
                 *ctx.cord.session_manager.last_activity.write().unwrap() = None;
-                ctx.cord.activity_manager.clear()?;
+                sent = ctx.cord.activity_manager.clear()?;
+                PresenceChangedEvent::new(None, None, PresenceReason::Clear)
+                    .on_event(ctx)?;
             }
+            ctx.respond(ActivityOutcome::new(sent, None));
         } else {
-            let mut sessions =
-                ctx.cord.session_manager.sessions.write().unwrap();
-
-            if let Some(session) = sessions.get_mut(&ctx.client_id) {
+            if let Some(mut session) =
+                ctx.cord.session_manager.get_session_mut(ctx.client_id)
+            {
                 session.last_activity = None;
             }
 
-            let latest = sessions
-                .iter()
-                .filter(|s| {
-                    s.0 != &ctx.client_id && s.1.last_activity.is_some()
-                })
-                .max_by_key(|s| {
-                    (
-                        s.1.last_activity.as_ref().is_some_and(|a| !a.is_idle),
-                        s.1.last_updated,
-                    )
-                })
-                .map(|(_, s)| s);
-
-            if let Some(session) = latest {
-                if let Some(mut activity) = session.last_activity.clone() {
-                    trace!(
-                        ctx.client_id,
-                        "Switching to activity from another session"
-                    );
-                    if ctx.cord.config.shared_timestamps {
-                        let shared_ts =
-                            &ctx.cord.session_manager.shared_timestamp;
-                        let ts_ref =
-                            activity.timestamps.get_or_insert_with(|| {
-                                ActivityTimestamps {
-                                    start: Some(
-                                        shared_ts.load(Ordering::SeqCst),
-                                    ),
-                                    end: None,
-                                }
-                            });
-                        if ts_ref.start.is_none() {
-                            ts_ref.start =
-                                Some(shared_ts.load(Ordering::SeqCst));
-                        }
-                    }
-
-                    if let Some(global) = &global_last_activity {
-                        if global == &activity {
-                            trace!(
-                                ctx.client_id,
-                                "Skipping: activity unchanged"
-                            );
-                            return Ok(());
-                        }
-                    }
-
-                    ctx.cord
-                        .session_manager
-                        .last_activity
-                        .write()
-                        .unwrap()
-                        .replace(activity.clone());
-
-                    ctx.cord.activity_manager.update(activity)?;
-                }
-            } else if global_last_activity.is_some() {
-                debug!(ctx.client_id, "No other sessions, clearing activity");
-                *ctx.cord.session_manager.last_activity.write().unwrap() = None;
-                ctx.cord.activity_manager.clear()?;
-            }
+            let outcome = refresh_presence(ctx, PresenceReason::Clear, false)?;
+            ctx.respond(outcome);
         }
 
         Ok(())
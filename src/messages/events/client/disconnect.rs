@@ -1,6 +1,7 @@
-use std::sync::atomic::Ordering;
-
 use crate::messages::events::event::{EventContext, OnEvent};
+use crate::messages::events::server::PresenceReason;
+use crate::session::selection::refresh_presence;
+use crate::session::state::Restored;
 use crate::{debug, trace};
 
 #[derive(Debug, Default)]
@@ -10,6 +11,68 @@ impl OnEvent for DisconnectEvent {
     fn on_event(self, ctx: &mut EventContext) -> crate::Result<()> {
         debug!(ctx.client_id, "Processing disconnect event");
 
+        if ctx.cord.handoff.as_ref().map(|(id, _)| *id) == Some(ctx.client_id) {
+            debug!(ctx.client_id, "Handoff abandoned");
+            ctx.cord.handoff = None;
+            return Ok(());
+        }
+
+        // A dead client can be reported by its read thread and by every
+        // write that failed since
+        if ctx
+            .cord
+            .session_manager
+            .get_session(ctx.client_id)
+            .is_none()
+        {
+            trace!(ctx.client_id, "Session already removed");
+            return Ok(());
+        }
+
+        if ctx.cord.session_manager.pinned_session() == Some(ctx.client_id) {
+            debug!(ctx.client_id, "Pinned session disconnected, unpinning");
+            ctx.cord.session_manager.set_pin(None);
+        }
+
+        let removed = ctx
+            .cord
+            .session_manager
+            .overrides
+            .write()
+            .unwrap()
+            .remove_session(ctx.client_id);
+        if removed > 0 {
+            debug!(ctx.client_id, "Removed {} override layer(s)", removed);
+        }
+
+        let released = ctx
+            .cord
+            .session_manager
+            .timestamps
+            .write()
+            .unwrap()
+            .release(ctx.client_id);
+        if !released.is_empty() {
+            debug!(
+                ctx.client_id,
+                "Reset shared timestamps: {}",
+                released.join(", ")
+            );
+        }
+
+        ctx.cord
+            .session_manager
+            .sharing
+            .write()
+            .unwrap()
+            .remove_session(ctx.client_id);
+
+        if let Some(exporter) =
+            ctx.cord.session_manager.wakatime.read().unwrap().as_ref()
+        {
+            exporter.forget(ctx.client_id);
+        }
+
         let mut sessions = ctx.cord.session_manager.sessions.write().unwrap();
         sessions.remove(&ctx.client_id);
         trace!(
@@ -20,6 +83,7 @@ impl OnEvent for DisconnectEvent {
 
         if sessions.is_empty() {
             debug!(ctx.client_id, "No remaining sessions, clearing activity");
+            ctx.cord.session_manager.set_active_session(None);
             ctx.cord.activity_manager.clear()?;
             ctx.cord
                 .session_manager
@@ -27,56 +91,29 @@ impl OnEvent for DisconnectEvent {
                 .write()
                 .unwrap()
                 .take();
+            ctx.cord.session_manager.timestamps.write().unwrap().clear();
             ctx.cord
                 .session_manager
-                .shared_timestamp
-                .store(0, Ordering::SeqCst);
+                .overrides
+                .write()
+                .unwrap()
+                .remove_session(0);
+            *ctx.cord.session_manager.restored.write().unwrap() =
+                Restored::default();
             return Ok(());
         }
 
-        let latest = sessions
-            .iter()
-            .filter(|s| s.1.last_activity.is_some())
-            .max_by_key(|s| {
-                (
-                    s.1.last_activity.as_ref().is_some_and(|a| !a.is_idle),
-                    s.1.last_updated,
-                )
-            })
-            .map(|(_, s)| s);
-
-        if let Some(session) = latest {
-            trace!(ctx.client_id, "Switching to activity from another session");
-            let activity = session.last_activity.as_ref().unwrap();
-
-            {
-                let mut last_activity =
-                    ctx.cord.session_manager.last_activity.write().unwrap();
-
-                if let Some(global_last_activity) = last_activity.as_ref() {
-                    if global_last_activity == activity {
-                        trace!(ctx.client_id, "Skipping: activity unchanged");
-                        return Ok(());
-                    }
-                }
-
-                *last_activity = Some(activity.clone());
-            }
-
-            ctx.cord.activity_manager.update(activity.clone())?;
-        } else {
-            let mut last_activity =
-                ctx.cord.session_manager.last_activity.write().unwrap();
-            if last_activity.is_some() {
-                debug!(
-                    ctx.client_id,
-                    "No other sessions with activity, clearing"
-                );
-                *last_activity = None;
-                drop(last_activity);
-                ctx.cord.activity_manager.clear()?;
-            }
-        }
+        drop(sessions);
+        ctx.cord
+            .activity_manager
+            .set_privacy(ctx.cord.session_manager.privacy());
+        ctx.cord
+            .session_manager
+            .sharing
+            .write()
+            .unwrap()
+            .set_enabled(ctx.cord.session_manager.confirm_share());
+        refresh_presence(ctx, PresenceReason::DisconnectFallback, false)?;
 
         Ok(())
     }
@@ -0,0 +1,56 @@
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+use crate::debug;
+use crate::messages::events::event::{EventContext, OnEvent};
+use crate::session::selection::settle_focus;
+
+/// Sent when the editor gains or loses focus. A session that gains focus
+/// only takes over the presence once it stayed focused for the configured
+/// grace period, so that quickly switching panes does not flood Discord.
+#[derive(Debug)]
+pub struct FocusEvent {
+    focused: bool,
+}
+
+impl FocusEvent {
+    pub fn new(focused: bool) -> Self {
+        Self { focused }
+    }
+}
+
+impl OnEvent for FocusEvent {
+    fn on_event(self, ctx: &mut EventContext) -> crate::Result<()> {
+        debug!(
+            ctx.client_id,
+            "Processing focus event: focused={}", self.focused
+        );
+
+        let manager = &ctx.cord.session_manager;
+        let grace = manager.focus_grace.load(Ordering::SeqCst);
+        {
+            let mut sessions = manager.sessions.write().unwrap();
+            let Some(session) = sessions.get_mut(&ctx.client_id) else {
+                return Ok(());
+            };
+            session.is_focused = self.focused;
+            if !self.focused {
+                session.focus_deadline = None;
+                return Ok(());
+            }
+
+            // Only the latest focus change settles
+            let deadline = Instant::now() + Duration::from_millis(grace);
+            for (id, session) in sessions.iter_mut() {
+                session.focus_deadline =
+                    (*id == ctx.client_id).then_some(deadline);
+            }
+        }
+
+        if grace == 0 {
+            return settle_focus(ctx);
+        }
+
+        Ok(())
+    }
+}
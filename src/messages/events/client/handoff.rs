@@ -0,0 +1,125 @@
+use crate::handoff::HandoffState;
+use crate::ipc::pipe::{PipeClientImpl, PipeServerImpl};
+use crate::messages::events::event::{EventContext, OnEvent};
+use crate::messages::events::server::AckEvent;
+use crate::protocol::msgpack::{
+    Deserialize, MsgPack, Serialize, SerializeFn, SerializeState, Value,
+    ValueRef,
+};
+use crate::session::state::PersistedState;
+use crate::{debug, trace};
+
+/// Sent by a new server binary that is about to take over the pipe. Answered
+/// with the server's state through the `ack` of the request. The connection
+/// stops being a session, as it is not an editor's.
+#[derive(Debug)]
+pub struct HandoffEvent {
+    /// Version of the new server.
+    version: Option<String>,
+}
+
+impl OnEvent for HandoffEvent {
+    fn on_event(self, ctx: &mut EventContext) -> crate::Result<()> {
+        debug!(
+            ctx.client_id,
+            "Processing handoff event: version={:?}", self.version
+        );
+
+        let Some(request_id) = ctx.request_id else {
+            debug!(ctx.client_id, "Ignoring handoff without a request id");
+            return Ok(());
+        };
+        if cfg!(target_os = "windows") {
+            ctx.fail("Handoff is not supported on Windows");
+            return Ok(());
+        }
+        if let Some((id, _)) = &ctx.cord.handoff {
+            trace!(ctx.client_id, "Handoff already requested by {}", id);
+            ctx.fail("Handoff already in progress");
+            return Ok(());
+        }
+
+        let Some(mut pipe_client) = ctx
+            .cord
+            .session_manager
+            .sessions
+            .write()
+            .unwrap()
+            .remove(&ctx.client_id)
+            .and_then(|session| session.pipe_client)
+        else {
+            return Ok(());
+        };
+
+        let client = ctx.cord.activity_manager.client.read().unwrap();
+        let discord_pipe = client.pipe_path.clone().filter(|_| {
+            client.is_ready.load(std::sync::atomic::Ordering::SeqCst)
+        });
+        drop(client);
+
+        let state = HandoffState {
+            state: PersistedState::capture(
+                &ctx.cord.session_manager,
+                ctx.cord.activity_manager.displayed(),
+            ),
+            discord_pipe,
+        };
+        // Without a session, the reply cannot be left to the event loop
+        pipe_client.write(&MsgPack::serialize(&AckEvent::new(
+            request_id,
+            Some(Box::new(state)),
+        ))?)?;
+        ctx.defer();
+        ctx.cord.handoff = Some((ctx.client_id, pipe_client));
+
+        Ok(())
+    }
+}
+
+impl Deserialize for HandoffEvent {
+    fn deserialize(input: Value) -> crate::Result<Self> {
+        let version = input
+            .take_map()
+            .and_then(|mut map| map.remove("version"))
+            .and_then(|v| v.take_string());
+
+        Ok(HandoffEvent { version })
+    }
+}
+
+/// Sent by the new server once it serves the pipe. Tells the clients to
+/// reconnect to it and shuts this server down.
+#[derive(Debug, Default)]
+pub struct HandoffCompleteEvent;
+
+impl OnEvent for HandoffCompleteEvent {
+    fn on_event(self, ctx: &mut EventContext) -> crate::Result<()> {
+        debug!(ctx.client_id, "Processing handoff_complete event");
+
+        if ctx.cord.handoff.as_ref().map(|(id, _)| *id) != Some(ctx.client_id) {
+            debug!(ctx.client_id, "Ignoring handoff_complete without handoff");
+            return Ok(());
+        }
+
+        ctx.cord.pipe.hand_over();
+        ctx.cord.pipe.broadcast(&MsgPack::serialize(&self)?)?;
+        // The new server saves the state from now on
+        ctx.cord.state = None;
+        ctx.cord.shutdown();
+
+        Ok(())
+    }
+}
+
+impl Serialize for HandoffCompleteEvent {
+    fn serialize<'a>(
+        &'a self,
+        f: SerializeFn<'a>,
+        state: &mut SerializeState,
+    ) -> crate::Result<()> {
+        f("type", ValueRef::Str("handoff"), state)?;
+        f("data", ValueRef::Nil, state)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,21 @@
+use crate::messages::events::event::{EventContext, OnEvent};
+use crate::trace;
+
+/// Sent periodically by clients that enabled heartbeats to show they are
+/// still responsive.
+#[derive(Debug, Default)]
+pub struct HeartbeatEvent;
+
+impl OnEvent for HeartbeatEvent {
+    fn on_event(self, ctx: &mut EventContext) -> crate::Result<()> {
+        trace!(ctx.client_id, "Processing heartbeat event");
+
+        if let Some(mut session) =
+            ctx.cord.session_manager.get_session_mut(ctx.client_id)
+        {
+            session.record_heartbeat();
+        }
+
+        Ok(())
+    }
+}
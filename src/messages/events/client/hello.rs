@@ -0,0 +1,83 @@
+use crate::cord::{MIN_PROTOCOL_VERSION, PROTOCOL_VERSION, features};
+use crate::ipc::pipe::PipeServerImpl;
+use crate::messages::events::event::{EventContext, OnEvent};
+use crate::messages::events::server::{ErrorEvent, HelloEvent as HelloReply};
+use crate::protocol::msgpack::{Deserialize, MsgPack};
+use crate::{client_event, debug, warn};
+
+#[derive(Debug)]
+pub struct HelloEvent {
+    pub protocol_version: u32,
+    pub capabilities: Vec<String>,
+}
+
+impl OnEvent for HelloEvent {
+    fn on_event(self, ctx: &mut EventContext) -> crate::Result<()> {
+        debug!(
+            ctx.client_id,
+            "Processing hello event: protocol_version={}, capabilities={:?}",
+            self.protocol_version,
+            self.capabilities
+        );
+
+        if !(MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION)
+            .contains(&self.protocol_version)
+        {
+            warn!(
+                ctx.client_id,
+                "Rejecting client with unsupported protocol version {}",
+                self.protocol_version
+            );
+            ctx.cord.pipe.write_to(
+                ctx.client_id,
+                &MsgPack::serialize(&ErrorEvent::incompatible_protocol(
+                    self.protocol_version,
+                ))?,
+            )?;
+            ctx.cord
+                .tx
+                .send(client_event!(ctx.client_id, Disconnect))
+                .ok();
+
+            return Ok(());
+        }
+
+        if let Some(mut session) =
+            ctx.cord.session_manager.get_session_mut(ctx.client_id)
+        {
+            session.set_protocol(self.protocol_version, self.capabilities);
+        }
+
+        ctx.cord.pipe.write_to(
+            ctx.client_id,
+            &MsgPack::serialize(&HelloReply::new(features(), ctx.client_id))?,
+        )?;
+
+        Ok(())
+    }
+}
+
+impl Deserialize for HelloEvent {
+    fn deserialize(
+        input: crate::protocol::msgpack::Value,
+    ) -> crate::Result<Self> {
+        let mut map = input.take_map().ok_or("Invalid hello event")?;
+        let protocol_version = map
+            .remove("protocol_version")
+            .and_then(|v| v.as_uinteger())
+            .and_then(|v| u32::try_from(v).ok())
+            .ok_or("Missing or invalid 'protocol_version' field")?;
+        let capabilities = map
+            .remove("capabilities")
+            .and_then(|v| v.take_array())
+            .map(|caps| {
+                caps.into_iter().filter_map(|c| c.take_string()).collect()
+            })
+            .unwrap_or_default();
+
+        Ok(HelloEvent {
+            protocol_version,
+            capabilities,
+        })
+    }
+}
@@ -0,0 +1,91 @@
+use crate::messages::events::event::{EventContext, OnEvent};
+use crate::messages::events::server::HistoryPageEvent;
+use crate::presence::history::{HistoryFilter, Reply};
+use crate::protocol::msgpack::{Deserialize, Value};
+use crate::util::glob;
+use crate::{debug, server_event};
+
+/// Entries returned when the request does not set a limit.
+const DEFAULT_LIMIT: usize = 20;
+
+/// Asks for the most recent entries of the presence history. Answered
+/// through the `ack` of the request, so it must carry an `id`. The `ack` is
+/// sent once the history writer read the entries.
+#[derive(Debug)]
+pub struct HistoryEvent {
+    filter: HistoryFilter,
+}
+
+impl OnEvent for HistoryEvent {
+    fn on_event(self, ctx: &mut EventContext) -> crate::Result<()> {
+        debug!(ctx.client_id, "Processing history event: {:?}", self.filter);
+
+        let Some(id) = ctx.request_id else {
+            debug!(
+                ctx.client_id,
+                "Ignoring history query without a request id"
+            );
+            return Ok(());
+        };
+
+        // Answered from the history writer, so that reading the files does
+        // not hold up the event loop
+        let tx = ctx.cord.tx.clone();
+        let client_id = ctx.client_id;
+        let reply: Reply = Box::new(move |entries| {
+            let page = HistoryPageEvent::new(id, entries);
+            if tx
+                .send(server_event!(client_id, HistoryPage, page))
+                .is_err()
+            {
+                debug!(client_id, "Event loop is gone, dropping history page");
+            }
+        });
+        match ctx.cord.activity_manager.history(self.filter, reply) {
+            Some(Ok(())) => ctx.defer(),
+            Some(Err(e)) => {
+                debug!(ctx.client_id, "Failed to query history: {}", e);
+                ctx.fail(format!("Failed to read history: {}", e));
+            }
+            None => ctx.fail("History is disabled"),
+        }
+
+        Ok(())
+    }
+}
+
+impl Deserialize for HistoryEvent {
+    fn deserialize(input: Value) -> crate::Result<Self> {
+        let mut map = match input {
+            Value::Nil => Default::default(),
+            input => input.take_map().ok_or("Invalid history event")?,
+        };
+
+        let session = match map.remove("session") {
+            None | Some(Value::Nil) => None,
+            Some(v) => Some(
+                v.as_uinteger()
+                    .and_then(|id| u32::try_from(id).ok())
+                    .ok_or("Invalid 'session' field")?,
+            ),
+        };
+        let workspace = map
+            .remove("workspace")
+            .and_then(|v| v.take_string())
+            .map(glob::expand_home);
+        let since = map.remove("since").and_then(|v| v.as_uinteger());
+        let limit = map
+            .remove("limit")
+            .and_then(|v| v.as_uinteger())
+            .map_or(DEFAULT_LIMIT, |limit| limit as usize);
+
+        Ok(HistoryEvent {
+            filter: HistoryFilter {
+                session,
+                workspace,
+                since,
+                limit,
+            },
+        })
+    }
+}
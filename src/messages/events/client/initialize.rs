@@ -1,4 +1,5 @@
 use std::sync::atomic::Ordering;
+use std::time::Duration;
 
 use crate::debug;
 use crate::error::CordErrorKind;
@@ -7,9 +8,15 @@ use crate::ipc::pipe::PipeServerImpl;
 use crate::local_event;
 use crate::messages::events::event::{EventContext, OnEvent};
 use crate::messages::events::local::ReconnectEvent;
-use crate::messages::events::server::StatusUpdateEvent;
 use crate::messages::events::server::status_update::Status;
+use crate::messages::events::server::{PresenceReason, StatusUpdateEvent};
+use crate::presence::history::{History, Recorder};
 use crate::protocol::msgpack::MsgPack;
+use crate::session::pause::Schedule;
+use crate::session::selection::{apply_pause, refresh_presence};
+use crate::session::state::state_dir;
+use crate::session::timestamps::TimestampScope;
+use crate::session::wakatime::Exporter;
 use crate::types::config::PluginConfig;
 use crate::util::{logger, now};
 
@@ -31,19 +38,94 @@ impl OnEvent for InitializeEvent {
             logger.set_level(self.config.log_level);
         }
 
-        ctx.cord.config.shared_timestamps = self.config.timestamp.shared;
-        if self.config.timestamp.shared {
-            let _ = ctx.cord.session_manager.shared_timestamp.compare_exchange(
-                0,
+        let timestamp = &self.config.timestamp;
+        ctx.cord.config.shared_timestamps =
+            timestamp.shared.then_some(timestamp.scope);
+        if timestamp.shared && timestamp.scope == TimestampScope::Global {
+            ctx.cord.session_manager.timestamps.write().unwrap().start(
+                TimestampScope::Global.key(ctx.client_id, None, None),
+                ctx.client_id,
+                None,
                 now().as_secs(),
-                Ordering::SeqCst,
-                Ordering::SeqCst,
             );
         }
 
         ctx.cord
             .activity_manager
             .set_config(self.config.advanced.discord.sync.clone());
+        let selection = &self.config.advanced.server.selection;
+        *ctx.cord.session_manager.policy.write().unwrap() =
+            selection.policy.clone();
+        ctx.cord
+            .session_manager
+            .focus_grace
+            .store(selection.focus_grace, Ordering::SeqCst);
+        ctx.cord
+            .session_manager
+            .rotation
+            .write()
+            .unwrap()
+            .set_interval(
+                selection.rotation.enabled.then(|| {
+                    Duration::from_millis(selection.rotation.interval)
+                }),
+            );
+
+        let away = &self.config.advanced.server.away;
+        ctx.cord.session_manager.away.write().unwrap().configure(
+            away.enabled.then(|| Duration::from_millis(away.timeout)),
+            away.activity.clone(),
+        );
+        ctx.cord.config.resume = self.config.advanced.server.resume.clone();
+        let stats = &self.config.advanced.server.stats;
+        ctx.cord
+            .session_manager
+            .stats
+            .write()
+            .unwrap()
+            .configure(stats.enabled, stats.utc_offset);
+        let history = &self.config.advanced.server.history;
+        ctx.cord.activity_manager.set_history(
+            state_dir().filter(|_| history.enabled).map(|dir| {
+                Recorder::new(History::new(dir.join("history.jsonl"), history))
+            }),
+        );
+        let wakatime = &self.config.advanced.server.wakatime;
+        {
+            // Every session sends its config, the exporter is only restarted
+            // when it changes
+            let mut exporter =
+                ctx.cord.session_manager.wakatime.write().unwrap();
+            if !wakatime.enabled {
+                exporter.take();
+            } else if exporter.as_ref().is_none_or(|e| e.config() != wakatime) {
+                match Exporter::new(
+                    wakatime.clone(),
+                    state_dir().map(|dir| dir.join("heartbeats.jsonl")),
+                    exporter.take(),
+                ) {
+                    Ok(started) => *exporter = Some(started),
+                    Err(e) => debug!(
+                        ctx.client_id,
+                        "Failed to start exporting heartbeats: {}", e
+                    ),
+                }
+            }
+        }
+
+        let dnd = &self.config.advanced.server.dnd;
+        {
+            let mut pause = ctx.cord.session_manager.pause.write().unwrap();
+            pause.configure(Schedule {
+                utc_offset: dnd.utc_offset,
+                windows: if dnd.enabled {
+                    dnd.rules.clone()
+                } else {
+                    Vec::new()
+                },
+            });
+            pause.tick(now().as_secs());
+        }
 
         let rich_client = &ctx.cord.activity_manager.client;
         let mut client = rich_client.write().unwrap();
@@ -53,6 +135,8 @@ impl OnEvent for InitializeEvent {
             debug!(ctx.client_id, "Setting custom Discord pipe paths");
             client.pipe_paths = self.config.advanced.discord.pipe_paths.clone();
         }
+        client.handshake = self.config.advanced.discord.handshake.clone();
+        client.write_timeout = self.config.advanced.discord.write_timeout;
 
         let config = &ctx.cord.config;
         let is_ready = client.is_ready.load(Ordering::SeqCst);
@@ -104,11 +188,48 @@ impl OnEvent for InitializeEvent {
             )?)?;
         }
 
+        let pid = self.config.client.pid;
         if let Some(mut session) =
             ctx.cord.session_manager.get_session_mut(ctx.client_id)
         {
+            session.set_client(self.config.client.clone());
+            let heartbeat = &self.config.advanced.server.heartbeat;
+            session.set_heartbeat_timeout(
+                heartbeat
+                    .enabled
+                    .then(|| Duration::from_millis(heartbeat.timeout)),
+            );
             session.set_config(self.config);
         }
+        // Every client's rules apply, so that one without any does not lift
+        // those of the others
+        ctx.cord
+            .activity_manager
+            .set_privacy(ctx.cord.session_manager.privacy());
+        ctx.cord
+            .session_manager
+            .sharing
+            .write()
+            .unwrap()
+            .set_enabled(ctx.cord.session_manager.confirm_share());
+
+        if let Some(pid) = pid
+            && ctx.cord.session_manager.restored.write().unwrap().claim(
+                &ctx.cord.session_manager,
+                ctx.client_id,
+                pid,
+            )
+        {
+            debug!(ctx.client_id, "Claimed state restored from last run");
+        }
+
+        drop(client);
+        apply_pause(ctx)?;
+        if is_ready
+            && ctx.cord.session_manager.aggregate.read().unwrap().is_some()
+        {
+            refresh_presence(ctx, PresenceReason::Aggregate, false)?;
+        }
 
         Ok(())
     }
@@ -1,31 +1,61 @@
 #![allow(clippy::large_enum_variant)]
 
+use std::collections::HashMap;
+
+use crate::ipc::pipe::PipeServerImpl;
 use crate::messages::events::event::{EventContext, OnEvent};
-use crate::protocol::msgpack::{Deserialize, MsgPack};
+use crate::messages::events::server::{AckEvent, ErrorEvent, PresenceReason};
+use crate::protocol::msgpack::{Deserialize, MsgPack, Value};
+use crate::session::selection::{record_event, refresh_presence};
 use crate::types::config::PluginConfig;
 use crate::{debug, trace};
 
+pub mod aggregate;
 pub mod clear_activity;
 pub mod connect;
 pub mod disconnect;
+pub mod focus;
+pub mod handoff;
+pub mod heartbeat;
+pub mod hello;
+pub mod history;
 pub mod initialize;
+pub mod overrides;
+pub mod pause;
+pub mod pin;
+pub mod query;
 pub mod reconnect;
 pub mod restart;
+pub mod share;
 pub mod shutdown;
+pub mod stats;
 pub mod update_activity;
 
+pub use aggregate::SetAggregateEvent;
 pub use clear_activity::ClearActivityEvent;
 pub use connect::ConnectEvent;
 pub use disconnect::DisconnectEvent;
+pub use focus::FocusEvent;
+pub use handoff::{HandoffCompleteEvent, HandoffEvent};
+pub use heartbeat::HeartbeatEvent;
+pub use hello::HelloEvent;
+pub use history::HistoryEvent;
 pub use initialize::InitializeEvent;
+pub use overrides::{PopOverrideEvent, PushOverrideEvent};
+pub use pause::{PauseEvent, ResumeEvent};
+pub use pin::{PinEvent, UnpinEvent};
+pub use query::QueryEvent;
 pub use reconnect::ReconnectClientEvent;
 pub use restart::RestartEvent;
+pub use share::ShareDecisionEvent;
 pub use shutdown::ShutdownEvent;
+pub use stats::StatsEvent;
 pub use update_activity::UpdateActivityEvent;
 
 #[derive(Debug)]
 pub enum ClientEvent {
     Connect(ConnectEvent),
+    Hello(HelloEvent),
     Initialize(InitializeEvent),
     UpdateActivity(UpdateActivityEvent),
     ClearActivity(ClearActivityEvent),
@@ -33,6 +63,21 @@ pub enum ClientEvent {
     Shutdown(ShutdownEvent),
     Restart(RestartEvent),
     Reconnect(ReconnectClientEvent),
+    Query(QueryEvent),
+    Heartbeat(HeartbeatEvent),
+    Focus(FocusEvent),
+    Pin(PinEvent),
+    Unpin(UnpinEvent),
+    PushOverride(PushOverrideEvent),
+    PopOverride(PopOverrideEvent),
+    SetAggregate(SetAggregateEvent),
+    Handoff(HandoffEvent),
+    HandoffComplete(HandoffCompleteEvent),
+    Pause(PauseEvent),
+    Resume(ResumeEvent),
+    ShareDecision(ShareDecisionEvent),
+    History(HistoryEvent),
+    Stats(StatsEvent),
 }
 
 /// Extracts the 'data' field from a map and returns an error if it is missing or invalid.
@@ -51,12 +96,40 @@ macro_rules! data {
 }
 
 impl ClientEvent {
-    // { type: string, data: any }
-    pub fn deserialize(json: &[u8]) -> crate::Result<Self> {
-        let mut map = MsgPack::deserialize(json)?
-            .take_map()
-            .ok_or("Invalid message")?;
+    /// Whether the event shows that the client is in use, as opposed to
+    /// connection bookkeeping sent on its own.
+    fn is_activity(&self) -> bool {
+        !matches!(
+            self,
+            Self::Connect(_)
+                | Self::Hello(_)
+                | Self::Disconnect(_)
+                | Self::Heartbeat(_)
+                | Self::Query(_)
+                | Self::History(_)
+                | Self::Stats(_)
+                | Self::Handoff(_)
+                | Self::HandoffComplete(_)
+                | Self::Pause(_)
+                | Self::Resume(_)
+        )
+    }
+
+    // { type: string, data: any, id?: integer }
+    /// Decodes a client message. The request `id` is returned separately so
+    /// that a message which fails to decode can still be answered.
+    pub fn deserialize(bytes: &[u8]) -> (Option<u64>, crate::Result<Self>) {
+        let mut map = match MsgPack::deserialize(bytes).map(|v| v.take_map()) {
+            Ok(Some(map)) => map,
+            Ok(None) => return (None, Err("Invalid message".into())),
+            Err(e) => return (None, Err(e)),
+        };
+        let id = map.remove("id").and_then(|v| v.as_uinteger());
+
+        (id, Self::from_map(map))
+    }
 
+    fn from_map(mut map: HashMap<String, Value>) -> crate::Result<Self> {
         let ty = map
             .get("type")
             .and_then(|v| v.as_str())
@@ -66,6 +139,7 @@ impl ClientEvent {
 
         Ok(match ty {
             "connect" => Self::Connect(ConnectEvent),
+            "hello" => Self::Hello(HelloEvent::deserialize(data!(map))?),
             "initialize" => Self::Initialize(InitializeEvent::new(
                 PluginConfig::deserialize(data!(map))?,
             )),
@@ -79,6 +153,42 @@ impl ClientEvent {
             "shutdown" => Self::Shutdown(ShutdownEvent),
             "restart" => Self::Restart(RestartEvent),
             "reconnect" => Self::Reconnect(ReconnectClientEvent),
+            "query" => Self::Query(QueryEvent::deserialize(
+                map.remove("data").unwrap_or(Value::Nil),
+            )?),
+            "heartbeat" => Self::Heartbeat(HeartbeatEvent),
+            "focus_gained" => Self::Focus(FocusEvent::new(true)),
+            "focus_lost" => Self::Focus(FocusEvent::new(false)),
+            "pin_session" => Self::Pin(PinEvent::deserialize(
+                map.remove("data").unwrap_or(Value::Nil),
+            )?),
+            "unpin" => Self::Unpin(UnpinEvent),
+            "push_override" => {
+                Self::PushOverride(PushOverrideEvent::deserialize(data!(map))?)
+            }
+            "pop_override" => {
+                Self::PopOverride(PopOverrideEvent::deserialize(data!(map))?)
+            }
+            "set_aggregate" => {
+                Self::SetAggregate(SetAggregateEvent::deserialize(
+                    map.remove("data").unwrap_or(Value::Nil),
+                )?)
+            }
+            "handoff" => Self::Handoff(HandoffEvent::deserialize(
+                map.remove("data").unwrap_or(Value::Nil),
+            )?),
+            "handoff_complete" => Self::HandoffComplete(HandoffCompleteEvent),
+            "pause" => Self::Pause(PauseEvent),
+            "resume" => Self::Resume(ResumeEvent),
+            "share_decision" => Self::ShareDecision(
+                ShareDecisionEvent::deserialize(data!(map))?,
+            ),
+            "history" => Self::History(HistoryEvent::deserialize(
+                map.remove("data").unwrap_or(Value::Nil),
+            )?),
+            "stats" => Self::Stats(StatsEvent::deserialize(
+                map.remove("data").unwrap_or(Value::Nil),
+            )?),
             _ => return Err(format!("Unknown message type: {}", ty).into()),
         })
     }
@@ -91,15 +201,59 @@ impl OnEvent for ClientEvent {
             "Processing client event: {:?}",
             std::mem::discriminant(&self)
         );
-        match self {
+        let back = self.is_activity() && record_event(ctx);
+        let mut result = match self {
             Self::Initialize(e) => e.on_event(ctx),
             Self::Connect(e) => e.on_event(ctx),
+            Self::Hello(e) => e.on_event(ctx),
             Self::Disconnect(e) => e.on_event(ctx),
             Self::UpdateActivity(e) => e.on_event(ctx),
             Self::ClearActivity(e) => e.on_event(ctx),
             Self::Shutdown(e) => e.on_event(ctx),
             Self::Restart(e) => e.on_event(ctx),
             Self::Reconnect(e) => e.on_event(ctx),
+            Self::Query(e) => e.on_event(ctx),
+            Self::Heartbeat(e) => e.on_event(ctx),
+            Self::Focus(e) => e.on_event(ctx),
+            Self::Pin(e) => e.on_event(ctx),
+            Self::Unpin(e) => e.on_event(ctx),
+            Self::PushOverride(e) => e.on_event(ctx),
+            Self::PopOverride(e) => e.on_event(ctx),
+            Self::SetAggregate(e) => e.on_event(ctx),
+            Self::Handoff(e) => e.on_event(ctx),
+            Self::HandoffComplete(e) => e.on_event(ctx),
+            Self::Pause(e) => e.on_event(ctx),
+            Self::Resume(e) => e.on_event(ctx),
+            Self::ShareDecision(e) => e.on_event(ctx),
+            Self::History(e) => e.on_event(ctx),
+            Self::Stats(e) => e.on_event(ctx),
+        };
+        if back && result.is_ok() {
+            result =
+                refresh_presence(ctx, PresenceReason::Away, false).map(|_| ());
+        }
+
+        if let Some(id) = ctx.request_id
+            && !(result.is_ok() && ctx.deferred)
+        {
+            let reply = match &result {
+                Ok(()) if ctx.failure.is_some() => {
+                    MsgPack::serialize(&ErrorEvent::request_failed(
+                        id,
+                        ctx.failure.take().unwrap_or_default(),
+                    ))?
+                }
+                Ok(()) => {
+                    MsgPack::serialize(&AckEvent::new(id, ctx.response.take()))?
+                }
+                Err(e) => MsgPack::serialize(&ErrorEvent::request_failed(
+                    id,
+                    e.to_string(),
+                ))?,
+            };
+            ctx.cord.pipe.write_to(ctx.client_id, &reply)?;
         }
+
+        result
     }
 }
@@ -0,0 +1,162 @@
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use crate::messages::events::event::{EventContext, OnEvent};
+use crate::messages::events::server::PresenceReason;
+use crate::presence::activity::{Activity, ActivityType, StatusDisplayType};
+use crate::protocol::msgpack::{Deserialize, Value};
+use crate::session::overrides::Override;
+use crate::session::selection::refresh_presence;
+use crate::util::now;
+use crate::{debug, trace};
+
+/// Pushes an activity layer on top of the displayed session's activity,
+/// replacing any layer with the same id.
+#[derive(Debug)]
+pub struct PushOverrideEvent {
+    id: String,
+    priority: i64,
+    activity: Activity,
+    ty: Option<ActivityType>,
+    status_display_type: Option<StatusDisplayType>,
+    /// Milliseconds after which the layer is removed.
+    ttl: Option<u64>,
+    /// Unix timestamp, in seconds, at which the layer is removed.
+    end: Option<u64>,
+}
+
+impl OnEvent for PushOverrideEvent {
+    fn on_event(self, ctx: &mut EventContext) -> crate::Result<()> {
+        debug!(
+            ctx.client_id,
+            "Processing push_override event: id={}, priority={}",
+            self.id,
+            self.priority
+        );
+
+        let expires_at = match (self.ttl, self.end) {
+            (Some(ttl), _) => Some(Instant::now() + Duration::from_millis(ttl)),
+            (None, Some(end)) => {
+                let Some(remaining) = end.checked_sub(now().as_secs()) else {
+                    trace!(
+                        ctx.client_id,
+                        "Cannot push override: already ended"
+                    );
+                    ctx.fail("Override already ended");
+                    return Ok(());
+                };
+                Some(Instant::now() + Duration::from_secs(remaining))
+            }
+            (None, None) => None,
+        };
+
+        ctx.cord
+            .session_manager
+            .overrides
+            .write()
+            .unwrap()
+            .push(Override {
+                id: self.id,
+                session: ctx.client_id,
+                priority: self.priority,
+                activity: self.activity,
+                ty: self.ty,
+                status_display_type: self.status_display_type,
+                expires_at,
+            });
+        let outcome = refresh_presence(ctx, PresenceReason::Override, false)?;
+        ctx.respond(outcome);
+
+        Ok(())
+    }
+}
+
+impl Deserialize for PushOverrideEvent {
+    fn deserialize(input: Value) -> crate::Result<Self> {
+        let mut map = input.take_map().ok_or("Invalid push_override event")?;
+
+        let id = map
+            .remove("id")
+            .and_then(|v| v.take_string())
+            .ok_or("Missing or invalid 'id' field")?;
+        let priority = map
+            .remove("priority")
+            .and_then(|v| v.as_integer())
+            .unwrap_or_default();
+        let activity =
+            map.remove("activity").ok_or("Missing 'activity' field")?;
+        let (ty, status_display_type) = match activity.as_map() {
+            Some(activity) => (
+                activity
+                    .get("type")
+                    .and_then(|v| v.as_str())
+                    .and_then(|ty| ActivityType::from_str(ty).ok()),
+                activity
+                    .get("status_display_type")
+                    .and_then(|v| v.as_str())
+                    .and_then(|ty| StatusDisplayType::from_str(ty).ok()),
+            ),
+            None => (None, None),
+        };
+        let activity = Activity::deserialize(activity)?;
+        let ttl = map
+            .remove("ttl")
+            .and_then(|v| v.as_uinteger())
+            .filter(|ttl| *ttl > 0);
+        let end = map.remove("end").and_then(|v| v.as_uinteger());
+
+        Ok(PushOverrideEvent {
+            id,
+            priority,
+            activity,
+            ty,
+            status_display_type,
+            ttl,
+            end,
+        })
+    }
+}
+
+/// Removes an override layer, showing whatever was below it again.
+#[derive(Debug)]
+pub struct PopOverrideEvent {
+    id: String,
+}
+
+impl OnEvent for PopOverrideEvent {
+    fn on_event(self, ctx: &mut EventContext) -> crate::Result<()> {
+        debug!(ctx.client_id, "Processing pop_override event: {}", self.id);
+
+        if !ctx
+            .cord
+            .session_manager
+            .overrides
+            .write()
+            .unwrap()
+            .remove(&self.id)
+        {
+            ctx.fail("No such override");
+            return Ok(());
+        }
+
+        let outcome = refresh_presence(ctx, PresenceReason::Override, true)?;
+        ctx.respond(outcome);
+
+        Ok(())
+    }
+}
+
+impl Deserialize for PopOverrideEvent {
+    fn deserialize(input: Value) -> crate::Result<Self> {
+        let id = match input {
+            Value::String(id) => id,
+            input => input
+                .take_map()
+                .and_then(|mut map| map.remove("id"))
+                .and_then(|v| v.take_string())
+                .ok_or("Missing or invalid 'id' field")?,
+        };
+
+        Ok(PopOverrideEvent { id })
+    }
+}
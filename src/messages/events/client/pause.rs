@@ -0,0 +1,31 @@
+use crate::debug;
+use crate::messages::events::event::{EventContext, OnEvent};
+use crate::session::selection::apply_pause;
+
+/// Hides the presence from every session until a `resume` event, sessions
+/// keep being tracked meanwhile.
+#[derive(Debug, Default)]
+pub struct PauseEvent;
+
+impl OnEvent for PauseEvent {
+    fn on_event(self, ctx: &mut EventContext) -> crate::Result<()> {
+        debug!(ctx.client_id, "Processing pause event");
+
+        ctx.cord.session_manager.pause.write().unwrap().pause();
+        apply_pause(ctx)
+    }
+}
+
+/// Shows the presence again after a `pause` event, or during a window of the
+/// do-not-disturb schedule until it closes.
+#[derive(Debug, Default)]
+pub struct ResumeEvent;
+
+impl OnEvent for ResumeEvent {
+    fn on_event(self, ctx: &mut EventContext) -> crate::Result<()> {
+        debug!(ctx.client_id, "Processing resume event");
+
+        ctx.cord.session_manager.pause.write().unwrap().resume();
+        apply_pause(ctx)
+    }
+}
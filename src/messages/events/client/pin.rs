@@ -0,0 +1,103 @@
+use std::time::{Duration, Instant};
+
+use crate::messages::events::event::{EventContext, OnEvent};
+use crate::messages::events::server::PresenceReason;
+use crate::protocol::msgpack::{Deserialize, Value};
+use crate::session::Pin;
+use crate::session::selection::refresh_presence;
+use crate::{debug, trace};
+
+/// Keeps a session's activity displayed whatever the selection policy,
+/// until it is unpinned, disconnects or the optional TTL runs out.
+#[derive(Debug)]
+pub struct PinEvent {
+    target: PinTarget,
+    /// Milliseconds after which the pin is removed.
+    ttl: Option<u64>,
+}
+
+#[derive(Debug)]
+enum PinTarget {
+    /// The session that sent the event.
+    Sender,
+    /// The session currently displayed.
+    Active,
+    Session(u32),
+}
+
+impl OnEvent for PinEvent {
+    fn on_event(self, ctx: &mut EventContext) -> crate::Result<()> {
+        debug!(ctx.client_id, "Processing pin_session event: {:?}", self);
+
+        let manager = &ctx.cord.session_manager;
+        let session = match self.target {
+            PinTarget::Sender => Some(ctx.client_id),
+            PinTarget::Active => manager.active_session(),
+            PinTarget::Session(id) => Some(id),
+        };
+        let Some(session) = session
+            .filter(|id| manager.sessions.read().unwrap().contains_key(id))
+        else {
+            trace!(ctx.client_id, "Cannot pin: no such session");
+            ctx.fail("No such session");
+            return Ok(());
+        };
+
+        manager.set_pin(Some(Pin {
+            session,
+            expires_at: self
+                .ttl
+                .map(|ttl| Instant::now() + Duration::from_millis(ttl)),
+        }));
+        let outcome = refresh_presence(ctx, PresenceReason::Pin, false)?;
+        ctx.respond(outcome);
+
+        Ok(())
+    }
+}
+
+impl Deserialize for PinEvent {
+    fn deserialize(input: Value) -> crate::Result<Self> {
+        let mut map = match input {
+            Value::Nil => Default::default(),
+            input => input.take_map().ok_or("Invalid pin_session event")?,
+        };
+
+        let target = match map.remove("session") {
+            None | Some(Value::Nil) => PinTarget::Sender,
+            Some(Value::String(s)) if s == "active" => PinTarget::Active,
+            Some(v) => PinTarget::Session(
+                v.as_uinteger()
+                    .and_then(|id| u32::try_from(id).ok())
+                    .ok_or("Invalid 'session' field")?,
+            ),
+        };
+        let ttl = map
+            .remove("ttl")
+            .and_then(|v| v.as_uinteger())
+            .filter(|ttl| *ttl > 0);
+
+        Ok(PinEvent { target, ttl })
+    }
+}
+
+/// Removes the pin, if any, letting the selection policy decide again.
+#[derive(Debug, Default)]
+pub struct UnpinEvent;
+
+impl OnEvent for UnpinEvent {
+    fn on_event(self, ctx: &mut EventContext) -> crate::Result<()> {
+        debug!(ctx.client_id, "Processing unpin event");
+
+        if ctx.cord.session_manager.pinned_session().is_none() {
+            ctx.fail("No session is pinned");
+            return Ok(());
+        }
+
+        ctx.cord.session_manager.set_pin(None);
+        let outcome = refresh_presence(ctx, PresenceReason::Unpin, false)?;
+        ctx.respond(outcome);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,358 @@
+use std::sync::atomic::Ordering;
+use std::time::Instant;
+
+use crate::messages::events::event::{EventContext, OnEvent};
+use crate::messages::events::server::status_update::Status;
+use crate::presence::activity::Activity;
+use crate::protocol::msgpack::{
+    Deserialize, Serialize, SerializeFn, SerializeState, ValueRef,
+};
+use crate::session::timestamps::TimestampScope;
+use crate::types::config::{ClientInfo, SyncConfig};
+use crate::{debug, trace};
+
+/// Asks the server for parts of its state. Answered through the `ack` of the
+/// request, so it must carry an `id`.
+#[derive(Debug)]
+pub struct QueryEvent {
+    topic: QueryTopic,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QueryTopic {
+    /// Connected sessions.
+    Sessions,
+    /// The activity currently displayed.
+    Activity,
+    /// The connection to Discord.
+    Status,
+    /// Server-wide settings in effect.
+    Config,
+    /// All of the above.
+    State,
+}
+
+impl QueryTopic {
+    fn includes(self, topic: QueryTopic) -> bool {
+        self == QueryTopic::State || self == topic
+    }
+}
+
+impl OnEvent for QueryEvent {
+    fn on_event(self, ctx: &mut EventContext) -> crate::Result<()> {
+        debug!(ctx.client_id, "Processing query event: {:?}", self.topic);
+
+        if ctx.request_id.is_none() {
+            debug!(ctx.client_id, "Ignoring query without a request id");
+            return Ok(());
+        }
+
+        let manager = &ctx.cord.session_manager;
+        let active_session = manager.active_session();
+        let pinned_session = manager.pinned_session();
+        let mut sessions: Vec<SessionInfo> = manager
+            .sessions
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(id, s)| SessionInfo {
+                id: *id,
+                workspace: s.workspace.clone(),
+                last_updated: (s.last_updated / 1_000_000) as u64,
+                is_idle: s.last_activity.as_ref().is_some_and(|a| a.is_idle),
+                has_activity: s.last_activity.is_some(),
+                is_active: active_session == Some(*id),
+                protocol_version: s.protocol_version,
+                client: s.client.clone(),
+                is_focused: s.is_focused,
+                is_pinned: pinned_session == Some(*id),
+            })
+            .collect();
+        sessions.sort_by_key(|s| s.id);
+
+        let overrides = manager
+            .overrides
+            .read()
+            .unwrap()
+            .layers()
+            .iter()
+            .rev()
+            .map(|layer| OverrideInfo {
+                id: layer.id.clone(),
+                session: layer.session,
+                priority: layer.priority,
+                expires_in: layer.expires_at.map(|at| {
+                    at.saturating_duration_since(Instant::now()).as_millis()
+                        as u64
+                }),
+            })
+            .collect();
+
+        let client = ctx.cord.activity_manager.client.read().unwrap();
+        let connection = ConnectionInfo {
+            status: client.status,
+            is_ready: client.is_ready.load(Ordering::SeqCst),
+            pipe_path: client.pipe_path.clone(),
+            is_reconnecting: client.is_reconnecting,
+        };
+        drop(client);
+
+        let state = ServerState {
+            topic: self.topic,
+            sessions,
+            activity: manager.last_activity.read().unwrap().clone(),
+            active_session,
+            is_away: manager.away.read().unwrap().is_away(),
+            is_paused: ctx.cord.activity_manager.is_paused(),
+            overrides,
+            connection,
+            sync: ctx.cord.activity_manager.get_config(),
+            shared_timestamps: ctx.cord.config.shared_timestamps,
+            selection: manager.policy.read().unwrap().to_string(),
+            rotation: manager
+                .rotation
+                .read()
+                .unwrap()
+                .interval()
+                .map(|interval| interval.as_millis() as u64),
+            aggregate: manager.aggregate.read().unwrap().as_ref().map(
+                |aggregate| {
+                    (aggregate.details.to_string(), aggregate.state.to_string())
+                },
+            ),
+        };
+        trace!(ctx.client_id, "Answering query with {:?}", state);
+        ctx.respond(state);
+
+        Ok(())
+    }
+}
+
+impl Deserialize for QueryEvent {
+    fn deserialize(
+        input: crate::protocol::msgpack::Value,
+    ) -> crate::Result<Self> {
+        let topic = match input.as_str().unwrap_or("state") {
+            "sessions" => QueryTopic::Sessions,
+            "activity" => QueryTopic::Activity,
+            "status" => QueryTopic::Status,
+            "config" => QueryTopic::Config,
+            "state" => QueryTopic::State,
+            topic => {
+                return Err(format!("Unknown query topic: {}", topic).into());
+            }
+        };
+
+        Ok(QueryEvent { topic })
+    }
+}
+
+#[derive(Debug)]
+struct SessionInfo {
+    id: u32,
+    workspace: Option<String>,
+    /// Milliseconds since the Unix epoch.
+    last_updated: u64,
+    is_idle: bool,
+    has_activity: bool,
+    is_active: bool,
+    protocol_version: Option<u32>,
+    client: ClientInfo,
+    is_focused: bool,
+    is_pinned: bool,
+}
+
+/// An override layer, listed from the top of the stack.
+#[derive(Debug)]
+struct OverrideInfo {
+    id: String,
+    session: u32,
+    priority: i64,
+    /// Milliseconds until the layer is removed.
+    expires_in: Option<u64>,
+}
+
+#[derive(Debug)]
+struct ConnectionInfo {
+    status: Status,
+    is_ready: bool,
+    pipe_path: Option<String>,
+    is_reconnecting: bool,
+}
+
+#[derive(Debug)]
+struct ServerState {
+    topic: QueryTopic,
+    sessions: Vec<SessionInfo>,
+    activity: Option<Activity>,
+    active_session: Option<u32>,
+    /// Whether the away presence replaces the sessions' activities.
+    is_away: bool,
+    /// Whether the presence is paused, in which case `activity` is what
+    /// would be displayed.
+    is_paused: bool,
+    overrides: Vec<OverrideInfo>,
+    connection: ConnectionInfo,
+    sync: SyncConfig,
+    shared_timestamps: Option<TimestampScope>,
+    selection: String,
+    /// Milliseconds each session is displayed for while rotating.
+    rotation: Option<u64>,
+    /// Details and state templates while in aggregate mode.
+    aggregate: Option<(String, String)>,
+}
+
+impl Serialize for SessionInfo {
+    fn serialize<'a>(
+        &'a self,
+        f: SerializeFn<'a>,
+        state: &mut SerializeState,
+    ) -> crate::Result<()> {
+        f("id", ValueRef::UInteger(self.id as u64), state)?;
+        match &self.workspace {
+            Some(workspace) => f("workspace", ValueRef::Str(workspace), state)?,
+            None => f("workspace", ValueRef::Nil, state)?,
+        }
+        f("last_updated", ValueRef::UInteger(self.last_updated), state)?;
+        f("is_idle", ValueRef::Boolean(self.is_idle), state)?;
+        f("has_activity", ValueRef::Boolean(self.has_activity), state)?;
+        f("is_active", ValueRef::Boolean(self.is_active), state)?;
+        f("is_focused", ValueRef::Boolean(self.is_focused), state)?;
+        f("is_pinned", ValueRef::Boolean(self.is_pinned), state)?;
+        if let Some(version) = self.protocol_version {
+            f(
+                "protocol_version",
+                ValueRef::UInteger(version as u64),
+                state,
+            )?;
+        }
+        f("client", ValueRef::Object(&self.client), state)?;
+
+        Ok(())
+    }
+}
+
+impl Serialize for OverrideInfo {
+    fn serialize<'a>(
+        &'a self,
+        f: SerializeFn<'a>,
+        state: &mut SerializeState,
+    ) -> crate::Result<()> {
+        f("id", ValueRef::Str(&self.id), state)?;
+        f("session", ValueRef::UInteger(self.session as u64), state)?;
+        f("priority", ValueRef::Integer(self.priority), state)?;
+        match self.expires_in {
+            Some(ms) => f("expires_in", ValueRef::UInteger(ms), state)?,
+            None => f("expires_in", ValueRef::Nil, state)?,
+        }
+
+        Ok(())
+    }
+}
+
+impl Serialize for ConnectionInfo {
+    fn serialize<'a>(
+        &'a self,
+        f: SerializeFn<'a>,
+        state: &mut SerializeState,
+    ) -> crate::Result<()> {
+        f("status", ValueRef::String(self.status.to_string()), state)?;
+        f("is_ready", ValueRef::Boolean(self.is_ready), state)?;
+        match &self.pipe_path {
+            Some(path) => f("pipe_path", ValueRef::Str(path), state)?,
+            None => f("pipe_path", ValueRef::Nil, state)?,
+        }
+        f(
+            "is_reconnecting",
+            ValueRef::Boolean(self.is_reconnecting),
+            state,
+        )?;
+
+        Ok(())
+    }
+}
+
+impl Serialize for ServerState {
+    fn serialize<'a>(
+        &'a self,
+        f: SerializeFn<'a>,
+        state: &mut SerializeState,
+    ) -> crate::Result<()> {
+        if self.topic.includes(QueryTopic::Sessions) {
+            f(
+                "sessions",
+                ValueRef::Array(
+                    self.sessions
+                        .iter()
+                        .map(|s| ValueRef::Object(s as &dyn Serialize))
+                        .collect(),
+                ),
+                state,
+            )?;
+        }
+        if self.topic.includes(QueryTopic::Activity) {
+            match &self.activity {
+                Some(activity) => {
+                    f("activity", ValueRef::Object(activity), state)?
+                }
+                None => f("activity", ValueRef::Nil, state)?,
+            }
+            match self.active_session {
+                Some(id) => {
+                    f("active_session", ValueRef::UInteger(id as u64), state)?
+                }
+                None => f("active_session", ValueRef::Nil, state)?,
+            }
+            f("is_away", ValueRef::Boolean(self.is_away), state)?;
+            f("is_paused", ValueRef::Boolean(self.is_paused), state)?;
+            f(
+                "overrides",
+                ValueRef::Array(
+                    self.overrides
+                        .iter()
+                        .map(|o| ValueRef::Object(o as &dyn Serialize))
+                        .collect(),
+                ),
+                state,
+            )?;
+        }
+        if self.topic.includes(QueryTopic::Status) {
+            f("status", ValueRef::Object(&self.connection), state)?;
+        }
+        if self.topic.includes(QueryTopic::Config) {
+            let mut config = std::collections::HashMap::new();
+            config.insert(
+                "shared_timestamps",
+                ValueRef::Boolean(self.shared_timestamps.is_some()),
+            );
+            config.insert(
+                "timestamp_scope",
+                self.shared_timestamps.map_or(ValueRef::Nil, |scope| {
+                    ValueRef::String(scope.to_string())
+                }),
+            );
+            config.insert("sync", ValueRef::Object(&self.sync));
+            config.insert("selection", ValueRef::Str(&self.selection));
+            config.insert(
+                "rotation",
+                self.rotation.map_or(ValueRef::Nil, ValueRef::UInteger),
+            );
+            config.insert(
+                "aggregate",
+                match &self.aggregate {
+                    Some((details, state)) => ValueRef::Map(
+                        [
+                            ("details", ValueRef::Str(details)),
+                            ("state", ValueRef::Str(state)),
+                        ]
+                        .into(),
+                    ),
+                    None => ValueRef::Nil,
+                },
+            );
+            f("config", ValueRef::Map(config), state)?;
+        }
+
+        Ok(())
+    }
+}
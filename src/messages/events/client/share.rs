@@ -0,0 +1,57 @@
+use crate::messages::events::event::{EventContext, OnEvent};
+use crate::messages::events::server::PresenceReason;
+use crate::protocol::msgpack::{Deserialize, Value};
+use crate::session::selection::refresh_presence;
+use crate::{debug, remove_field, trace};
+
+/// Allows or denies displaying a workspace, in reply to `confirm_share` or
+/// to change an earlier decision. Defaults to the sender's workspace.
+#[derive(Debug)]
+pub struct ShareDecisionEvent {
+    workspace: Option<String>,
+    allow: bool,
+}
+
+impl OnEvent for ShareDecisionEvent {
+    fn on_event(self, ctx: &mut EventContext) -> crate::Result<()> {
+        debug!(ctx.client_id, "Processing share_decision event: {:?}", self);
+
+        let manager = &ctx.cord.session_manager;
+        let workspace = self
+            .workspace
+            .or_else(|| manager.get_session(ctx.client_id)?.workspace.clone());
+        let Some(workspace) = workspace else {
+            trace!(ctx.client_id, "Cannot decide: no workspace");
+            ctx.fail("No workspace");
+            return Ok(());
+        };
+
+        let saved = manager
+            .sharing
+            .write()
+            .unwrap()
+            .decide(workspace, self.allow);
+        if let Err(e) = saved {
+            debug!(ctx.client_id, "Failed to save workspace decision: {}", e);
+            ctx.fail(format!("Failed to save decision: {}", e));
+        }
+
+        let outcome = refresh_presence(ctx, PresenceReason::Share, false)?;
+        if ctx.failure.is_none() {
+            ctx.respond(outcome);
+        }
+
+        Ok(())
+    }
+}
+
+impl Deserialize for ShareDecisionEvent {
+    fn deserialize(input: Value) -> crate::Result<Self> {
+        let mut map = input.take_map().ok_or("Invalid share_decision event")?;
+
+        let workspace = map.remove("workspace").and_then(|v| v.take_string());
+        let allow = remove_field!(map, "allow", |v| v.as_bool());
+
+        Ok(ShareDecisionEvent { workspace, allow })
+    }
+}
@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+
+use crate::messages::events::event::{EventContext, OnEvent};
+use crate::protocol::msgpack::{
+    Deserialize, Serialize, SerializeFn, SerializeState, Value, ValueRef,
+};
+use crate::session::stats::{StatsFilter, Summary};
+use crate::util::{glob, now};
+use crate::{debug, trace};
+
+/// Days summed up when the request does not say how many.
+const DEFAULT_DAYS: u64 = 7;
+
+/// Asks for the time tracked per day, workspace and filetype. Answered
+/// through the `ack` of the request, so it must carry an `id`.
+#[derive(Debug)]
+pub struct StatsEvent {
+    filter: StatsFilter,
+}
+
+impl OnEvent for StatsEvent {
+    fn on_event(self, ctx: &mut EventContext) -> crate::Result<()> {
+        debug!(ctx.client_id, "Processing stats event: {:?}", self.filter);
+
+        if ctx.request_id.is_none() {
+            debug!(ctx.client_id, "Ignoring stats query without a request id");
+            return Ok(());
+        }
+
+        let summary = {
+            let stats = ctx.cord.session_manager.stats.read().unwrap();
+            stats.is_enabled().then(|| {
+                stats.summary(&self.filter, stats.day(now().as_secs()))
+            })
+        };
+        let Some(summary) = summary else {
+            ctx.fail("Time tracking is disabled");
+            return Ok(());
+        };
+        trace!(
+            ctx.client_id,
+            "Answering with {} day(s) of statistics",
+            summary.days.len()
+        );
+        ctx.respond(StatsReport(summary));
+
+        Ok(())
+    }
+}
+
+impl Deserialize for StatsEvent {
+    fn deserialize(input: Value) -> crate::Result<Self> {
+        let mut map = match input {
+            Value::Nil => Default::default(),
+            input => input.take_map().ok_or("Invalid stats event")?,
+        };
+
+        let days = map
+            .remove("days")
+            .and_then(|v| v.as_uinteger())
+            .unwrap_or(DEFAULT_DAYS);
+        let workspace = map
+            .remove("workspace")
+            .and_then(|v| v.take_string())
+            .map(glob::expand_home);
+
+        Ok(StatsEvent {
+            filter: StatsFilter { days, workspace },
+        })
+    }
+}
+
+#[derive(Debug)]
+struct StatsReport(Summary);
+
+impl Serialize for StatsReport {
+    fn serialize<'a>(
+        &'a self,
+        f: SerializeFn<'a>,
+        state: &mut SerializeState,
+    ) -> crate::Result<()> {
+        // [{ key, time }], time in milliseconds
+        let totals = |totals: &'a [(String, u64)]| {
+            ValueRef::Array(
+                totals
+                    .iter()
+                    .map(|(key, time)| {
+                        ValueRef::Map(HashMap::from([
+                            ("key", ValueRef::Str(key)),
+                            ("time", ValueRef::UInteger(*time)),
+                        ]))
+                    })
+                    .collect(),
+            )
+        };
+
+        f("today", ValueRef::UInteger(self.0.today), state)?;
+        f("total", ValueRef::UInteger(self.0.total), state)?;
+        f("days", totals(&self.0.days), state)?;
+        f("workspaces", totals(&self.0.workspaces), state)?;
+        f("filetypes", totals(&self.0.filetypes), state)?;
+
+        Ok(())
+    }
+}
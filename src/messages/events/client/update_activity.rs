@@ -1,9 +1,10 @@
-use core::clone::Clone;
 use std::sync::atomic::Ordering;
 
 use crate::messages::events::event::{EventContext, OnEvent};
+use crate::messages::events::server::{ActivityOutcome, PresenceReason};
 use crate::presence::activity::{Activity, ActivityTimestamps};
 use crate::protocol::msgpack::Deserialize;
+use crate::session::selection::refresh_presence;
 use crate::util::now;
 use crate::{debug, trace};
 
@@ -11,16 +12,32 @@ use crate::{debug, trace};
 pub struct UpdateActivityEvent {
     activity: Activity,
     force: bool,
+    workspace: Option<String>,
+    /// Path of the file being edited.
+    file: Option<String>,
+    filetype: Option<String>,
 }
 
 impl OnEvent for UpdateActivityEvent {
-    // if new activity is idle, set the most recent activity available, if not, display the new activity
+    // store the new activity, then let the selection policy decide which session is displayed
     fn on_event(self, ctx: &mut EventContext) -> crate::Result<()> {
         trace!(
             ctx.client_id,
             "Processing update_activity event, force={}", self.force
         );
 
+        // Heartbeats do not depend on Discord
+        if let Some(file) = &self.file
+            && !self.activity.is_idle
+        {
+            ctx.cord.session_manager.export_heartbeat(
+                ctx.client_id,
+                file,
+                self.workspace.as_deref(),
+                self.filetype.as_deref(),
+            );
+        }
+
         if !ctx
             .cord
             .activity_manager
@@ -34,84 +51,45 @@ impl OnEvent for UpdateActivityEvent {
                 ctx.client_id,
                 "Ignoring activity update: Discord not ready"
             );
+            ctx.respond(ActivityOutcome::new(false, None));
             return Ok(());
         }
 
-        let mut activity = self
-            .activity
-            .is_idle
-            .then_some(
-                ctx.cord
-                    .session_manager
-                    .sessions
-                    .read()
-                    .unwrap()
-                    .iter()
-                    .filter(|s| {
-                        s.0 != &ctx.client_id
-                            && s.1
-                                .last_activity
-                                .as_ref()
-                                .is_some_and(|a| !a.is_idle)
-                    })
-                    .max_by_key(|s| s.1.last_updated)
-                    .and_then(|(_, s)| s.last_activity.clone()),
-            )
-            .flatten()
-            .unwrap_or(self.activity);
-
-        if ctx.cord.config.shared_timestamps {
-            let shared_ts = &ctx.cord.session_manager.shared_timestamp;
-            let ts_ref =
-                activity
-                    .timestamps
-                    .get_or_insert_with(|| ActivityTimestamps {
-                        start: Some(shared_ts.load(Ordering::SeqCst)),
-                        end: None,
-                    });
-            if let Some(start) = ts_ref.start {
-                shared_ts.store(start, Ordering::SeqCst);
-            } else {
-                ts_ref.start = Some(shared_ts.load(Ordering::SeqCst));
-            }
-        }
-
+        let mut activity = self.activity;
         if let Some(mut session) =
             ctx.cord.session_manager.get_session_mut(ctx.client_id)
         {
-            session.set_last_activity(activity.clone());
-            session.last_updated = now().as_nanos();
-        }
-
-        let should_update = {
-            let mut last_activity =
-                ctx.cord.session_manager.last_activity.write().unwrap();
+            if let Some(workspace) = self.workspace {
+                session.set_workspace(workspace);
+            }
+            session.set_file(self.file);
+            session.set_filetype(self.filetype);
 
-            if let Some(global_last_activity) = last_activity.as_ref() {
-                if !self.force && global_last_activity == &activity {
-                    trace!(
+            if let Some(scope) = ctx.cord.config.shared_timestamps {
+                let timestamps =
+                    activity.timestamps.get_or_insert(ActivityTimestamps {
+                        start: None,
+                        end: None,
+                    });
+                let start =
+                    ctx.cord.session_manager.timestamps.write().unwrap().start(
+                        session.timestamp_key(ctx.client_id, scope),
                         ctx.client_id,
-                        "Skipping activity update: no change"
+                        timestamps.start,
+                        now().as_secs(),
                     );
-                    false
-                } else {
-                    *last_activity = Some(activity.clone());
-                    true
-                }
-            } else {
-                *last_activity = Some(activity.clone());
-                true
+                timestamps.start = Some(start);
+                session.set_timestamp(start);
             }
-        };
 
-        if should_update {
-            debug!(
-                ctx.client_id,
-                "Updating activity: is_idle={}", activity.is_idle
-            );
-            ctx.cord.activity_manager.update(activity)?;
+            session.set_last_activity(activity);
+            session.last_updated = now().as_nanos();
         }
 
+        let outcome =
+            refresh_presence(ctx, PresenceReason::Update, self.force)?;
+        ctx.respond(outcome);
+
         Ok(())
     }
 }
@@ -130,7 +108,19 @@ impl Deserialize for UpdateActivityEvent {
             .remove("force")
             .and_then(|v| v.as_bool())
             .unwrap_or_default();
+        let workspace = map.remove("workspace").and_then(|v| v.take_string());
+        let file = map.remove("file").and_then(|v| v.take_string());
+        let filetype = map
+            .remove("filetype")
+            .and_then(|v| v.take_string())
+            .filter(|filetype| !filetype.is_empty());
 
-        Ok(UpdateActivityEvent { activity, force })
+        Ok(UpdateActivityEvent {
+            activity,
+            force,
+            workspace,
+            file,
+            filetype,
+        })
     }
 }
@@ -2,6 +2,7 @@ use super::client::ClientEvent;
 use super::local::LocalEvent;
 use super::server::ServerEvent;
 use crate::cord::Cord;
+use crate::protocol::msgpack::SerializeObj;
 
 #[allow(clippy::large_enum_variant)]
 #[derive(Debug)]
@@ -14,6 +15,54 @@ pub enum Event {
 pub struct EventContext<'a> {
     pub cord: &'a mut Cord,
     pub client_id: u32,
+    /// The `id` of the client request being handled, if any.
+    pub request_id: Option<u64>,
+    /// Data to include in the `ack` sent back for the current request.
+    pub response: Option<Box<dyn SerializeObj>>,
+    /// Reason the current request could not be carried out, sent back in
+    /// an `error` instead of the `ack`.
+    pub failure: Option<String>,
+    /// Set when the reply is sent later, once the request was carried out
+    /// off the event loop.
+    pub deferred: bool,
+}
+
+impl<'a> EventContext<'a> {
+    pub fn new(
+        cord: &'a mut Cord,
+        client_id: u32,
+        request_id: Option<u64>,
+    ) -> Self {
+        Self {
+            cord,
+            client_id,
+            request_id,
+            response: None,
+            failure: None,
+            deferred: false,
+        }
+    }
+
+    /// Sets the data returned to the client in the `ack` for this request.
+    /// Does nothing if the client did not ask for a reply.
+    pub fn respond(&mut self, data: impl SerializeObj + 'static) {
+        if self.request_id.is_some() {
+            self.response = Some(Box::new(data));
+        }
+    }
+
+    /// Rejects the current request without treating it as a server error.
+    /// Does nothing if the client did not ask for a reply.
+    pub fn fail(&mut self, reason: impl Into<String>) {
+        if self.request_id.is_some() {
+            self.failure = Some(reason.into());
+        }
+    }
+
+    /// Leaves the reply to whoever carries out the current request.
+    pub fn defer(&mut self) {
+        self.deferred = true;
+    }
 }
 
 pub trait OnEvent {
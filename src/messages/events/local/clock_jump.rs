@@ -0,0 +1,91 @@
+use std::sync::atomic::Ordering;
+
+use crate::messages::events::event::{EventContext, OnEvent};
+use crate::messages::events::local::ReconnectEvent;
+use crate::messages::events::server::PresenceReason;
+use crate::session::selection::refresh_presence;
+use crate::types::config::ResumeTimestamps;
+use crate::util::clock::ClockJump;
+use crate::util::now;
+use crate::{debug, info, local_event};
+
+/// Sent when the machine resumed from suspend or the wall clock jumped.
+/// Reconnects to Discord, whose connection rarely survives a suspend even
+/// though it still looks ready, and adjusts shared timestamps as configured.
+#[derive(Debug)]
+pub struct ClockJumpEvent {
+    jump: ClockJump,
+}
+
+impl ClockJumpEvent {
+    pub fn new(jump: ClockJump) -> Self {
+        Self { jump }
+    }
+}
+
+impl OnEvent for ClockJumpEvent {
+    fn on_event(self, ctx: &mut EventContext) -> crate::Result<()> {
+        info!(
+            "Clock jumped by {}s, the machine likely resumed from suspend",
+            self.jump.skipped
+        );
+
+        let resume = ctx.cord.config.resume.clone();
+        let adjusted = match ctx.cord.config.shared_timestamps {
+            Some(scope) if resume.timestamps != ResumeTimestamps::Keep => {
+                let manager = &ctx.cord.session_manager;
+                let mut sessions = manager.sessions.write().unwrap();
+                let mut timestamps = manager.timestamps.write().unwrap();
+                if resume.timestamps == ResumeTimestamps::Shift {
+                    timestamps.shift(self.jump.skipped);
+                } else {
+                    timestamps.reset(now().as_secs());
+                }
+
+                for (id, session) in sessions.iter_mut() {
+                    let Some(start) =
+                        timestamps.get(&session.timestamp_key(*id, scope))
+                    else {
+                        continue;
+                    };
+                    if session.timestamp.is_none() {
+                        continue;
+                    }
+                    session.set_timestamp(start);
+                    if let Some(timestamps) = session
+                        .last_activity
+                        .as_mut()
+                        .and_then(|a| a.timestamps.as_mut())
+                    {
+                        timestamps.start = Some(start);
+                    }
+                }
+                true
+            }
+            _ => false,
+        };
+
+        let is_ready = ctx
+            .cord
+            .activity_manager
+            .client
+            .read()
+            .unwrap()
+            .is_ready
+            .load(Ordering::SeqCst);
+        if resume.reconnect && is_ready && !ctx.cord.reconnect_state.in_progress
+        {
+            // The presence is sent again once Discord is ready
+            debug!("Reconnecting to Discord after clock jump");
+            let _ = ctx.cord.tx.send(local_event!(
+                0,
+                Reconnect,
+                ReconnectEvent::new(false)
+            ));
+        } else if adjusted {
+            refresh_presence(ctx, PresenceReason::Resume, true)?;
+        }
+
+        Ok(())
+    }
+}
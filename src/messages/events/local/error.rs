@@ -1,3 +1,4 @@
+use crate::ipc::discord::client::Connection;
 use crate::ipc::discord::error::DiscordError;
 use crate::ipc::pipe::PipeServerImpl;
 use crate::messages::events::event::{EventContext, OnEvent};
@@ -58,6 +59,30 @@ impl OnEvent for ErrorEvent {
 
                     return Ok(());
                 }
+                DiscordError::HandshakeTimeout(_) => {
+                    if ctx.cord.reconnect_state.in_progress {
+                        debug!("Handshake timed out during reconnect");
+                        return Ok(());
+                    }
+
+                    debug!("{}, closing the connection", discord_error);
+                    ctx.cord.activity_manager.client.write().unwrap().close();
+                    ctx.cord.pipe.broadcast(&MsgPack::serialize(
+                        &StatusUpdateEvent::disconnected(),
+                    )?)?;
+
+                    if ctx.cord.config.reconnect_interval == 0 {
+                        return Err(discord_error.to_string().into());
+                    }
+
+                    let _ = ctx.cord.tx.send(local_event!(
+                        0,
+                        Reconnect,
+                        ReconnectEvent::new(false)
+                    ));
+
+                    return Ok(());
+                }
                 _ => {
                     ctx.cord.pipe.broadcast(&MsgPack::serialize(
                         &StatusUpdateEvent::disconnected(),
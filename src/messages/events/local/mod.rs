@@ -1,10 +1,14 @@
+pub mod clock_jump;
 pub mod error;
 pub mod reconnect;
 pub mod reconnect_complete;
+pub mod tick;
 
+pub use clock_jump::ClockJumpEvent;
 pub use error::ErrorEvent;
 pub use reconnect::ReconnectEvent;
 pub use reconnect_complete::ReconnectCompleteEvent;
+pub use tick::TickEvent;
 
 use super::event::{EventContext, OnEvent};
 use crate::trace;
@@ -14,6 +18,8 @@ pub enum LocalEvent {
     Error(ErrorEvent),
     Reconnect(ReconnectEvent),
     ReconnectComplete(ReconnectCompleteEvent),
+    Tick(TickEvent),
+    ClockJump(ClockJumpEvent),
 }
 
 impl OnEvent for LocalEvent {
@@ -34,6 +40,8 @@ impl OnEvent for LocalEvent {
                 );
                 e.on_event(ctx)
             }
+            Self::Tick(e) => e.on_event(ctx),
+            Self::ClockJump(e) => e.on_event(ctx),
         }
     }
 }
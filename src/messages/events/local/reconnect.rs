@@ -86,6 +86,8 @@ impl OnEvent for ReconnectEvent {
                         client.client_id,
                         client.pipe_paths.clone(),
                     );
+                rich_client.handshake = client.handshake.clone();
+                rich_client.write_timeout = client.write_timeout;
 
                 match rich_client.connect() {
                     Ok(()) => match rich_client.handshake() {
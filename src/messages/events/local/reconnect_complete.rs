@@ -1,7 +1,10 @@
 use crate::ipc::pipe::PipeServerImpl;
 use crate::messages::events::event::{EventContext, OnEvent};
-use crate::messages::events::server::StatusUpdateEvent;
+use crate::messages::events::server::{
+    PresenceChangedEvent, PresenceReason, StatusUpdateEvent,
+};
 use crate::protocol::msgpack::MsgPack;
+use crate::session::selection::resend_presence;
 use crate::{debug, error};
 
 #[derive(Debug)]
@@ -40,7 +43,23 @@ impl OnEvent for ReconnectCompleteEvent {
         match &self.status {
             ReconnectStatus::Ok => {
                 debug!(client_id, "reconnect complete: ok");
-                *ctx.cord.session_manager.last_activity.write().unwrap() = None;
+                let previous = ctx
+                    .cord
+                    .session_manager
+                    .last_activity
+                    .write()
+                    .unwrap()
+                    .take();
+                if previous.is_some() {
+                    ctx.cord.session_manager.set_active_session(None);
+                    PresenceChangedEvent::new(
+                        None,
+                        None,
+                        PresenceReason::Clear,
+                    )
+                    .on_event(ctx)?;
+                }
+                resend_presence(ctx)?;
             }
             ReconnectStatus::Err(err) => {
                 debug!(client_id, "reconnect complete: failed: {}", err);
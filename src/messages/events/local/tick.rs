@@ -0,0 +1,118 @@
+use crate::messages::events::event::{EventContext, OnEvent};
+use crate::messages::events::server::PresenceReason;
+use crate::session::selection::{
+    apply_pause, check_away, refresh_presence, rotate, settle_focus, track_time,
+};
+use crate::util::{now, process};
+use crate::{client_event, debug, error, info};
+
+type Step = fn(&mut EventContext) -> crate::Result<()>;
+
+/// What is kept up to date on every tick, in order.
+const STEPS: [(&str, Step); 9] = [
+    ("session liveness", drop_dead_sessions),
+    ("pin", expire_pin),
+    ("override", expire_overrides),
+    ("pause", tick_pause),
+    ("focus", settle_focus),
+    ("rotation", rotate),
+    ("away", check_away),
+    ("time tracking", track_time),
+    ("state", save_state),
+];
+
+/// Sent every second while there are sessions. Drives everything that
+/// changes with time: unresponsive sessions are dropped as if they had
+/// disconnected, an expired pin and expired override layers are removed,
+/// a session that stayed focused for the grace period takes over, and the
+/// do-not-disturb schedule, rotation, the away presence, time tracking and
+/// the saved server state are kept up to date.
+#[derive(Debug, Default)]
+pub struct TickEvent;
+
+impl OnEvent for TickEvent {
+    fn on_event(self, ctx: &mut EventContext) -> crate::Result<()> {
+        // Each step runs on its own, so that one failing neither skips the
+        // others nor stops the server
+        for (name, step) in STEPS {
+            if let Err(e) = step(ctx) {
+                error!("Periodic {} update failed: {}", name, e);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn drop_dead_sessions(ctx: &mut EventContext) -> crate::Result<()> {
+    let hostname = process::hostname();
+    let dead: Vec<(u32, String)> = ctx
+        .cord
+        .session_manager
+        .sessions
+        .read()
+        .unwrap()
+        .iter()
+        .filter_map(|(id, s)| {
+            s.liveness_failure(hostname.as_deref())
+                .map(|reason| (*id, reason))
+        })
+        .collect();
+
+    for (id, reason) in dead {
+        info!("Dropping unresponsive session {}: {}", id, reason);
+        if ctx.cord.tx.send(client_event!(id, Disconnect)).is_err() {
+            debug!("Failed to queue disconnect for session {}", id);
+        }
+    }
+
+    Ok(())
+}
+
+fn expire_pin(ctx: &mut EventContext) -> crate::Result<()> {
+    let expired = ctx
+        .cord
+        .session_manager
+        .pin
+        .read()
+        .unwrap()
+        .is_some_and(|pin| pin.is_expired());
+    if expired {
+        debug!("Pin expired");
+        ctx.cord.session_manager.set_pin(None);
+        refresh_presence(ctx, PresenceReason::Unpin, false)?;
+    }
+
+    Ok(())
+}
+
+fn expire_overrides(ctx: &mut EventContext) -> crate::Result<()> {
+    let expired = ctx
+        .cord
+        .session_manager
+        .overrides
+        .write()
+        .unwrap()
+        .remove_expired();
+    if !expired.is_empty() {
+        debug!("Override layer(s) expired: {}", expired.join(", "));
+        refresh_presence(ctx, PresenceReason::Override, true)?;
+    }
+
+    Ok(())
+}
+
+fn tick_pause(ctx: &mut EventContext) -> crate::Result<()> {
+    ctx.cord
+        .session_manager
+        .pause
+        .write()
+        .unwrap()
+        .tick(now().as_secs());
+    apply_pause(ctx)
+}
+
+fn save_state(ctx: &mut EventContext) -> crate::Result<()> {
+    ctx.cord.save_state();
+    Ok(())
+}
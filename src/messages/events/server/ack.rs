@@ -0,0 +1,63 @@
+use crate::presence::activity::Activity;
+use crate::protocol::msgpack::{Serialize, SerializeObj, ValueRef};
+
+/// Confirms that the client request with the given `id` was handled.
+#[derive(Debug)]
+pub struct AckEvent {
+    pub id: u64,
+    pub data: Option<Box<dyn SerializeObj>>,
+}
+
+impl AckEvent {
+    pub fn new(id: u64, data: Option<Box<dyn SerializeObj>>) -> Self {
+        Self { id, data }
+    }
+}
+
+impl Serialize for AckEvent {
+    fn serialize<'a>(
+        &'a self,
+        f: crate::protocol::msgpack::SerializeFn<'a>,
+        state: &mut crate::protocol::msgpack::SerializeState,
+    ) -> crate::Result<()> {
+        f("type", ValueRef::Str("ack"), state)?;
+        f("id", ValueRef::UInteger(self.id), state)?;
+        match &self.data {
+            Some(data) => f("data", ValueRef::Object(data.as_ref()), state)?,
+            None => f("data", ValueRef::Nil, state)?,
+        }
+
+        Ok(())
+    }
+}
+
+/// Outcome of a request that changes the displayed activity.
+#[derive(Debug)]
+pub struct ActivityOutcome {
+    /// Whether Discord was updated as a result of the request.
+    pub sent: bool,
+    /// The activity displayed after the request, if any.
+    pub activity: Option<Activity>,
+}
+
+impl ActivityOutcome {
+    pub fn new(sent: bool, activity: Option<Activity>) -> Self {
+        Self { sent, activity }
+    }
+}
+
+impl Serialize for ActivityOutcome {
+    fn serialize<'a>(
+        &'a self,
+        f: crate::protocol::msgpack::SerializeFn<'a>,
+        state: &mut crate::protocol::msgpack::SerializeState,
+    ) -> crate::Result<()> {
+        f("sent", ValueRef::Boolean(self.sent), state)?;
+        match &self.activity {
+            Some(activity) => f("activity", ValueRef::Object(activity), state)?,
+            None => f("activity", ValueRef::Nil, state)?,
+        }
+
+        Ok(())
+    }
+}
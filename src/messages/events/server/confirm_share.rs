@@ -0,0 +1,45 @@
+use std::collections::HashMap;
+
+use crate::ipc::pipe::PipeServerImpl;
+use crate::messages::events::event::{EventContext, OnEvent};
+use crate::protocol::msgpack::{MsgPack, Serialize, ValueRef};
+
+/// Asks a session whether its workspace may be displayed. The session
+/// replies with a `share_decision` event.
+#[derive(Debug)]
+pub struct ConfirmShareEvent {
+    pub session: u32,
+    pub workspace: String,
+}
+
+impl ConfirmShareEvent {
+    pub fn new(session: u32, workspace: String) -> Self {
+        Self { session, workspace }
+    }
+}
+
+impl OnEvent for ConfirmShareEvent {
+    fn on_event(self, ctx: &mut EventContext) -> crate::Result<()> {
+        ctx.cord
+            .pipe
+            .write_to(self.session, &MsgPack::serialize(&self)?)?;
+
+        Ok(())
+    }
+}
+
+impl Serialize for ConfirmShareEvent {
+    fn serialize<'a>(
+        &'a self,
+        f: crate::protocol::msgpack::SerializeFn<'a>,
+        state: &mut crate::protocol::msgpack::SerializeState,
+    ) -> crate::Result<()> {
+        let mut data = HashMap::new();
+        data.insert("workspace", ValueRef::Str(&self.workspace));
+
+        f("type", ValueRef::Str("confirm_share"), state)?;
+        f("data", ValueRef::Map(data), state)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+
+use crate::cord::{MIN_PROTOCOL_VERSION, PROTOCOL_VERSION, VERSION};
+use crate::ipc::pipe::PipeServerImpl;
+use crate::messages::events::event::{EventContext, OnEvent};
+use crate::protocol::msgpack::{MsgPack, Serialize, ValueRef};
+use crate::trace;
+
+/// A structured error reported to a single client.
+#[derive(Debug)]
+pub struct ErrorEvent {
+    pub code: ErrorCode,
+    pub message: String,
+    /// The `id` of the client request that failed, if any.
+    pub id: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum ErrorCode {
+    /// The client speaks a protocol version this server does not support.
+    IncompatibleProtocol,
+    /// The client sent a message the server could not understand.
+    InvalidMessage,
+    /// The server failed to handle a client request.
+    RequestFailed,
+    /// The client's workspace cannot be saved as a sharing decision, so it
+    /// is never displayed while sharing must be confirmed.
+    UnshareableWorkspace,
+}
+
+impl Display for ErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::IncompatibleProtocol => write!(f, "incompatible_protocol"),
+            Self::InvalidMessage => write!(f, "invalid_message"),
+            Self::RequestFailed => write!(f, "request_failed"),
+            Self::UnshareableWorkspace => write!(f, "unshareable_workspace"),
+        }
+    }
+}
+
+impl ErrorEvent {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            id: None,
+        }
+    }
+
+    pub fn with_request_id(mut self, id: Option<u64>) -> Self {
+        self.id = id;
+        self
+    }
+
+    pub fn incompatible_protocol(protocol_version: u32) -> Self {
+        let supported = if MIN_PROTOCOL_VERSION == PROTOCOL_VERSION {
+            PROTOCOL_VERSION.to_string()
+        } else {
+            format!("{}-{}", MIN_PROTOCOL_VERSION, PROTOCOL_VERSION)
+        };
+
+        Self::new(
+            ErrorCode::IncompatibleProtocol,
+            format!(
+                "Server v{} supports protocol version {}, but the client uses version {}",
+                VERSION, supported, protocol_version
+            ),
+        )
+    }
+
+    pub fn invalid_message(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::InvalidMessage, message)
+    }
+
+    pub fn request_failed(id: u64, message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::RequestFailed, message).with_request_id(Some(id))
+    }
+
+    pub fn unshareable_workspace(workspace: &str) -> Self {
+        Self::new(
+            ErrorCode::UnshareableWorkspace,
+            format!(
+                "Workspace {:?} contains control characters and is not shown on Discord while sharing must be confirmed",
+                workspace
+            ),
+        )
+    }
+}
+
+impl OnEvent for ErrorEvent {
+    fn on_event(self, ctx: &mut EventContext) -> crate::Result<()> {
+        trace!(
+            ctx.client_id,
+            "Sending error to client: {}: {}", self.code, self.message
+        );
+        ctx.cord
+            .pipe
+            .write_to(ctx.client_id, &MsgPack::serialize(&self)?)?;
+
+        Ok(())
+    }
+}
+
+impl Serialize for ErrorEvent {
+    fn serialize<'a>(
+        &'a self,
+        f: crate::protocol::msgpack::SerializeFn<'a>,
+        state: &mut crate::protocol::msgpack::SerializeState,
+    ) -> crate::Result<()> {
+        let mut data = HashMap::new();
+        data.insert("code", ValueRef::String(self.code.to_string()));
+        data.insert("message", ValueRef::Str(&self.message));
+
+        f("type", ValueRef::Str("error"), state)?;
+        if let Some(id) = self.id {
+            f("id", ValueRef::UInteger(id), state)?;
+        }
+        f("data", ValueRef::Map(data), state)?;
+
+        Ok(())
+    }
+}
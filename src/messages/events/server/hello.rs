@@ -0,0 +1,48 @@
+use std::collections::HashMap;
+
+use crate::cord::{PROTOCOL_VERSION, VERSION};
+use crate::protocol::msgpack::{Serialize, ValueRef};
+
+/// Reply to a client's `hello`, describing what this server supports.
+#[derive(Debug)]
+pub struct HelloEvent {
+    pub features: Vec<&'static str>,
+    /// The id the server assigned to the client's session.
+    pub session_id: u32,
+}
+
+impl HelloEvent {
+    pub fn new(features: Vec<&'static str>, session_id: u32) -> Self {
+        Self {
+            features,
+            session_id,
+        }
+    }
+}
+
+impl Serialize for HelloEvent {
+    fn serialize<'a>(
+        &'a self,
+        f: crate::protocol::msgpack::SerializeFn<'a>,
+        state: &mut crate::protocol::msgpack::SerializeState,
+    ) -> crate::Result<()> {
+        let mut data = HashMap::new();
+        data.insert("version", ValueRef::Str(VERSION));
+        data.insert(
+            "protocol_version",
+            ValueRef::UInteger(PROTOCOL_VERSION as u64),
+        );
+        data.insert("session_id", ValueRef::UInteger(self.session_id as u64));
+        data.insert(
+            "features",
+            ValueRef::Array(
+                self.features.iter().map(|f| ValueRef::Str(f)).collect(),
+            ),
+        );
+
+        f("type", ValueRef::Str("hello"), state)?;
+        f("data", ValueRef::Map(data), state)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,78 @@
+use crate::ipc::pipe::PipeServerImpl;
+use crate::messages::events::event::{EventContext, OnEvent};
+use crate::messages::events::server::{AckEvent, ErrorEvent};
+use crate::presence::history::HistoryEntry;
+use crate::protocol::msgpack::{
+    MsgPack, Serialize, SerializeFn, SerializeState, ValueRef,
+};
+use crate::{debug, trace};
+
+/// Answers a `history` request once the history writer read the entries.
+#[derive(Debug)]
+pub struct HistoryPageEvent {
+    /// The `id` of the `history` request.
+    pub id: u64,
+    pub entries: crate::Result<Vec<HistoryEntry>>,
+}
+
+impl HistoryPageEvent {
+    pub fn new(id: u64, entries: crate::Result<Vec<HistoryEntry>>) -> Self {
+        Self { id, entries }
+    }
+}
+
+impl OnEvent for HistoryPageEvent {
+    fn on_event(self, ctx: &mut EventContext) -> crate::Result<()> {
+        let reply = match self.entries {
+            Ok(entries) => {
+                trace!(
+                    ctx.client_id,
+                    "Answering with {} entries",
+                    entries.len()
+                );
+                let page = HistoryPage { entries };
+                MsgPack::serialize(&AckEvent::new(
+                    self.id,
+                    Some(Box::new(page)),
+                ))?
+            }
+            Err(e) => {
+                debug!(ctx.client_id, "Failed to read history: {}", e);
+                MsgPack::serialize(&ErrorEvent::request_failed(
+                    self.id,
+                    format!("Failed to read history: {}", e),
+                ))?
+            }
+        };
+        ctx.cord.pipe.write_to(ctx.client_id, &reply)?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct HistoryPage {
+    /// Newest first.
+    entries: Vec<HistoryEntry>,
+}
+
+impl Serialize for HistoryPage {
+    fn serialize<'a>(
+        &'a self,
+        f: SerializeFn<'a>,
+        state: &mut SerializeState,
+    ) -> crate::Result<()> {
+        f(
+            "entries",
+            ValueRef::Array(
+                self.entries
+                    .iter()
+                    .map(|e| ValueRef::Object(e as &dyn Serialize))
+                    .collect(),
+            ),
+            state,
+        )?;
+
+        Ok(())
+    }
+}
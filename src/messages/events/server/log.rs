@@ -12,7 +12,17 @@ use crate::protocol::msgpack::{MsgPack, Serialize, ValueRef};
 use crate::util::logger::LogLevel;
 
 impl OnEvent for LogEvent {
-    fn on_event(self, ctx: &mut EventContext) -> crate::Result<()> {
+    fn on_event(mut self, ctx: &mut EventContext) -> crate::Result<()> {
+        if ctx.client_id != 0
+            && let Some(label) = ctx
+                .cord
+                .session_manager
+                .get_session(ctx.client_id)
+                .and_then(|s| s.client.label())
+        {
+            self.message = format!("[{}] {}", label, self.message);
+        }
+
         if ctx.cord.session_manager.sessions.read().unwrap().is_empty() {
             if ctx.cord.log_buffer.len() >= 100 {
                 ctx.cord.log_buffer.pop_front();
@@ -1,9 +1,21 @@
+pub mod ack;
 pub mod batch_log;
+pub mod confirm_share;
+pub mod error;
+pub mod hello;
+pub mod history_page;
 pub mod log;
+pub mod presence_changed;
 pub mod status_update;
 
+pub use ack::{AckEvent, ActivityOutcome};
 pub use batch_log::BatchLogEvent;
+pub use confirm_share::ConfirmShareEvent;
+pub use error::ErrorEvent;
+pub use hello::HelloEvent;
+pub use history_page::HistoryPageEvent;
 pub use log::LogEvent;
+pub use presence_changed::{PresenceChangedEvent, PresenceReason};
 pub use status_update::StatusUpdateEvent;
 
 use crate::messages::events::event::{EventContext, OnEvent};
@@ -15,6 +27,8 @@ pub enum ServerEvent {
     #[allow(dead_code)]
     BatchLog(BatchLogEvent),
     StatusUpdate(StatusUpdateEvent),
+    Error(ErrorEvent),
+    HistoryPage(HistoryPageEvent),
 }
 
 impl OnEvent for ServerEvent {
@@ -29,6 +43,8 @@ impl OnEvent for ServerEvent {
                 );
                 e.on_event(ctx)
             }
+            Self::Error(e) => e.on_event(ctx),
+            Self::HistoryPage(e) => e.on_event(ctx),
         }
     }
 }
@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+
+use crate::ipc::pipe::PipeServerImpl;
+use crate::messages::events::event::{EventContext, OnEvent};
+use crate::presence::activity::Activity;
+use crate::protocol::msgpack::{MsgPack, Serialize, ValueRef};
+use crate::trace;
+
+/// Tells every session what Discord is displaying after the effective
+/// presence changed.
+#[derive(Debug)]
+pub struct PresenceChangedEvent {
+    pub activity: Option<Activity>,
+    /// The session whose activity is displayed, if any.
+    pub session: Option<u32>,
+    pub reason: PresenceReason,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum PresenceReason {
+    /// A session updated its own activity.
+    Update,
+    /// A session went idle and another session's activity took over.
+    IdleTakeover,
+    /// A session disconnected and another session's activity took over.
+    DisconnectFallback,
+    /// The activity was cleared, possibly falling back to another session.
+    Clear,
+    /// Another session gained focus.
+    Focus,
+    /// A session was pinned.
+    Pin,
+    /// The pin was removed or expired.
+    Unpin,
+    /// An override layer was pushed, removed or expired.
+    Override,
+    /// Rotation moved on to the next active session.
+    Rotation,
+    /// Aggregate mode was turned on or off, or an editor joined the summary.
+    Aggregate,
+    /// The activity shown before the server restarted was restored.
+    Restore,
+    /// Every session went without events for too long, or one came back.
+    Away,
+    /// Shared timestamps were adjusted after a suspend or clock jump.
+    Resume,
+    /// The presence was paused or resumed.
+    Pause,
+    /// A workspace was allowed or denied to be displayed.
+    Share,
+    /// A time shown through a `{stats.*}` placeholder went up.
+    Stats,
+    /// The activity was sent again after reconnecting to Discord.
+    Reconnect,
+}
+
+impl Display for PresenceReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Update => write!(f, "update"),
+            Self::IdleTakeover => write!(f, "idle_takeover"),
+            Self::DisconnectFallback => write!(f, "disconnect_fallback"),
+            Self::Clear => write!(f, "clear"),
+            Self::Focus => write!(f, "focus"),
+            Self::Pin => write!(f, "pin"),
+            Self::Unpin => write!(f, "unpin"),
+            Self::Override => write!(f, "override"),
+            Self::Rotation => write!(f, "rotation"),
+            Self::Aggregate => write!(f, "aggregate"),
+            Self::Restore => write!(f, "restore"),
+            Self::Away => write!(f, "away"),
+            Self::Resume => write!(f, "resume"),
+            Self::Pause => write!(f, "pause"),
+            Self::Share => write!(f, "share"),
+            Self::Stats => write!(f, "stats"),
+            Self::Reconnect => write!(f, "reconnect"),
+        }
+    }
+}
+
+impl PresenceChangedEvent {
+    pub fn new(
+        activity: Option<Activity>,
+        session: Option<u32>,
+        reason: PresenceReason,
+    ) -> Self {
+        Self {
+            activity,
+            session,
+            reason,
+        }
+    }
+}
+
+impl OnEvent for PresenceChangedEvent {
+    fn on_event(self, ctx: &mut EventContext) -> crate::Result<()> {
+        // Nothing is displayed while paused
+        if ctx.cord.activity_manager.is_paused()
+            && !matches!(self.reason, PresenceReason::Pause)
+        {
+            trace!(ctx.client_id, "Not broadcasting presence change: paused");
+            return Ok(());
+        }
+
+        trace!(
+            ctx.client_id,
+            "Broadcasting presence change: reason={}, session={:?}",
+            self.reason,
+            self.session
+        );
+        ctx.cord.pipe.broadcast(&MsgPack::serialize(&self)?)?;
+
+        Ok(())
+    }
+}
+
+impl Serialize for PresenceChangedEvent {
+    fn serialize<'a>(
+        &'a self,
+        f: crate::protocol::msgpack::SerializeFn<'a>,
+        state: &mut crate::protocol::msgpack::SerializeState,
+    ) -> crate::Result<()> {
+        let mut data = HashMap::new();
+        data.insert(
+            "activity",
+            self.activity
+                .as_ref()
+                .map_or(ValueRef::Nil, |a| ValueRef::Object(a)),
+        );
+        data.insert(
+            "session",
+            self.session
+                .map_or(ValueRef::Nil, |id| ValueRef::UInteger(id as u64)),
+        );
+        data.insert("reason", ValueRef::String(self.reason.to_string()));
+
+        f("type", ValueRef::Str("presence_changed"), state)?;
+        f("data", ValueRef::Map(data), state)?;
+
+        Ok(())
+    }
+}
@@ -6,6 +6,7 @@ use crate::messages::events::event::{EventContext, OnEvent};
 use crate::protocol::msgpack::MsgPack;
 use crate::protocol::msgpack::serialize::Serialize;
 use crate::protocol::msgpack::value::ValueRef;
+use crate::session::selection::{resend_presence, show_restored};
 use crate::trace;
 
 #[derive(Debug)]
@@ -62,6 +63,10 @@ impl OnEvent for StatusUpdateEvent {
     fn on_event(self, ctx: &mut EventContext) -> crate::Result<()> {
         trace!(ctx.client_id, "Broadcasting status update: {}", self.status);
         ctx.cord.pipe.broadcast(&MsgPack::serialize(&self)?)?;
+        if matches!(self.status, Status::Ready) {
+            show_restored(ctx)?;
+            resend_presence(ctx)?;
+        }
 
         Ok(())
     }
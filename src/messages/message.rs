@@ -4,6 +4,8 @@ use super::events::event::Event;
 pub struct Message {
     pub client_id: u32,
     pub event: Event,
+    /// The `id` the client attached to the message, if it expects a reply.
+    pub request_id: Option<u64>,
 }
 
 impl Message {
@@ -11,8 +13,14 @@ impl Message {
         Self {
             client_id,
             event: message,
+            request_id: None,
         }
     }
+
+    pub fn with_request_id(mut self, request_id: Option<u64>) -> Self {
+        self.request_id = request_id;
+        self
+    }
 }
 
 /// Constructs a `Message` for client events.
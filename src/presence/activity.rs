@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+use std::fmt::Display;
 use std::str::FromStr;
 
 use crate::protocol::json;
@@ -73,6 +75,17 @@ impl FromStr for ActivityType {
     }
 }
 
+impl Display for ActivityType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Playing => write!(f, "playing"),
+            Self::Listening => write!(f, "listening"),
+            Self::Watching => write!(f, "watching"),
+            Self::Competing => write!(f, "competing"),
+        }
+    }
+}
+
 impl FromStr for StatusDisplayType {
     type Err = ();
 
@@ -86,6 +99,16 @@ impl FromStr for StatusDisplayType {
     }
 }
 
+impl Display for StatusDisplayType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Name => write!(f, "name"),
+            Self::State => write!(f, "state"),
+            Self::Details => write!(f, "details"),
+        }
+    }
+}
+
 impl json::Serialize for Activity {
     fn serialize<'a>(
         &'a self,
@@ -194,6 +217,121 @@ impl json::Serialize for ActivityButton {
     }
 }
 
+impl msgpack::Serialize for Activity {
+    fn serialize<'a>(
+        &'a self,
+        f: msgpack::SerializeFn<'a>,
+        state: &mut msgpack::SerializeState,
+    ) -> crate::Result<()> {
+        f(
+            "type",
+            msgpack::ValueRef::String(self.ty.to_string()),
+            state,
+        )?;
+        f(
+            "status_display_type",
+            msgpack::ValueRef::String(self.status_display_type.to_string()),
+            state,
+        )?;
+        if let Some(details) = &self.details {
+            f("details", msgpack::ValueRef::Str(details), state)?;
+        }
+        if let Some(details_url) = &self.details_url {
+            f("details_url", msgpack::ValueRef::Str(details_url), state)?;
+        }
+        if let Some(state_str) = &self.state {
+            f("state", msgpack::ValueRef::Str(state_str), state)?;
+        }
+        if let Some(state_url) = &self.state_url {
+            f("state_url", msgpack::ValueRef::Str(state_url), state)?;
+        }
+        if let Some(assets) = &self.assets {
+            f("assets", msgpack::ValueRef::Object(assets), state)?;
+        }
+        if let Some(timestamps) = &self.timestamps {
+            f("timestamps", msgpack::ValueRef::Object(timestamps), state)?;
+        }
+        if !self.buttons.is_empty() {
+            f(
+                "buttons",
+                msgpack::ValueRef::Array(
+                    self.buttons
+                        .iter()
+                        .map(|b| {
+                            msgpack::ValueRef::Object(
+                                b as &dyn msgpack::Serialize,
+                            )
+                        })
+                        .collect(),
+                ),
+                state,
+            )?;
+        }
+        f("is_idle", msgpack::ValueRef::Boolean(self.is_idle), state)?;
+
+        Ok(())
+    }
+}
+
+impl msgpack::Serialize for ActivityAssets {
+    fn serialize<'a>(
+        &'a self,
+        f: msgpack::SerializeFn<'a>,
+        state: &mut msgpack::SerializeState,
+    ) -> crate::Result<()> {
+        if let Some(large_image) = &self.large_image {
+            f("large_image", msgpack::ValueRef::Str(large_image), state)?;
+        }
+        if let Some(large_text) = &self.large_text {
+            f("large_text", msgpack::ValueRef::Str(large_text), state)?;
+        }
+        if let Some(large_url) = &self.large_url {
+            f("large_url", msgpack::ValueRef::Str(large_url), state)?;
+        }
+        if let Some(small_image) = &self.small_image {
+            f("small_image", msgpack::ValueRef::Str(small_image), state)?;
+        }
+        if let Some(small_text) = &self.small_text {
+            f("small_text", msgpack::ValueRef::Str(small_text), state)?;
+        }
+        if let Some(small_url) = &self.small_url {
+            f("small_url", msgpack::ValueRef::Str(small_url), state)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl msgpack::Serialize for ActivityTimestamps {
+    fn serialize<'a>(
+        &'a self,
+        f: msgpack::SerializeFn<'a>,
+        state: &mut msgpack::SerializeState,
+    ) -> crate::Result<()> {
+        if let Some(start) = self.start {
+            f("start", msgpack::ValueRef::UInteger(start), state)?;
+        }
+        if let Some(end) = self.end {
+            f("end", msgpack::ValueRef::UInteger(end), state)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl msgpack::Serialize for ActivityButton {
+    fn serialize<'a>(
+        &'a self,
+        f: msgpack::SerializeFn<'a>,
+        state: &mut msgpack::SerializeState,
+    ) -> crate::Result<()> {
+        f("label", msgpack::ValueRef::Str(&self.label), state)?;
+        f("url", msgpack::ValueRef::Str(&self.url), state)?;
+
+        Ok(())
+    }
+}
+
 impl msgpack::Deserialize for Activity {
     fn deserialize<'a>(input: Value) -> crate::Result<Self> {
         let mut input = input.take_map().ok_or("Invalid activity")?;
@@ -294,3 +432,101 @@ impl msgpack::Deserialize for ActivityButton {
         Ok(ActivityButton { label, url })
     }
 }
+
+/// Reads an activity back from the JSON sent to Discord, which leaves out
+/// whether it is idle.
+impl json::Deserialize for Activity {
+    fn deserialize<'a>(
+        input: &HashMap<&'a str, json::Value<'a>>,
+    ) -> crate::Result<Self> {
+        let string = |key| input.get(key).and_then(|v| v.as_string());
+        let number = |key| input.get(key).and_then(|v| v.as_number());
+
+        let ty = match number("type").map(|n| n as u8) {
+            Some(2) => ActivityType::Listening,
+            Some(3) => ActivityType::Watching,
+            Some(5) => ActivityType::Competing,
+            _ => ActivityType::Playing,
+        };
+        let status_display_type =
+            match number("status_display_type").map(|n| n as u8) {
+                Some(1) => StatusDisplayType::State,
+                Some(2) => StatusDisplayType::Details,
+                _ => StatusDisplayType::Name,
+            };
+        let assets = match input.get("assets").and_then(|v| v.as_map()) {
+            Some(assets) => Some(ActivityAssets::deserialize(assets)?),
+            None => None,
+        };
+        let timestamps = match input.get("timestamps").and_then(|v| v.as_map())
+        {
+            Some(t) => Some(ActivityTimestamps::deserialize(t)?),
+            None => None,
+        };
+        let buttons = input
+            .get("buttons")
+            .and_then(|v| v.as_array())
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|v| v.as_map())
+            .map(ActivityButton::deserialize)
+            .collect::<crate::Result<_>>()?;
+
+        Ok(Activity {
+            ty,
+            status_display_type,
+            details: string("details"),
+            details_url: string("details_url"),
+            state: string("state"),
+            state_url: string("state_url"),
+            assets,
+            timestamps,
+            buttons,
+            is_idle: false,
+        })
+    }
+}
+
+impl json::Deserialize for ActivityAssets {
+    fn deserialize<'a>(
+        input: &HashMap<&'a str, json::Value<'a>>,
+    ) -> crate::Result<Self> {
+        let string = |key| input.get(key).and_then(|v| v.as_string());
+
+        Ok(ActivityAssets {
+            large_image: string("large_image"),
+            large_text: string("large_text"),
+            large_url: string("large_url"),
+            small_image: string("small_image"),
+            small_text: string("small_text"),
+            small_url: string("small_url"),
+        })
+    }
+}
+
+impl json::Deserialize for ActivityTimestamps {
+    fn deserialize<'a>(
+        input: &HashMap<&'a str, json::Value<'a>>,
+    ) -> crate::Result<Self> {
+        let number =
+            |key| input.get(key).and_then(|v| v.as_number()).map(|n| n as u64);
+
+        Ok(ActivityTimestamps {
+            start: number("start"),
+            end: number("end"),
+        })
+    }
+}
+
+impl json::Deserialize for ActivityButton {
+    fn deserialize<'a>(
+        input: &HashMap<&'a str, json::Value<'a>>,
+    ) -> crate::Result<Self> {
+        let string = |key| input.get(key).and_then(|v| v.as_string());
+
+        Ok(ActivityButton {
+            label: string("label").ok_or("Invalid activity button")?,
+            url: string("url").ok_or("Invalid activity button")?,
+        })
+    }
+}
@@ -0,0 +1,508 @@
+use std::ffi::OsString;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+
+use crate::debug;
+use crate::presence::activity::Activity;
+use crate::protocol::json::{self, Json};
+use crate::protocol::msgpack::{self, SerializeFn, SerializeState, ValueRef};
+use crate::types::config::HistoryConfig;
+use crate::util::glob;
+
+/// Log of the activities sent to Discord, one JSON object per line. The
+/// file is rotated once it would grow past the configured size.
+#[derive(Debug)]
+pub struct History {
+    path: PathBuf,
+    max_size: u64,
+    max_files: usize,
+    /// The entry written last, so that resending the same activity is not
+    /// logged again.
+    last: Option<HistoryEntry>,
+}
+
+/// Writes to a [`History`] from a thread of its own, so that updating
+/// Discord does not wait on the file. Queries are answered by the same
+/// thread and see every entry recorded before them.
+#[derive(Debug)]
+pub struct Recorder {
+    tx: Option<Sender<Request>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+/// Receives the entries matching a query, on the writer thread.
+pub type Reply = Box<dyn FnOnce(crate::Result<Vec<HistoryEntry>>) + Send>;
+
+enum Request {
+    Record(Box<HistoryEntry>),
+    Query(HistoryFilter, Reply),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    /// Unix timestamp, in milliseconds, at which Discord was updated.
+    pub timestamp: u64,
+    /// The session whose activity was sent, `None` if it came from none.
+    pub session: Option<u32>,
+    pub workspace: Option<String>,
+    /// The activity after privacy rules were applied, `None` if the
+    /// presence was cleared.
+    pub activity: Option<Activity>,
+}
+
+/// Which entries a query returns, newest first.
+#[derive(Debug, Default)]
+pub struct HistoryFilter {
+    pub session: Option<u32>,
+    /// Glob the workspace must match.
+    pub workspace: Option<String>,
+    /// Unix timestamp, in milliseconds, before which entries are skipped.
+    pub since: Option<u64>,
+    /// Most entries to return, 0 for all of them.
+    pub limit: usize,
+}
+
+impl History {
+    pub fn new(path: PathBuf, config: &HistoryConfig) -> Self {
+        Self {
+            path,
+            max_size: config.max_size,
+            max_files: config.max_files,
+            last: None,
+        }
+    }
+
+    /// Appends `entry` to the log, unless it only repeats the last one.
+    pub fn record(&mut self, entry: HistoryEntry) -> crate::Result<()> {
+        if self.last.as_ref().is_some_and(|last| {
+            last.session == entry.session
+                && last.workspace == entry.workspace
+                && last.activity == entry.activity
+        }) {
+            return Ok(());
+        }
+
+        let mut line = Json::serialize(&entry)?;
+        line.push('\n');
+
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let size = match fs::metadata(&self.path) {
+            Ok(metadata) => metadata.len(),
+            Err(e) if e.kind() == ErrorKind::NotFound => 0,
+            Err(e) => return Err(e.into()),
+        };
+        if self.max_size > 0
+            && size > 0
+            && size + line.len() as u64 > self.max_size
+        {
+            self.rotate()?;
+        }
+
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?
+            .write_all(line.as_bytes())?;
+        self.last = Some(entry);
+
+        Ok(())
+    }
+
+    /// Reads the entries matching `filter`, newest first. Lines that cannot
+    /// be parsed are skipped.
+    pub fn query(
+        &self,
+        filter: &HistoryFilter,
+    ) -> crate::Result<Vec<HistoryEntry>> {
+        let mut entries = Vec::new();
+
+        for path in self.files() {
+            let text = match fs::read_to_string(&path) {
+                Ok(text) => text,
+                Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
+
+            for entry in text.lines().rev().filter_map(parse) {
+                if filter.since.is_some_and(|since| entry.timestamp < since) {
+                    return Ok(entries);
+                }
+                if filter.matches(&entry) {
+                    entries.push(entry);
+                    if entries.len() == filter.limit {
+                        return Ok(entries);
+                    }
+                }
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// The log followed by its rotated files, from newest to oldest.
+    fn files(&self) -> Vec<PathBuf> {
+        std::iter::once(self.path.clone())
+            .chain((1..=self.max_files).map(|n| self.rotated(n)))
+            .collect()
+    }
+
+    fn rotated(&self, n: usize) -> PathBuf {
+        let mut name = OsString::from(&self.path);
+        name.push(format!(".{}", n));
+        name.into()
+    }
+
+    /// Shifts every rotated file up by one, dropping the oldest, and moves
+    /// the log to the first one.
+    fn rotate(&self) -> crate::Result<()> {
+        let files = self.files();
+        let ignore_missing = |result: std::io::Result<()>| match result {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        };
+
+        ignore_missing(fs::remove_file(&files[files.len() - 1]))?;
+        for pair in files.windows(2).rev() {
+            ignore_missing(fs::rename(&pair[0], &pair[1]))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Recorder {
+    /// Starts the thread `history` is written from.
+    pub fn new(history: History) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let thread = thread::spawn(move || run(rx, history));
+
+        Self {
+            tx: Some(tx),
+            thread: Some(thread),
+        }
+    }
+
+    /// Queues `entry` without waiting for it to be written. Failing to
+    /// write it is only logged.
+    pub fn record(&self, entry: HistoryEntry) {
+        if let Some(tx) = &self.tx
+            && tx.send(Request::Record(Box::new(entry))).is_err()
+        {
+            debug!("History writer is gone, dropping entry");
+        }
+    }
+
+    /// Queues a query for the entries matching `filter` without waiting
+    /// for it. Once the entries queued before were written, they are read
+    /// and passed to `reply`.
+    pub fn query(
+        &self,
+        filter: HistoryFilter,
+        reply: Reply,
+    ) -> crate::Result<()> {
+        self.tx
+            .as_ref()
+            .and_then(|tx| tx.send(Request::Query(filter, reply)).ok())
+            .ok_or("History writer is gone")?;
+
+        Ok(())
+    }
+
+    /// Stops the thread once it wrote the entries left in its queue.
+    pub fn stop(mut self) {
+        self.tx.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn run(rx: Receiver<Request>, mut history: History) {
+    for request in rx {
+        match request {
+            Request::Record(entry) => {
+                if let Err(e) = history.record(*entry) {
+                    debug!("Failed to write history: {}", e);
+                }
+            }
+            Request::Query(filter, reply) => reply(history.query(&filter)),
+        }
+    }
+}
+
+impl HistoryFilter {
+    fn matches(&self, entry: &HistoryEntry) -> bool {
+        self.session.is_none_or(|id| entry.session == Some(id))
+            && self.workspace.as_deref().is_none_or(|pattern| {
+                entry
+                    .workspace
+                    .as_deref()
+                    .is_some_and(|workspace| glob::matches(pattern, workspace))
+            })
+    }
+}
+
+fn parse(line: &str) -> Option<HistoryEntry> {
+    let map = Json::deserialize(line).ok()?;
+    let timestamp = map.get("timestamp")?.as_number()? as u64;
+    let session = map
+        .get("session")
+        .and_then(|v| v.as_number())
+        .map(|n| n as u32);
+    let workspace = map.get("workspace").and_then(|v| v.as_string());
+    let activity = match map.get("activity").and_then(|v| v.as_map()) {
+        Some(activity) => {
+            let mut activity =
+                <Activity as json::Deserialize>::deserialize(activity).ok()?;
+            activity.is_idle = map
+                .get("is_idle")
+                .and_then(|v| v.as_bool())
+                .unwrap_or_default();
+            Some(activity)
+        }
+        None => None,
+    };
+
+    Some(HistoryEntry {
+        timestamp,
+        session,
+        workspace,
+        activity,
+    })
+}
+
+impl json::Serialize for HistoryEntry {
+    fn serialize<'a>(
+        &'a self,
+        f: json::SerializeFn<'a>,
+        state: &mut json::SerializeState,
+    ) -> crate::Result<()> {
+        f(
+            "timestamp",
+            json::ValueRef::Number(self.timestamp as f64),
+            state,
+        )?;
+        f(
+            "session",
+            match self.session {
+                Some(id) => json::ValueRef::Number(id as f64),
+                None => json::ValueRef::Null,
+            },
+            state,
+        )?;
+        f(
+            "workspace",
+            match &self.workspace {
+                Some(workspace) => json::ValueRef::String(workspace),
+                None => json::ValueRef::Null,
+            },
+            state,
+        )?;
+        match &self.activity {
+            Some(activity) => {
+                f("activity", json::ValueRef::Object(activity), state)?;
+                f("is_idle", json::ValueRef::Boolean(activity.is_idle), state)?;
+            }
+            None => f("activity", json::ValueRef::Null, state)?,
+        }
+
+        Ok(())
+    }
+}
+
+impl msgpack::Serialize for HistoryEntry {
+    fn serialize<'a>(
+        &'a self,
+        f: SerializeFn<'a>,
+        state: &mut SerializeState,
+    ) -> crate::Result<()> {
+        f("timestamp", ValueRef::UInteger(self.timestamp), state)?;
+        f(
+            "session",
+            self.session
+                .map_or(ValueRef::Nil, |id| ValueRef::UInteger(id as u64)),
+            state,
+        )?;
+        f(
+            "workspace",
+            self.workspace
+                .as_deref()
+                .map_or(ValueRef::Nil, ValueRef::Str),
+            state,
+        )?;
+        f(
+            "activity",
+            self.activity.as_ref().map_or(ValueRef::Nil, |activity| {
+                ValueRef::Object(activity as &dyn msgpack::Serialize)
+            }),
+            state,
+        )?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::presence::activity::{
+        ActivityAssets, ActivityButton, ActivityTimestamps, ActivityType,
+    };
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "cord-history-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn entry(timestamp: u64, session: u32, details: &str) -> HistoryEntry {
+        HistoryEntry {
+            timestamp,
+            session: Some(session),
+            workspace: Some(format!("/work/{}", session)),
+            activity: Some(Activity {
+                details: Some(details.to_string()),
+                ..Default::default()
+            }),
+        }
+    }
+
+    fn config(max_size: u64, max_files: usize) -> HistoryConfig {
+        HistoryConfig {
+            enabled: true,
+            max_size,
+            max_files,
+        }
+    }
+
+    #[test]
+    fn entries_round_trip() {
+        let entry = HistoryEntry {
+            timestamp: 1_760_000_000_123,
+            session: Some(3),
+            workspace: Some("C:\\work\\\"acme\"".to_string()),
+            activity: Some(Activity {
+                ty: ActivityType::Watching,
+                details: Some("Editing main.rs\n\u{1F600}".to_string()),
+                assets: Some(ActivityAssets {
+                    large_image: Some("rust".to_string()),
+                    large_text: None,
+                    large_url: None,
+                    small_image: None,
+                    small_text: Some("Neovim".to_string()),
+                    small_url: None,
+                }),
+                timestamps: Some(ActivityTimestamps {
+                    start: Some(1_760_000_000),
+                    end: None,
+                }),
+                buttons: vec![ActivityButton {
+                    label: "Repo".to_string(),
+                    url: "https://example.com".to_string(),
+                }],
+                is_idle: true,
+                ..Default::default()
+            }),
+        };
+        let line = Json::serialize(&entry).unwrap();
+        assert_eq!(parse(&line), Some(entry));
+
+        let cleared = HistoryEntry {
+            timestamp: 5,
+            session: None,
+            workspace: None,
+            activity: None,
+        };
+        assert_eq!(parse(&Json::serialize(&cleared).unwrap()), Some(cleared));
+    }
+
+    #[test]
+    fn skips_repeated_entries_and_filters() {
+        let dir = temp_dir("filter");
+        let mut history = History::new(dir.join("history"), &config(0, 0));
+        history.record(entry(1, 1, "a")).unwrap();
+        history.record(entry(2, 1, "a")).unwrap();
+        history.record(entry(3, 2, "b")).unwrap();
+        history.record(entry(4, 1, "c")).unwrap();
+
+        let all = history.query(&HistoryFilter::default()).unwrap();
+        let times: Vec<_> = all.iter().map(|e| e.timestamp).collect();
+        assert_eq!(times, [4, 3, 1]);
+
+        let filter = HistoryFilter {
+            workspace: Some("/work/1".to_string()),
+            limit: 1,
+            ..Default::default()
+        };
+        assert_eq!(history.query(&filter).unwrap()[0].timestamp, 4);
+
+        let filter = HistoryFilter {
+            since: Some(3),
+            session: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(history.query(&filter).unwrap().len(), 1);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn recorder_writes_before_answering_queries() {
+        let dir = temp_dir("recorder");
+        let recorder =
+            Recorder::new(History::new(dir.join("history"), &config(0, 0)));
+        for i in 0..3 {
+            recorder.record(entry(i, 1, &i.to_string()));
+        }
+
+        let (tx, rx) = mpsc::channel();
+        recorder
+            .query(
+                HistoryFilter::default(),
+                Box::new(move |entries| tx.send(entries).unwrap()),
+            )
+            .unwrap();
+        let all = rx.recv().unwrap().unwrap();
+        let times: Vec<_> = all.iter().map(|e| e.timestamp).collect();
+        assert_eq!(times, [2, 1, 0]);
+
+        recorder.record(entry(3, 1, "3"));
+        recorder.stop();
+        let history = History::new(dir.join("history"), &config(0, 0));
+        assert_eq!(history.query(&HistoryFilter::default()).unwrap().len(), 4);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn rotates_and_drops_the_oldest_file() {
+        let dir = temp_dir("rotate");
+        let line = Json::serialize(&entry(10, 1, "0")).unwrap().len() + 1;
+        let mut history =
+            History::new(dir.join("history"), &config(line as u64 * 2, 1));
+        for i in 0..5 {
+            history.record(entry(10 + i, 1, &i.to_string())).unwrap();
+        }
+
+        assert!(dir.join("history.1").exists());
+        assert!(!dir.join("history.2").exists());
+        let times: Vec<_> = history
+            .query(&HistoryFilter::default())
+            .unwrap()
+            .iter()
+            .map(|e| e.timestamp)
+            .collect();
+        assert_eq!(times, [14, 13, 12]);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
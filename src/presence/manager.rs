@@ -1,18 +1,26 @@
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
 use std::sync::{Arc, RwLock};
 use std::thread;
 use std::time::{Duration, Instant};
 
 use crate::ipc::discord::client::RichClient;
+use crate::ipc::discord::error::{DiscordError, is_write_timeout};
+use crate::messages::events::local::ErrorEvent;
+use crate::messages::events::server::ActivityOutcome;
+use crate::messages::message::Message;
 use crate::presence::activity::Activity;
+use crate::presence::history::{HistoryEntry, HistoryFilter, Recorder, Reply};
 use crate::presence::packet::Packet;
+use crate::presence::privacy::Privacy;
 use crate::types::config::{SyncConfig, SyncMode};
-use crate::util::pad_activity_field;
-use crate::{debug, trace};
+use crate::util::{now, pad_activity_field};
+use crate::{debug, local_event, trace};
 
 #[derive(Clone)]
 enum PendingOp {
-    Update(Activity),
+    /// The activity and the session and workspace it came from.
+    Update(Box<Activity>, Option<u32>, Option<String>),
     Clear,
 }
 
@@ -24,10 +32,23 @@ pub struct ActivityManager {
     config: Arc<RwLock<SyncConfig>>,
     pending_op: Arc<RwLock<Option<PendingOp>>>,
     first_update: Arc<AtomicBool>,
+    /// Set while the presence is paused: Discord is cleared and updates are
+    /// ignored until it is resumed.
+    paused: Arc<AtomicBool>,
+    /// Applied to every activity before it is stored or sent.
+    privacy: Arc<RwLock<Privacy>>,
+    /// Logs what Discord was sent, if enabled.
+    history: Arc<RwLock<Option<Recorder>>>,
+    /// Where a lost connection is reported to the event loop.
+    events: Sender<Message>,
 }
 
 impl ActivityManager {
-    pub fn new(client_id: u64, pipe_paths: Vec<String>) -> Self {
+    pub fn new(
+        client_id: u64,
+        pipe_paths: Vec<String>,
+        events: Sender<Message>,
+    ) -> Self {
         debug!("Creating ActivityManager with client_id={}", client_id);
         let client =
             Arc::new(RwLock::new(RichClient::new(client_id, pipe_paths)));
@@ -37,6 +58,9 @@ impl ActivityManager {
         let config = Arc::new(RwLock::new(SyncConfig::default()));
         let pending_op = Arc::new(RwLock::new(None));
         let first_update = Arc::new(AtomicBool::new(true));
+        let paused = Arc::new(AtomicBool::new(false));
+        let privacy = Arc::new(RwLock::new(Privacy::default()));
+        let history = Arc::new(RwLock::new(None));
 
         let manager = Self {
             client,
@@ -46,6 +70,10 @@ impl ActivityManager {
             config,
             pending_op,
             first_update,
+            paused,
+            privacy,
+            history,
+            events,
         };
 
         manager.start_loop();
@@ -60,6 +88,9 @@ impl ActivityManager {
         let last_periodic_sync = self.last_periodic_sync.clone();
         let config = self.config.clone();
         let pending_op = self.pending_op.clone();
+        let paused = self.paused.clone();
+        let history = self.history.clone();
+        let events = self.events.clone();
 
         debug!("Starting activity manager background loop");
         thread::spawn(move || {
@@ -68,7 +99,7 @@ impl ActivityManager {
 
                 let config_guard = config.read().unwrap();
                 let enabled = config_guard.enabled;
-                if !enabled {
+                if !enabled || paused.load(Ordering::SeqCst) {
                     continue;
                 }
 
@@ -107,10 +138,18 @@ impl ActivityManager {
                                     client_guard.pid,
                                     Some(&padded_activity),
                                 );
-                                let _ = client_guard.update(&packet);
+                                let _ = written(
+                                    client_guard.update(&packet),
+                                    &client_guard,
+                                    &events,
+                                );
                             } else {
                                 trace!("Periodic sync: clearing activity");
-                                let _ = client_guard.clear();
+                                let _ = written(
+                                    client_guard.clear(),
+                                    &client_guard,
+                                    &events,
+                                );
                             }
 
                             if reset_on_update {
@@ -127,10 +166,15 @@ impl ActivityManager {
                             if let Some(op) = pending_opt.take() {
                                 let client_guard = client.read().unwrap();
                                 match op {
-                                    PendingOp::Update(mut activity) => {
+                                    PendingOp::Update(
+                                        mut activity,
+                                        session,
+                                        workspace,
+                                    ) => {
                                         trace!(
                                             "Deferred sync: updating activity"
                                         );
+                                        let logged = (*activity).clone();
                                         if pad_enabled {
                                             pad_activity_field(
                                                 &mut activity.details,
@@ -143,13 +187,30 @@ impl ActivityManager {
                                             client_guard.pid,
                                             Some(&activity),
                                         );
-                                        let _ = client_guard.update(&packet);
+                                        if let Ok(true) = written(
+                                            client_guard.update(&packet),
+                                            &client_guard,
+                                            &events,
+                                        ) {
+                                            record(
+                                                &history,
+                                                session,
+                                                workspace,
+                                                Some(logged),
+                                            );
+                                        }
                                     }
                                     PendingOp::Clear => {
                                         trace!(
                                             "Deferred sync: clearing activity"
                                         );
-                                        let _ = client_guard.clear();
+                                        if let Ok(true) = written(
+                                            client_guard.clear(),
+                                            &client_guard,
+                                            &events,
+                                        ) {
+                                            record(&history, None, None, None);
+                                        }
                                     }
                                 }
                                 *last_update_lock = now;
@@ -169,8 +230,110 @@ impl ActivityManager {
         *self.config.write().unwrap() = new_config;
     }
 
-    pub fn update(&self, activity: Activity) -> crate::Result<()> {
+    pub fn get_config(&self) -> SyncConfig {
+        self.config.read().unwrap().clone()
+    }
+
+    pub fn set_privacy(&self, privacy: Privacy) {
+        debug!(
+            "Setting privacy rules: workspaces={}, redactions={}, private_hosts={}",
+            privacy.workspaces.len(),
+            privacy.redactions.len(),
+            privacy.private_hosts.len()
+        );
+        *self.privacy.write().unwrap() = privacy;
+    }
+
+    /// Starts or stops logging what Discord is sent. The previous log is
+    /// done writing before the new one starts.
+    pub fn set_history(&self, history: Option<Recorder>) {
+        debug!("Setting history: enabled={}", history.is_some());
+        let mut current = self.history.write().unwrap();
+        if let Some(previous) = std::mem::replace(&mut *current, history) {
+            previous.stop();
+        }
+    }
+
+    /// Queues a query for the logged entries matching `filter`, which are
+    /// passed to `reply` once read, or returns `None` if the history is
+    /// disabled.
+    pub fn history(
+        &self,
+        filter: HistoryFilter,
+        reply: Reply,
+    ) -> Option<crate::Result<()>> {
+        self.history
+            .read()
+            .unwrap()
+            .as_ref()
+            .map(|history| history.query(filter, reply))
+    }
+
+    /// The activity Discord is displaying, after privacy rules were applied.
+    pub fn displayed(&self) -> Option<Activity> {
+        self.last_activity.read().unwrap().clone()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    /// Clears Discord and ignores updates until `resume` is called.
+    pub fn pause(&self) -> crate::Result<()> {
+        debug!("Pausing presence");
+        self.paused.store(true, Ordering::SeqCst);
+        *self.pending_op.write().unwrap() = None;
+        *self.last_activity.write().unwrap() = None;
+        if self.clear_client()? {
+            self.record(None, None, None);
+        }
+
+        Ok(())
+    }
+
+    /// Accepts updates again. The caller sends the activity to show.
+    pub fn resume(&self) {
+        debug!("Resuming presence");
+        self.paused.store(false, Ordering::SeqCst);
+    }
+
+    /// The activity `update` would display for `workspace`, with privacy
+    /// rules applied, or `None` if nothing would be displayed.
+    pub fn preview(
+        &self,
+        activity: Activity,
+        workspace: Option<&str>,
+    ) -> Option<Activity> {
+        if self.is_paused() {
+            return None;
+        }
+        self.privacy.read().unwrap().apply(activity, workspace)
+    }
+
+    /// Sends the activity of `session` and `workspace`, or clears the
+    /// presence if the privacy rules hide it. The outcome tells whether
+    /// Discord was written to, which it is not while paused, when the update
+    /// is deferred or when the connection was lost, and the activity as
+    /// displayed, with privacy rules applied.
+    pub fn update(
+        &self,
+        activity: Activity,
+        session: Option<u32>,
+        workspace: Option<&str>,
+    ) -> crate::Result<ActivityOutcome> {
         trace!("ActivityManager update called");
+        if self.is_paused() {
+            trace!("Ignoring activity update while paused");
+            return Ok(ActivityOutcome::new(false, None));
+        }
+        let Some(activity) =
+            self.privacy.read().unwrap().apply(activity, workspace)
+        else {
+            debug!("Hiding activity: its workspace is private");
+            return Ok(ActivityOutcome::new(self.clear()?, None));
+        };
+        let displayed = activity.clone();
+        let mut written = false;
         let config = self.config.read().unwrap();
 
         match (config.enabled, &config.mode) {
@@ -193,13 +356,20 @@ impl ActivityManager {
                     }
                     let packet =
                         Packet::new(client.pid, Some(&padded_activity));
-                    client.update(&packet)?;
+                    written = self.written(client.update(&packet), &client)?;
+                    if written {
+                        self.record(session, workspace, Some(activity.clone()));
+                    }
                     *last_update = Instant::now();
                     *self.pending_op.write().unwrap() = None;
                 } else {
                     trace!("Deferring activity update");
                     *self.pending_op.write().unwrap() =
-                        Some(PendingOp::Update(activity.clone()));
+                        Some(PendingOp::Update(
+                            Box::new(activity.clone()),
+                            session,
+                            workspace.map(str::to_string),
+                        ));
                 }
                 *self.last_activity.write().unwrap() = Some(activity);
             }
@@ -212,7 +382,10 @@ impl ActivityManager {
                     pad_activity_field(&mut padded_activity.state);
                 }
                 let packet = Packet::new(client.pid, Some(&padded_activity));
-                client.update(&packet)?;
+                written = self.written(client.update(&packet), &client)?;
+                if written {
+                    self.record(session, workspace, Some(activity.clone()));
+                }
                 *self.last_update.write().unwrap() = Instant::now();
                 *self.last_activity.write().unwrap() = Some(activity);
             }
@@ -225,14 +398,51 @@ impl ActivityManager {
                     pad_activity_field(&mut padded_activity.state);
                 }
                 let packet = Packet::new(client.pid, Some(&padded_activity));
-                client.update(&packet)?;
+                written = self.written(client.update(&packet), &client)?;
+                if written {
+                    self.record(session, workspace, Some(activity.clone()));
+                }
+                *self.last_activity.write().unwrap() = Some(activity);
             }
         }
-        Ok(())
+        Ok(ActivityOutcome::new(written, Some(displayed)))
+    }
+
+    fn written(
+        &self,
+        result: crate::Result<()>,
+        client: &RichClient,
+    ) -> crate::Result<bool> {
+        written(result, client, &self.events)
+    }
+
+    fn clear_client(&self) -> crate::Result<bool> {
+        let client = self.client.read().unwrap();
+        self.written(client.clear(), &client)
     }
 
-    pub fn clear(&self) -> crate::Result<()> {
+    fn record(
+        &self,
+        session: Option<u32>,
+        workspace: Option<&str>,
+        activity: Option<Activity>,
+    ) {
+        record(
+            &self.history,
+            session,
+            workspace.map(str::to_string),
+            activity,
+        );
+    }
+
+    /// Clears the presence, returning whether Discord was written to.
+    pub fn clear(&self) -> crate::Result<bool> {
         trace!("ActivityManager clear called");
+        if self.is_paused() {
+            trace!("Ignoring activity clear while paused");
+            return Ok(false);
+        }
+        let mut written = false;
         let config = self.config.read().unwrap();
 
         match (config.enabled, &config.mode) {
@@ -245,7 +455,10 @@ impl ActivityManager {
                     || Instant::now().duration_since(*last_update) >= interval
                 {
                     debug!("Clearing Discord activity (defer mode, immediate)");
-                    self.client.read().unwrap().clear()?;
+                    written = self.clear_client()?;
+                    if written {
+                        self.record(None, None, None);
+                    }
                     *last_update = Instant::now();
                     *self.pending_op.write().unwrap() = None;
                 } else {
@@ -256,11 +469,96 @@ impl ActivityManager {
             }
             _ => {
                 debug!("Clearing Discord activity");
-                self.client.read().unwrap().clear()?;
+                written = self.clear_client()?;
+                if written {
+                    self.record(None, None, None);
+                }
                 *self.last_activity.write().unwrap() = None;
                 *self.last_update.write().unwrap() = Instant::now();
             }
         }
-        Ok(())
+        Ok(written)
+    }
+}
+
+/// Whether a write to Discord went through. One Discord did not accept in
+/// time means the connection is gone: the client is marked as not ready and
+/// the event loop is told, so it reconnects instead of failing the event
+/// that sent it.
+fn written(
+    result: crate::Result<()>,
+    client: &RichClient,
+    events: &Sender<Message>,
+) -> crate::Result<bool> {
+    let Err(e) = result else {
+        return Ok(true);
+    };
+    if !is_write_timeout(&e) {
+        return Err(e);
+    }
+
+    debug!("Treating the write timeout as a lost connection: {}", e);
+    client.is_ready.store(false, Ordering::SeqCst);
+    events
+        .send(local_event!(
+            0,
+            Error,
+            ErrorEvent::new(Box::new(DiscordError::ConnectionClosed))
+        ))
+        .ok();
+
+    Ok(false)
+}
+
+/// Queues what Discord was sent for the history, if it is enabled. It is
+/// written by the history's own thread, failing to do so does not fail the
+/// update.
+fn record(
+    history: &RwLock<Option<Recorder>>,
+    session: Option<u32>,
+    workspace: Option<String>,
+    activity: Option<Activity>,
+) {
+    if let Some(history) = history.read().unwrap().as_ref() {
+        history.record(HistoryEntry {
+            timestamp: now().as_millis() as u64,
+            session,
+            workspace,
+            activity,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messages::events::event::Event;
+    use crate::messages::events::local::LocalEvent;
+    use std::sync::mpsc;
+
+    #[test]
+    fn write_timeout_is_a_lost_connection() {
+        let (tx, rx) = mpsc::channel();
+        let client = RichClient::new(0, vec![]);
+        client.is_ready.store(true, Ordering::SeqCst);
+
+        assert!(written(Ok(()), &client, &tx).unwrap());
+        assert!(rx.try_recv().is_err());
+
+        let timeout = Err(DiscordError::WriteTimeout(100).into());
+        assert!(!written(timeout, &client, &tx).unwrap());
+        assert!(!client.is_ready.load(Ordering::SeqCst));
+        let message = rx.try_recv().unwrap();
+        let Event::Local(LocalEvent::Error(event)) = message.event else {
+            panic!("expected an error event, got {:?}", message.event);
+        };
+        assert!(matches!(
+            event.error.downcast_ref::<DiscordError>(),
+            Some(DiscordError::ConnectionClosed)
+        ));
+
+        let closed = Err(DiscordError::PipeNotFound.into());
+        assert!(written(closed, &client, &tx).is_err());
+        assert!(rx.try_recv().is_err());
     }
 }
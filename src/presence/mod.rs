@@ -1,3 +1,5 @@
 pub mod activity;
+pub mod history;
 pub mod manager;
 pub mod packet;
+pub mod privacy;
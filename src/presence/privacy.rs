@@ -0,0 +1,368 @@
+use crate::presence::activity::Activity;
+use crate::protocol::msgpack::{Deserialize, Value};
+use crate::util::glob;
+use crate::util::regex::Regex;
+use crate::{remove_field, remove_field_or_none};
+
+/// Rules applied to every activity before it is sent to Discord, whichever
+/// client it came from.
+#[derive(Debug, Clone, Default)]
+pub struct Privacy {
+    /// Checked in order, the first rule matching the workspace applies.
+    pub workspaces: Vec<WorkspaceRule>,
+    pub redactions: Vec<Redaction>,
+    /// Host globs whose URLs are stripped from buttons and links.
+    pub private_hosts: Vec<String>,
+}
+
+/// Hides the presence of matching workspaces, or replaces it with
+/// `activity`.
+#[derive(Debug, Clone)]
+pub struct WorkspaceRule {
+    pub glob: String,
+    pub activity: Option<Activity>,
+}
+
+/// Replaces the matches of `pattern` in the displayed text.
+#[derive(Debug, Clone)]
+pub struct Redaction {
+    pub pattern: Regex,
+    pub replacement: String,
+}
+
+impl Privacy {
+    /// Applies the rules to an activity of `workspace`, returning `None` if
+    /// it must not be displayed at all.
+    pub fn apply(
+        &self,
+        mut activity: Activity,
+        workspace: Option<&str>,
+    ) -> Option<Activity> {
+        if let Some(workspace) = workspace
+            && let Some(rule) = self
+                .workspaces
+                .iter()
+                .find(|rule| glob::matches(&rule.glob, workspace))
+        {
+            let mut replacement = rule.activity.clone()?;
+            if replacement.timestamps.is_none() {
+                replacement.timestamps = activity.timestamps.take();
+            }
+            replacement.is_idle = activity.is_idle;
+            activity = replacement;
+        }
+
+        for field in [&mut activity.details, &mut activity.state] {
+            self.redact(field);
+        }
+        if let Some(assets) = &mut activity.assets {
+            self.redact(&mut assets.large_text);
+            self.redact(&mut assets.small_text);
+        }
+
+        if !self.private_hosts.is_empty() {
+            activity
+                .buttons
+                .retain(|button| !self.is_private(&button.url));
+            let mut urls =
+                vec![&mut activity.details_url, &mut activity.state_url];
+            if let Some(assets) = &mut activity.assets {
+                urls.extend([&mut assets.large_url, &mut assets.small_url]);
+            }
+            for url in urls {
+                if url.as_deref().is_some_and(|url| self.is_private(url)) {
+                    *url = None;
+                }
+            }
+        }
+
+        Some(activity)
+    }
+
+    /// Adds the rules of `other` that are not already part of these, so
+    /// that the rules of every client apply. Workspace rules of `other`
+    /// come after these and do not replace one with the same glob.
+    pub fn merge(&mut self, other: &Privacy) {
+        for rule in &other.workspaces {
+            if !self.workspaces.iter().any(|r| r.glob == rule.glob) {
+                self.workspaces.push(rule.clone());
+            }
+        }
+        for redaction in &other.redactions {
+            if !self.redactions.iter().any(|r| {
+                r.pattern.to_string() == redaction.pattern.to_string()
+                    && r.replacement == redaction.replacement
+            }) {
+                self.redactions.push(redaction.clone());
+            }
+        }
+        for host in &other.private_hosts {
+            if !self.private_hosts.contains(host) {
+                self.private_hosts.push(host.clone());
+            }
+        }
+    }
+
+    fn redact(&self, text: &mut Option<String>) {
+        let Some(value) = text else {
+            return;
+        };
+        for redaction in &self.redactions {
+            *value =
+                redaction.pattern.replace_all(value, &redaction.replacement);
+        }
+        if value.is_empty() {
+            *text = None;
+        }
+    }
+
+    fn is_private(&self, url: &str) -> bool {
+        let host = host(url).to_lowercase();
+        self.private_hosts
+            .iter()
+            .any(|pattern| glob::matches(pattern, &host))
+    }
+}
+
+/// Extracts the host of `url`, without its user info and port.
+fn host(url: &str) -> &str {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    let host = authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, host)| host);
+
+    match host.strip_prefix('[') {
+        Some(ipv6) => ipv6.split(']').next().unwrap_or_default(),
+        None => host.split(':').next().unwrap_or_default(),
+    }
+}
+
+impl Deserialize for Privacy {
+    fn deserialize<'a>(input: Value) -> crate::Result<Self> {
+        let mut input = input.take_map().ok_or("Invalid privacy config")?;
+        let mut array = |field: &str| {
+            input
+                .remove(field)
+                .and_then(|v| v.take_array())
+                .unwrap_or_default()
+        };
+
+        let workspaces = array("workspaces")
+            .into_iter()
+            .map(WorkspaceRule::deserialize)
+            .collect::<crate::Result<_>>()?;
+        let redactions = array("redact")
+            .into_iter()
+            .map(Redaction::deserialize)
+            .collect::<crate::Result<_>>()?;
+        let private_hosts = array("private_hosts")
+            .into_iter()
+            .map(|v| {
+                v.take_string()
+                    .map(|host| host.to_lowercase())
+                    .ok_or("Invalid private host")
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Privacy {
+            workspaces,
+            redactions,
+            private_hosts,
+        })
+    }
+}
+
+impl Deserialize for WorkspaceRule {
+    fn deserialize<'a>(input: Value) -> crate::Result<Self> {
+        let mut input = input.take_map().ok_or("Invalid workspace rule")?;
+
+        let glob = glob::expand_home(
+            remove_field!(input, "glob", |v| v.take_string())
+        );
+        let activity = match input.remove("activity") {
+            Some(v) => Some(Activity::deserialize(v)?),
+            None => None,
+        };
+
+        Ok(WorkspaceRule { glob, activity })
+    }
+}
+
+impl Deserialize for Redaction {
+    fn deserialize<'a>(input: Value) -> crate::Result<Self> {
+        let mut input = input.take_map().ok_or("Invalid redaction rule")?;
+
+        let pattern = remove_field!(input, "pattern", |v| v.take_string());
+        let pattern = Regex::new(&pattern).map_err(|e| {
+            format!("Invalid redaction pattern '{}': {}", pattern, e)
+        })?;
+        let replacement = remove_field_or_none!(input, "replacement", |v| v
+            .take_string())
+        .unwrap_or_default();
+
+        Ok(Redaction {
+            pattern,
+            replacement,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::presence::activity::{
+        ActivityAssets, ActivityButton, ActivityTimestamps,
+    };
+
+    fn activity() -> Activity {
+        Activity {
+            details: Some("Editing acme-billing/main.rs".to_string()),
+            state: Some("Workspace: acme-billing".to_string()),
+            details_url: Some("https://git.corp.example/acme".to_string()),
+            assets: Some(ActivityAssets {
+                large_image: None,
+                large_text: Some("acme-billing".to_string()),
+                large_url: None,
+                small_image: None,
+                small_text: Some("Neovim".to_string()),
+                small_url: Some("https://neovim.io".to_string()),
+            }),
+            timestamps: Some(ActivityTimestamps {
+                start: Some(100),
+                end: None,
+            }),
+            buttons: vec![
+                ActivityButton {
+                    label: "Repository".to_string(),
+                    url: "https://user@git.corp.example:8443/acme".to_string(),
+                },
+                ActivityButton {
+                    label: "Website".to_string(),
+                    url: "https://example.com".to_string(),
+                },
+            ],
+            is_idle: true,
+            ..Default::default()
+        }
+    }
+
+    fn redaction(pattern: &str, replacement: &str) -> Redaction {
+        Redaction {
+            pattern: Regex::new(pattern).unwrap(),
+            replacement: replacement.to_string(),
+        }
+    }
+
+    #[test]
+    fn workspace_rules_hide_or_replace() {
+        let privacy = Privacy {
+            workspaces: vec![
+                WorkspaceRule {
+                    glob: "/work/secret/**".to_string(),
+                    activity: None,
+                },
+                WorkspaceRule {
+                    glob: "/work/**".to_string(),
+                    activity: Some(Activity {
+                        details: Some("Working".to_string()),
+                        ..Default::default()
+                    }),
+                },
+            ],
+            ..Default::default()
+        };
+
+        assert_eq!(privacy.apply(activity(), Some("/work/secret/a")), None);
+
+        let replaced = privacy.apply(activity(), Some("/work/acme")).unwrap();
+        assert_eq!(replaced.details.as_deref(), Some("Working"));
+        assert_eq!(replaced.state, None);
+        assert!(replaced.buttons.is_empty());
+        assert_eq!(replaced.timestamps, activity().timestamps);
+        assert!(replaced.is_idle);
+
+        assert_eq!(
+            privacy.apply(activity(), Some("/home/me")),
+            Some(activity())
+        );
+        assert_eq!(privacy.apply(activity(), None), Some(activity()));
+    }
+
+    #[test]
+    fn redacts_text_fields() {
+        let privacy = Privacy {
+            redactions: vec![
+                redaction(r"(?i)acme-\w+", "client"),
+                redaction("^Workspace: client$", ""),
+            ],
+            ..Default::default()
+        };
+
+        let redacted = privacy.apply(activity(), None).unwrap();
+        assert_eq!(redacted.details.as_deref(), Some("Editing client/main.rs"));
+        assert_eq!(redacted.state, None);
+        let assets = redacted.assets.unwrap();
+        assert_eq!(assets.large_text.as_deref(), Some("client"));
+        assert_eq!(assets.small_text.as_deref(), Some("Neovim"));
+        assert_eq!(redacted.buttons.len(), 2);
+    }
+
+    #[test]
+    fn strips_urls_of_private_hosts() {
+        let privacy = Privacy {
+            private_hosts: vec!["*.corp.example".to_string()],
+            ..Default::default()
+        };
+
+        let stripped = privacy.apply(activity(), None).unwrap();
+        assert_eq!(stripped.details_url, None);
+        assert_eq!(stripped.buttons.len(), 1);
+        assert_eq!(stripped.buttons[0].label, "Website");
+        assert_eq!(
+            stripped.assets.unwrap().small_url.as_deref(),
+            Some("https://neovim.io")
+        );
+    }
+
+    #[test]
+    fn merges_rules_of_every_client() {
+        let mut privacy = Privacy {
+            workspaces: vec![WorkspaceRule {
+                glob: "/work/**".to_string(),
+                activity: None,
+            }],
+            redactions: vec![redaction("acme", "client")],
+            private_hosts: vec!["*.corp.example".to_string()],
+        };
+        privacy.merge(&Privacy::default());
+        privacy.merge(&Privacy {
+            workspaces: vec![
+                WorkspaceRule {
+                    glob: "/work/**".to_string(),
+                    activity: Some(Activity::default()),
+                },
+                WorkspaceRule {
+                    glob: "/secret/**".to_string(),
+                    activity: None,
+                },
+            ],
+            redactions: vec![redaction("acme", "client")],
+            private_hosts: vec!["git.example".to_string()],
+        });
+
+        assert_eq!(privacy.workspaces.len(), 2);
+        assert_eq!(privacy.apply(activity(), Some("/work/a")), None);
+        assert_eq!(privacy.apply(activity(), Some("/secret/a")), None);
+        assert_eq!(privacy.redactions.len(), 1);
+        assert_eq!(privacy.private_hosts, ["*.corp.example", "git.example"]);
+    }
+
+    #[test]
+    fn extracts_hosts() {
+        assert_eq!(host("https://example.com"), "example.com");
+        assert_eq!(host("https://u:p@example.com:80/a?b#c"), "example.com");
+        assert_eq!(host("http://[::1]:8080/"), "::1");
+        assert_eq!(host("example.com/path"), "example.com");
+    }
+}
@@ -31,10 +31,11 @@ impl Value<'_> {
         }
     }
 
+    /// Returns the string with its escape sequences decoded.
     #[inline]
     pub fn as_string(&self) -> Option<String> {
         match self {
-            Value::String(s) => Some(s.to_string()),
+            Value::String(s) => Some(unescape(s)),
             _ => None,
         }
     }
@@ -76,3 +77,74 @@ impl Value<'_> {
         matches!(self, Value::Null)
     }
 }
+
+/// Decodes the escape sequences of a raw JSON string slice. Invalid escapes
+/// are kept as they are, unpaired surrogates are replaced.
+fn unescape(s: &str) -> String {
+    if !s.contains('\\') {
+        return s.to_string();
+    }
+
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('"') => out.push('"'),
+            Some('\\') => out.push('\\'),
+            Some('/') => out.push('/'),
+            Some('b') => out.push('\u{8}'),
+            Some('f') => out.push('\u{c}'),
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('t') => out.push('\t'),
+            Some('u') => {
+                let mut code = hex4(chars.as_str());
+                chars.nth(3);
+                if let Some(high @ 0xD800..=0xDBFF) = code {
+                    let low = chars
+                        .as_str()
+                        .strip_prefix("\\u")
+                        .and_then(hex4)
+                        .filter(|low| (0xDC00..=0xDFFF).contains(low));
+                    code = low.map(|low| {
+                        chars.nth(5);
+                        0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+                    });
+                }
+                out.push(
+                    code.and_then(char::from_u32)
+                        .unwrap_or(char::REPLACEMENT_CHARACTER),
+                );
+            }
+            Some(other) => {
+                out.push('\\');
+                out.push(other);
+            }
+            None => out.push('\\'),
+        }
+    }
+
+    out
+}
+
+fn hex4(s: &str) -> Option<u32> {
+    u32::from_str_radix(s.get(..4)?, 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unescapes_strings() {
+        assert_eq!(unescape("plain"), "plain");
+        assert_eq!(unescape(r#"a\"b\\c\/d\n"#), "a\"b\\c/d\n");
+        assert_eq!(unescape(r"\u00e9\u0001"), "\u{e9}\u{1}");
+        assert_eq!(unescape(r"\ud83d\ude00"), "\u{1F600}");
+        assert_eq!(unescape(r"\ud83d!"), "\u{FFFD}!");
+    }
+}
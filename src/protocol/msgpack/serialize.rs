@@ -2,9 +2,9 @@
 
 use super::value::ValueRef;
 use super::{
-    ARRAY16, ARRAY32, FALSE, FIXARRAY_VALUE, FIXMAP_SIZE_MASK, FIXMAP_VALUE,
-    FIXSTR_VALUE, FLOAT64, INT64, MAP16, MAP32, MsgPack, NIL, STR16, STR32,
-    TRUE, UINT8, UINT16, UINT32, UINT64,
+    ARRAY16, ARRAY32, FALSE, FIXARRAY_VALUE, FIXMAP_VALUE, FIXSTR_VALUE,
+    FLOAT64, INT64, MAP16, MAP32, MsgPack, NIL, STR16, STR32, TRUE, UINT8,
+    UINT16, UINT32, UINT64,
 };
 use crate::protocol::error::ProtocolError;
 
@@ -51,7 +51,8 @@ impl<T: Serialize + std::fmt::Debug> SerializeObj for T {}
 
 pub struct SerializeState {
     buf: Vec<u8>,
-    stack: Vec<usize>,
+    /// Header position and entry count of each map being written.
+    stack: Vec<(usize, usize)>,
 }
 
 impl SerializeState {
@@ -62,8 +63,38 @@ impl SerializeState {
         }
     }
 
+    /// Starts a map whose size is not known upfront by reserving a single
+    /// header byte, which `pop_scope` fills in once all entries are written.
     fn push_scope(&mut self) {
-        self.stack.push(self.buf.len());
+        self.stack.push((self.buf.len(), 0));
+        self.buf.push(FIXMAP_VALUE);
+    }
+
+    fn pop_scope(&mut self) -> crate::Result<()> {
+        let (pos, len) =
+            self.stack.pop().ok_or(ProtocolError::InvalidLength)?;
+        match len {
+            0..=15 => self.buf[pos] = FIXMAP_VALUE | (len as u8),
+            16..=65535 => {
+                let [hi, lo] = (len as u16).to_be_bytes();
+                self.buf.splice(pos..pos + 1, [MAP16, hi, lo]);
+            }
+            _ => {
+                if len > u32::MAX as usize {
+                    return Err(ProtocolError::InvalidLength.into());
+                }
+                let mut header = vec![MAP32];
+                header.extend_from_slice(&(len as u32).to_be_bytes());
+                self.buf.splice(pos..pos + 1, header);
+            }
+        }
+        Ok(())
+    }
+
+    fn count_entry(&mut self) {
+        if let Some((_, len)) = self.stack.last_mut() {
+            *len += 1;
+        }
     }
 
     fn write_u8(&mut self, v: u8) {
@@ -115,7 +146,7 @@ impl std::fmt::Debug for ValueRef<'_> {
 impl MsgPack {
     pub fn serialize(value: &dyn Serialize) -> crate::Result<Vec<u8>> {
         let mut state = SerializeState::new();
-        state.write_u8(FIXMAP_VALUE | 2);
+        state.push_scope();
 
         fn serialize_adapter(
             key: &str,
@@ -126,6 +157,7 @@ impl MsgPack {
         }
 
         value.serialize(serialize_adapter, &mut state)?;
+        state.pop_scope()?;
         Ok(state.buf)
     }
 
@@ -223,7 +255,7 @@ impl MsgPack {
             ValueRef::Object(obj) => {
                 state.push_scope();
                 obj.serialize(|k, v, s| Self::write_kv(k, &v, s), state)?;
-                Ok(())
+                state.pop_scope()
             }
             ValueRef::Map(map) => {
                 let len = map.len();
@@ -257,7 +289,95 @@ impl MsgPack {
         value: &ValueRef,
         state: &mut SerializeState,
     ) -> crate::Result<()> {
+        state.count_entry();
         Self::write_str(key, state)?;
         Self::write_value(value, state)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::protocol::msgpack::Value;
+
+    /// A map of `keys` to their index, optionally holding another map.
+    #[derive(Debug)]
+    struct Entries {
+        keys: Vec<String>,
+        nested: Option<Box<Entries>>,
+    }
+
+    impl Entries {
+        fn new(len: usize) -> Self {
+            Self {
+                keys: (0..len).map(|i| format!("k{}", i)).collect(),
+                nested: None,
+            }
+        }
+
+        fn expected(&self) -> Value {
+            let mut map: HashMap<String, Value> = self
+                .keys
+                .iter()
+                .enumerate()
+                .map(|(i, key)| (key.clone(), Value::UInteger(i as u64)))
+                .collect();
+            if let Some(nested) = &self.nested {
+                map.insert("nested".to_string(), nested.expected());
+            }
+            Value::Map(map)
+        }
+    }
+
+    impl Serialize for Entries {
+        fn serialize<'a>(
+            &'a self,
+            f: SerializeFn<'a>,
+            state: &mut SerializeState,
+        ) -> crate::Result<()> {
+            for (i, key) in self.keys.iter().enumerate() {
+                f(key, ValueRef::UInteger(i as u64), state)?;
+            }
+            if let Some(nested) = &self.nested {
+                f("nested", ValueRef::Object(nested.as_ref()), state)?;
+            }
+            Ok(())
+        }
+    }
+
+    fn round_trip(entries: &Entries) -> Vec<u8> {
+        let bytes = MsgPack::serialize(entries).unwrap();
+        assert_eq!(MsgPack::deserialize(&bytes).unwrap(), entries.expected());
+        bytes
+    }
+
+    #[test]
+    fn map_headers_fit_the_entry_count() {
+        assert_eq!(round_trip(&Entries::new(0)), [FIXMAP_VALUE]);
+        assert_eq!(round_trip(&Entries::new(15))[0], FIXMAP_VALUE | 15);
+        assert_eq!(round_trip(&Entries::new(16))[..3], [MAP16, 0, 16]);
+        assert_eq!(round_trip(&Entries::new(65535))[..3], [MAP16, 0xff, 0xff]);
+        assert_eq!(round_trip(&Entries::new(65536))[..5], [MAP32, 0, 1, 0, 0]);
+    }
+
+    #[test]
+    fn nested_maps_keep_their_own_count() {
+        let entries = Entries {
+            nested: Some(Box::new(Entries {
+                nested: Some(Box::new(Entries::new(0))),
+                ..Entries::new(16)
+            })),
+            ..Entries::new(3)
+        };
+        let bytes = round_trip(&entries);
+        assert_eq!(bytes[0], FIXMAP_VALUE | 4);
+
+        let wide = Entries {
+            nested: Some(Box::new(Entries::new(2))),
+            ..Entries::new(20)
+        };
+        round_trip(&wide);
+    }
+}
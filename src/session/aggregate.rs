@@ -0,0 +1,137 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use crate::presence::activity::Activity;
+use crate::session::Session;
+use crate::session::sharing::Sharing;
+use crate::util::template::Template;
+
+pub const DEFAULT_DETAILS: &str = "{editors:editor} · {projects:project}";
+pub const DEFAULT_STATE: &str = "{focused.details}";
+
+/// Fields available to aggregate templates.
+pub const FIELDS: &[&str] = &[
+    // Connected editors.
+    "editors",
+    // Distinct workspaces among them.
+    "projects",
+    // Editors whose activity is not idle.
+    "active",
+    // Editors whose activity is idle.
+    "idle",
+    // Workspace names, comma separated.
+    "workspaces",
+    // The same fields of the focused editor's activity.
+    "focused.details",
+    "focused.state",
+    "focused.workspace",
+    "focused.editor",
+];
+
+/// Summarizes every session in a single synthetic activity instead of
+/// showing one session's activity.
+#[derive(Debug, Clone)]
+pub struct Aggregate {
+    pub details: Template,
+    pub state: Template,
+}
+
+impl Aggregate {
+    pub fn new(
+        details: Option<&str>,
+        state: Option<&str>,
+    ) -> Result<Self, String> {
+        let details = Template::parse(details.unwrap_or(DEFAULT_DETAILS))?;
+        let state = Template::parse(state.unwrap_or(DEFAULT_STATE))?;
+
+        if let Some(field) = details
+            .fields()
+            .chain(state.fields())
+            .find(|field| !FIELDS.contains(field))
+        {
+            return Err(format!("Unknown template field: {}", field));
+        }
+
+        Ok(Self { details, state })
+    }
+
+    /// Builds the activity summarizing `sessions` whose workspace `sharing`
+    /// allows. Assets, timestamps and buttons come from the focused session,
+    /// or `selected` if no session with an activity has focus.
+    pub fn build(
+        &self,
+        sessions: &HashMap<u32, Session>,
+        selected: u32,
+        sharing: &Sharing,
+    ) -> Option<Activity> {
+        let editors: Vec<&Session> = sessions
+            .values()
+            .filter(|s| s.config.is_some() || s.last_activity.is_some())
+            .filter(|s| sharing.is_allowed(s.workspace.as_deref()))
+            .collect();
+        let focused = editors
+            .iter()
+            .copied()
+            .filter(|s| s.is_focused && s.last_activity.is_some())
+            .max_by_key(|s| s.last_focused)
+            .or_else(|| sessions.get(&selected))?;
+        let mut activity = focused.last_activity.clone()?;
+
+        let mut workspaces: Vec<&str> = editors
+            .iter()
+            .filter_map(|s| s.workspace.as_deref())
+            .map(|workspace| {
+                Path::new(workspace)
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .unwrap_or(workspace)
+            })
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        workspaces.sort_unstable();
+        let idle = editors
+            .iter()
+            .filter(|s| s.last_activity.as_ref().is_some_and(|a| a.is_idle))
+            .count();
+        let active = editors
+            .iter()
+            .filter(|s| s.last_activity.as_ref().is_some_and(|a| !a.is_idle))
+            .count();
+
+        let value = |field: &str| -> Option<String> {
+            let non_empty = |s: &str| (!s.is_empty()).then(|| s.to_string());
+            match field {
+                "editors" => Some(editors.len().to_string()),
+                "projects" => Some(workspaces.len().to_string()),
+                "active" => Some(active.to_string()),
+                "idle" => Some(idle.to_string()),
+                "workspaces" => non_empty(&workspaces.join(", ")),
+                "focused.details" => {
+                    focused.last_activity.as_ref()?.details.clone()
+                }
+                "focused.state" => {
+                    focused.last_activity.as_ref()?.state.clone()
+                }
+                "focused.workspace" => focused
+                    .workspace
+                    .as_deref()
+                    .map(Path::new)
+                    .and_then(|path| path.file_name()?.to_str())
+                    .map(str::to_string),
+                "focused.editor" => focused.client.name.clone(),
+                _ => None,
+            }
+        };
+
+        let details = self.details.render(value);
+        let state = self.state.render(value);
+        activity.details = (!details.is_empty()).then_some(details);
+        activity.state = (!state.is_empty()).then_some(state);
+        activity.details_url = None;
+        activity.state_url = None;
+        activity.is_idle = active == 0;
+
+        Some(activity)
+    }
+}
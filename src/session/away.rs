@@ -0,0 +1,144 @@
+use std::time::{Duration, Instant};
+
+use crate::presence::activity::{Activity, ActivityTimestamps};
+
+/// Replaces the presence once every session has gone without a client event
+/// for a while, as happens when the editors are left open on a machine
+/// nobody uses. Clients that stop sending events cannot report themselves
+/// idle, so this does not rely on `Activity::is_idle`.
+#[derive(Debug, Default)]
+pub struct Away {
+    timeout: Option<Duration>,
+    /// Shown while away, the presence is cleared if there is none.
+    activity: Option<Activity>,
+    /// Unix timestamp, in seconds, at which the sessions went away.
+    since: Option<u64>,
+}
+
+impl Away {
+    /// Goes away after `timeout` without events, or never.
+    pub fn configure(
+        &mut self,
+        timeout: Option<Duration>,
+        activity: Option<Activity>,
+    ) {
+        self.timeout = timeout;
+        self.activity = activity;
+        if self.timeout.is_none() {
+            self.since = None;
+        }
+    }
+
+    pub fn is_away(&self) -> bool {
+        self.since.is_some()
+    }
+
+    /// Whether the sessions whose last events are given should go away:
+    /// there is at least one and none of them had an event within the
+    /// timeout.
+    pub fn is_due(
+        &self,
+        mut last_events: impl Iterator<Item = Instant>,
+    ) -> bool {
+        let Some(timeout) = self.timeout else {
+            return false;
+        };
+        if self.is_away() {
+            return false;
+        }
+
+        let mut any = false;
+        let inactive = last_events.all(|at| {
+            any = true;
+            at.elapsed() >= timeout
+        });
+
+        any && inactive
+    }
+
+    /// Marks the sessions as away from `now`, in Unix seconds.
+    pub fn enter(&mut self, now: u64) {
+        self.since.get_or_insert(now);
+    }
+
+    /// Marks the sessions as back, returning whether they were away.
+    pub fn leave(&mut self) -> bool {
+        self.since.take().is_some()
+    }
+
+    /// The activity to show while away, timed from when the sessions went
+    /// away unless it sets its own timestamps.
+    pub fn activity(&self) -> Option<Activity> {
+        let since = self.since?;
+        let mut activity = self.activity.clone()?;
+        activity.is_idle = true;
+        activity.timestamps.get_or_insert(ActivityTimestamps {
+            start: Some(since),
+            end: None,
+        });
+
+        Some(activity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn away(activity: Option<Activity>) -> Away {
+        let mut away = Away::default();
+        away.configure(Some(Duration::from_secs(60)), activity);
+        away
+    }
+
+    #[test]
+    fn due_once_every_session_is_inactive() {
+        let away = away(None);
+        let now = Instant::now();
+        let old = now - Duration::from_secs(120);
+
+        assert!(!away.is_due([].into_iter()));
+        assert!(!away.is_due([old, now].into_iter()));
+        assert!(away.is_due([old, old].into_iter()));
+        assert!(!Away::default().is_due([old].into_iter()));
+    }
+
+    #[test]
+    fn entering_and_leaving() {
+        let mut away = away(None);
+        let old = Instant::now() - Duration::from_secs(120);
+
+        away.enter(1000);
+        assert!(away.is_away());
+        assert!(!away.is_due([old].into_iter()));
+        assert_eq!(away.activity(), None);
+
+        assert!(away.leave());
+        assert!(!away.leave());
+        assert!(away.is_due([old].into_iter()));
+    }
+
+    #[test]
+    fn away_activity_is_idle_and_timed() {
+        let mut away = away(Some(Activity {
+            details: Some("Away".to_string()),
+            ..Default::default()
+        }));
+        assert_eq!(away.activity(), None);
+
+        away.enter(1000);
+        away.enter(2000);
+        let activity = away.activity().unwrap();
+        assert!(activity.is_idle);
+        assert_eq!(activity.details.as_deref(), Some("Away"));
+        assert_eq!(activity.timestamps.unwrap().start, Some(1000));
+    }
+
+    #[test]
+    fn disabling_comes_back() {
+        let mut away = away(None);
+        away.enter(1000);
+        away.configure(None, None);
+        assert!(!away.is_away());
+    }
+}
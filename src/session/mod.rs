@@ -1,32 +1,89 @@
 #![allow(dead_code)]
 
 use std::collections::HashMap;
-use std::sync::atomic::AtomicU64;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
 use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::{Duration, Instant};
 
 use crate::ipc::pipe::platform::client::PipeClient;
 use crate::presence::activity::Activity;
-use crate::types::config::PluginConfig;
+use crate::presence::privacy::Privacy;
+use crate::types::config::{ClientInfo, PluginConfig};
+use crate::util::{now, process};
 use crate::{debug, trace};
 
+pub mod aggregate;
+pub mod away;
+pub mod overrides;
+pub mod pause;
+pub mod policy;
+pub mod rotation;
+pub mod selection;
+pub mod sharing;
+pub mod state;
+pub mod stats;
+pub mod timestamps;
+pub mod wakatime;
+
+use aggregate::Aggregate;
+use away::Away;
+use overrides::OverrideStack;
+use pause::Pause;
+use policy::SelectionPolicy;
+use rotation::Rotation;
+use sharing::Sharing;
+use state::Restored;
+use stats::{Current, Stats};
+use timestamps::{SharedTimestamps, TimestampScope};
+use wakatime::{Exporter, Heartbeat};
+
 pub struct Session {
     pub workspace: Option<String>,
+    /// Path of the file being edited, if the client reports it.
+    pub file: Option<String>,
+    /// Filetype of the file being edited, if the client reports it.
+    pub filetype: Option<String>,
     pub timestamp: Option<u64>,
     pub last_activity: Option<Activity>,
     pub last_updated: u128,
+    /// When the session took over focus, 0 if it never did.
+    pub last_focused: u128,
+    pub is_focused: bool,
+    /// When the session takes over focus, if it gained focus and has not
+    /// yet stayed focused for the grace period.
+    pub focus_deadline: Option<Instant>,
     pub config: Option<PluginConfig>,
     pub pipe_client: Option<PipeClient>,
+    pub protocol_version: Option<u32>,
+    pub capabilities: Vec<String>,
+    pub client: ClientInfo,
+    /// How long the session may go without a heartbeat, if it sends them.
+    pub heartbeat_timeout: Option<Duration>,
+    pub last_heartbeat: Instant,
+    /// When the client last sent an event other than a heartbeat or query.
+    pub last_event: Instant,
 }
 
 impl Session {
     pub fn new() -> Self {
         Self {
             workspace: None,
+            file: None,
+            filetype: None,
             timestamp: None,
             last_activity: None,
             last_updated: 0,
+            last_focused: 0,
+            is_focused: false,
+            focus_deadline: None,
             config: None,
             pipe_client: None,
+            protocol_version: None,
+            capabilities: Vec::new(),
+            client: ClientInfo::default(),
+            heartbeat_timeout: None,
+            last_heartbeat: Instant::now(),
+            last_event: Instant::now(),
         }
     }
 
@@ -35,6 +92,32 @@ impl Session {
         self.workspace = Some(workspace);
     }
 
+    pub fn set_file(&mut self, file: Option<String>) {
+        trace!("Setting session file: {:?}", file);
+        self.file = file;
+    }
+
+    pub fn set_filetype(&mut self, filetype: Option<String>) {
+        trace!("Setting session filetype: {:?}", filetype);
+        self.filetype = filetype;
+    }
+
+    /// What the session's time is credited to, falling back to the key of
+    /// its large image if the client reports no filetype.
+    pub fn current(&self) -> Current<'_> {
+        Current {
+            workspace: self.workspace.as_deref(),
+            filetype: self.filetype.as_deref().or_else(|| {
+                self.last_activity.as_ref().and_then(stats::asset_key)
+            }),
+        }
+    }
+
+    /// Key of the shared timestamp this session uses under `scope`.
+    pub fn timestamp_key(&self, id: u32, scope: TimestampScope) -> String {
+        scope.key(id, self.workspace.as_deref(), self.file.as_deref())
+    }
+
     pub fn set_timestamp(&mut self, timestamp: u64) {
         trace!("Setting session timestamp: {}", timestamp);
         self.timestamp = Some(timestamp);
@@ -50,6 +133,65 @@ impl Session {
         self.config = Some(config);
     }
 
+    pub fn set_protocol(
+        &mut self,
+        protocol_version: u32,
+        capabilities: Vec<String>,
+    ) {
+        trace!(
+            "Setting session protocol: version={}, capabilities={:?}",
+            protocol_version, capabilities
+        );
+        self.protocol_version = Some(protocol_version);
+        self.capabilities = capabilities;
+    }
+
+    pub fn set_client(&mut self, client: ClientInfo) {
+        trace!("Setting session client: {:?}", client);
+        self.client = client;
+    }
+
+    pub fn set_heartbeat_timeout(&mut self, timeout: Option<Duration>) {
+        trace!("Setting session heartbeat timeout: {:?}", timeout);
+        self.heartbeat_timeout = timeout;
+        self.last_heartbeat = Instant::now();
+    }
+
+    pub fn record_heartbeat(&mut self) {
+        self.last_heartbeat = Instant::now();
+    }
+
+    pub fn record_event(&mut self) {
+        self.last_event = Instant::now();
+    }
+
+    /// Returns why the session is considered dead, if it is. `hostname` is
+    /// the server's own, PIDs reported from other machines are not checked.
+    pub fn liveness_failure(&self, hostname: Option<&str>) -> Option<String> {
+        if let Some(timeout) = self.heartbeat_timeout
+            && self.last_heartbeat.elapsed() > timeout
+        {
+            return Some(format!(
+                "no heartbeat for {}ms",
+                self.last_heartbeat.elapsed().as_millis()
+            ));
+        }
+
+        if let Some(pid) = self.client.pid
+            && (self.client.hostname.is_none()
+                || self.client.hostname.as_deref() == hostname)
+            && process::is_alive(pid, self.client.start_time) == Some(false)
+        {
+            return Some(format!("process {} no longer exists", pid));
+        }
+
+        None
+    }
+
+    pub fn has_capability(&self, capability: &str) -> bool {
+        self.capabilities.iter().any(|c| c == capability)
+    }
+
     pub fn set_pipe_client(&mut self, client: PipeClient) {
         self.pipe_client = Some(client);
     }
@@ -71,6 +213,19 @@ impl Session {
     }
 }
 
+/// A session whose activity is displayed regardless of the selection policy.
+#[derive(Debug, Clone, Copy)]
+pub struct Pin {
+    pub session: u32,
+    pub expires_at: Option<Instant>,
+}
+
+impl Pin {
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.is_some_and(|at| at <= Instant::now())
+    }
+}
+
 pub struct SessionRef<'a> {
     sessions: RwLockReadGuard<'a, HashMap<u32, Session>>,
     id: u32,
@@ -106,8 +261,31 @@ impl std::ops::DerefMut for SessionRefMut<'_> {
 #[derive(Default)]
 pub struct SessionManager {
     pub sessions: RwLock<HashMap<u32, Session>>,
-    pub shared_timestamp: AtomicU64,
+    pub timestamps: RwLock<SharedTimestamps>,
     pub last_activity: RwLock<Option<Activity>>,
+    /// Id of the session whose activity is displayed, or 0 if none is.
+    pub active_session: AtomicU32,
+    pub policy: RwLock<SelectionPolicy>,
+    /// Milliseconds a session must stay focused before it takes over.
+    pub focus_grace: AtomicU64,
+    pub pin: RwLock<Option<Pin>>,
+    pub overrides: RwLock<OverrideStack>,
+    pub rotation: RwLock<Rotation>,
+    /// Set when every session is summarized instead of showing one.
+    pub aggregate: RwLock<Option<Aggregate>>,
+    /// Set while every session has gone without events for too long.
+    pub away: RwLock<Away>,
+    /// Whether the presence is hidden on request or by the schedule.
+    pub pause: RwLock<Pause>,
+    /// Workspaces allowed or denied when sharing must be confirmed.
+    pub sharing: RwLock<Sharing>,
+    /// Active time per day, workspace and filetype.
+    pub stats: RwLock<Stats>,
+    /// Set while WakaTime heartbeats are exported.
+    pub wakatime: RwLock<Option<Exporter>>,
+    /// State restored from a previous server that its sessions have not
+    /// claimed yet.
+    pub restored: RwLock<Restored>,
 }
 
 impl SessionManager {
@@ -119,10 +297,49 @@ impl SessionManager {
         sessions.insert(id, session);
     }
 
-    pub fn remove_session(&self, id: u32) {
-        debug!("Removing session for client {}", id);
-        let mut sessions = self.sessions.write().unwrap();
-        sessions.remove(&id);
+    /// The privacy rules of every session combined, those of the session
+    /// that connected first coming first.
+    pub fn privacy(&self) -> Privacy {
+        let sessions = self.sessions.read().unwrap();
+        let mut ids: Vec<u32> = sessions.keys().copied().collect();
+        ids.sort_unstable();
+
+        let mut privacy = Privacy::default();
+        for config in ids.iter().filter_map(|id| sessions[id].get_config()) {
+            privacy.merge(&config.advanced.server.privacy);
+        }
+        privacy
+    }
+
+    /// Whether sharing must be confirmed, which is the case as soon as one
+    /// session asks for it.
+    pub fn confirm_share(&self) -> bool {
+        self.sessions.read().unwrap().values().any(|session| {
+            session.get_config().is_some_and(|config| {
+                config.advanced.server.confirm_share.enabled
+            })
+        })
+    }
+
+    pub fn active_session(&self) -> Option<u32> {
+        match self.active_session.load(Ordering::SeqCst) {
+            0 => None,
+            id => Some(id),
+        }
+    }
+
+    pub fn set_active_session(&self, id: Option<u32>) {
+        trace!("Setting active session: {:?}", id);
+        self.active_session.store(id.unwrap_or(0), Ordering::SeqCst);
+    }
+
+    pub fn pinned_session(&self) -> Option<u32> {
+        self.pin.read().unwrap().map(|pin| pin.session)
+    }
+
+    pub fn set_pin(&self, pin: Option<Pin>) {
+        trace!("Setting pin: {:?}", pin);
+        *self.pin.write().unwrap() = pin;
     }
 
     pub fn get_session(&self, id: u32) -> Option<SessionRef<'_>> {
@@ -135,6 +352,43 @@ impl SessionManager {
         }
     }
 
+    /// Queues a WakaTime heartbeat for `file`, which session `id` reports
+    /// editing, if heartbeats are exported and one is due. The workspace and
+    /// filetype default to those the session reported last.
+    pub fn export_heartbeat(
+        &self,
+        id: u32,
+        file: &str,
+        workspace: Option<&str>,
+        filetype: Option<&str>,
+    ) {
+        let exporter = self.wakatime.read().unwrap();
+        let Some(exporter) = exporter.as_ref() else {
+            return;
+        };
+        let Some(session) = self.get_session(id) else {
+            return;
+        };
+
+        trace!(id, "Exporting heartbeat for {}", file);
+        let current = session.current();
+        exporter.send(
+            id,
+            Heartbeat {
+                entity: file.to_string(),
+                project: workspace
+                    .or(current.workspace)
+                    .and_then(Heartbeat::project),
+                language: filetype.or(current.filetype).map(str::to_string),
+                time: now().as_secs_f64(),
+                is_write: false,
+                user_agent: Heartbeat::user_agent(
+                    session.client.name.as_deref(),
+                ),
+            },
+        );
+    }
+
     pub fn get_session_mut(&self, id: u32) -> Option<SessionRefMut<'_>> {
         let sessions = self.sessions.write().unwrap();
         if sessions.contains_key(&id) {
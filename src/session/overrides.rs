@@ -0,0 +1,286 @@
+use std::time::Instant;
+
+use crate::presence::activity::{Activity, ActivityType, StatusDisplayType};
+
+/// An activity pushed on top of the displayed session's activity. Fields it
+/// sets replace those of the layers below it, the others show through.
+#[derive(Debug, Clone)]
+pub struct Override {
+    pub id: String,
+    /// The session that pushed the layer; it is removed when it disconnects.
+    pub session: u32,
+    pub priority: i64,
+    pub activity: Activity,
+    /// Set when the layer specifies its own activity type.
+    pub ty: Option<ActivityType>,
+    /// Set when the layer specifies its own status display type.
+    pub status_display_type: Option<StatusDisplayType>,
+    pub expires_at: Option<Instant>,
+}
+
+impl Override {
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.is_some_and(|at| at <= Instant::now())
+    }
+
+    fn apply(&self, activity: &mut Activity) {
+        let layer = &self.activity;
+
+        if let Some(ty) = self.ty {
+            activity.ty = ty;
+        }
+        if let Some(status_display_type) = self.status_display_type {
+            activity.status_display_type = status_display_type;
+        }
+        if layer.details.is_some() {
+            activity.details.clone_from(&layer.details);
+        }
+        if layer.details_url.is_some() {
+            activity.details_url.clone_from(&layer.details_url);
+        }
+        if layer.state.is_some() {
+            activity.state.clone_from(&layer.state);
+        }
+        if layer.state_url.is_some() {
+            activity.state_url.clone_from(&layer.state_url);
+        }
+        if let Some(assets) = &layer.assets {
+            match &mut activity.assets {
+                Some(base) => {
+                    macro_rules! overlay {
+                        ($($field:ident),*) => {
+                            $(if assets.$field.is_some() {
+                                base.$field.clone_from(&assets.$field);
+                            })*
+                        };
+                    }
+                    overlay!(
+                        large_image,
+                        large_text,
+                        large_url,
+                        small_image,
+                        small_text,
+                        small_url
+                    );
+                }
+                None => activity.assets = Some(assets.clone()),
+            }
+        }
+        if layer.timestamps.is_some() {
+            activity.timestamps.clone_from(&layer.timestamps);
+        }
+        if !layer.buttons.is_empty() {
+            activity.buttons.clone_from(&layer.buttons);
+        }
+    }
+}
+
+/// Override layers ordered from the bottom of the stack to its top: by
+/// priority, then by the order they were pushed in.
+#[derive(Debug, Default)]
+pub struct OverrideStack {
+    layers: Vec<Override>,
+}
+
+impl OverrideStack {
+    /// Pushes `layer` on the stack, replacing any layer with the same id.
+    pub fn push(&mut self, layer: Override) {
+        self.remove(&layer.id);
+        let index = self
+            .layers
+            .iter()
+            .position(|l| l.priority > layer.priority)
+            .unwrap_or(self.layers.len());
+        self.layers.insert(index, layer);
+    }
+
+    /// Removes the layer with the given id, returning whether it existed.
+    pub fn remove(&mut self, id: &str) -> bool {
+        let len = self.layers.len();
+        self.layers.retain(|l| l.id != id);
+        self.layers.len() != len
+    }
+
+    /// Removes the layers pushed by `session`, returning how many there were.
+    pub fn remove_session(&mut self, session: u32) -> usize {
+        let len = self.layers.len();
+        self.layers.retain(|l| l.session != session);
+        len - self.layers.len()
+    }
+
+    /// Hands the layer with the given id over to `session` if no session
+    /// owns it, as is the case for layers restored from a previous server.
+    pub fn adopt(&mut self, id: &str, session: u32) -> bool {
+        match self
+            .layers
+            .iter_mut()
+            .find(|l| l.id == id && l.session == 0)
+        {
+            Some(layer) => {
+                layer.session = session;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes expired layers, returning their ids.
+    pub fn remove_expired(&mut self) -> Vec<String> {
+        let (expired, layers) =
+            self.layers.drain(..).partition(|l| l.is_expired());
+        self.layers = layers;
+        expired.into_iter().map(|l: Override| l.id).collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+
+    pub fn layers(&self) -> &[Override] {
+        &self.layers
+    }
+
+    /// Applies the layers that have not expired on top of `base`. Whether the
+    /// activity is idle is always taken from `base`.
+    pub fn compose(&self, mut base: Activity) -> Activity {
+        for layer in self.layers.iter().filter(|l| !l.is_expired()) {
+            layer.apply(&mut base);
+        }
+
+        base
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::presence::activity::ActivityAssets;
+
+    fn layer(id: &str, priority: i64, details: &str) -> Override {
+        Override {
+            id: id.to_string(),
+            session: 1,
+            priority,
+            activity: Activity {
+                details: Some(details.to_string()),
+                ..Default::default()
+            },
+            ty: None,
+            status_display_type: None,
+            expires_at: None,
+        }
+    }
+
+    fn base() -> Activity {
+        Activity {
+            ty: ActivityType::Watching,
+            details: Some("Editing main.rs".to_string()),
+            state: Some("In cord".to_string()),
+            assets: Some(ActivityAssets {
+                large_image: Some("rust".to_string()),
+                large_text: Some("Rust".to_string()),
+                large_url: None,
+                small_image: Some("neovim".to_string()),
+                small_text: None,
+                small_url: None,
+            }),
+            is_idle: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn empty_stack_keeps_base() {
+        assert_eq!(OverrideStack::default().compose(base()), base());
+    }
+
+    #[test]
+    fn layer_fields_replace_base_fields() {
+        let mut stack = OverrideStack::default();
+        let mut top = layer("build", 0, "Building");
+        top.activity.assets = Some(ActivityAssets {
+            large_image: Some("hammer".to_string()),
+            large_text: None,
+            large_url: None,
+            small_image: None,
+            small_text: None,
+            small_url: None,
+        });
+        stack.push(top);
+
+        let activity = stack.compose(base());
+        assert_eq!(activity.details.as_deref(), Some("Building"));
+        assert_eq!(activity.state.as_deref(), Some("In cord"));
+        assert_eq!(activity.ty, ActivityType::Watching);
+        assert!(activity.is_idle);
+
+        let assets = activity.assets.unwrap();
+        assert_eq!(assets.large_image.as_deref(), Some("hammer"));
+        assert_eq!(assets.large_text.as_deref(), Some("Rust"));
+        assert_eq!(assets.small_image.as_deref(), Some("neovim"));
+    }
+
+    #[test]
+    fn highest_priority_layer_wins() {
+        let mut stack = OverrideStack::default();
+        stack.push(layer("meeting", 10, "In a meeting"));
+        stack.push(layer("build", 0, "Building"));
+        assert_eq!(
+            stack.compose(base()).details.as_deref(),
+            Some("In a meeting")
+        );
+
+        stack.push(layer("deploy", 10, "Deploying"));
+        assert_eq!(stack.compose(base()).details.as_deref(), Some("Deploying"));
+    }
+
+    #[test]
+    fn pushing_same_id_replaces_layer() {
+        let mut stack = OverrideStack::default();
+        stack.push(layer("build", 0, "Building"));
+        stack.push(layer("build", 0, "Build failed"));
+        assert_eq!(stack.layers().len(), 1);
+        assert_eq!(
+            stack.compose(base()).details.as_deref(),
+            Some("Build failed")
+        );
+    }
+
+    #[test]
+    fn removing_layers() {
+        let mut stack = OverrideStack::default();
+        stack.push(layer("build", 0, "Building"));
+        stack.push(Override {
+            session: 2,
+            ..layer("meeting", 5, "In a meeting")
+        });
+
+        assert!(!stack.remove("missing"));
+        assert_eq!(stack.remove_session(2), 1);
+        assert!(stack.remove("build"));
+        assert!(stack.is_empty());
+        assert_eq!(stack.compose(base()), base());
+    }
+
+    #[test]
+    fn expired_layers_are_ignored_and_removed() {
+        let mut stack = OverrideStack::default();
+        stack.push(Override {
+            expires_at: Some(Instant::now() - Duration::from_secs(1)),
+            ..layer("build", 10, "Building")
+        });
+        stack.push(Override {
+            expires_at: Some(Instant::now() + Duration::from_secs(60)),
+            ..layer("meeting", 0, "In a meeting")
+        });
+
+        assert_eq!(
+            stack.compose(base()).details.as_deref(),
+            Some("In a meeting")
+        );
+        assert_eq!(stack.remove_expired(), vec!["build".to_string()]);
+        assert_eq!(stack.layers().len(), 1);
+    }
+}
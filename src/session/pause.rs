@@ -0,0 +1,237 @@
+use crate::protocol::msgpack::{Deserialize, Value};
+
+const DAYS: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+
+/// Whether the presence is hidden, either on request or because a
+/// do-not-disturb window is open.
+#[derive(Debug, Default)]
+pub struct Pause {
+    /// Paused through a `pause` event until a `resume` event.
+    manual: bool,
+    /// A window of the schedule is open.
+    scheduled: bool,
+    /// Resumed while a window is open, which lasts until the window closes.
+    overridden: bool,
+    schedule: Schedule,
+}
+
+impl Pause {
+    pub fn configure(&mut self, schedule: Schedule) {
+        self.schedule = schedule;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.manual || (self.scheduled && !self.overridden)
+    }
+
+    pub fn pause(&mut self) {
+        self.manual = true;
+    }
+
+    /// Resumes, including while a window of the schedule is open.
+    pub fn resume(&mut self) {
+        self.manual = false;
+        self.overridden = self.scheduled;
+    }
+
+    /// Opens or closes the windows of the schedule at `now`, in Unix seconds.
+    pub fn tick(&mut self, now: u64) {
+        self.scheduled = self.schedule.is_active(now);
+        if !self.scheduled {
+            self.overridden = false;
+        }
+    }
+}
+
+/// Weekly do-not-disturb windows, in local time.
+#[derive(Debug, Clone, Default)]
+pub struct Schedule {
+    /// Minutes to add to UTC to get local time.
+    pub utc_offset: i64,
+    pub windows: Vec<Window>,
+}
+
+impl Schedule {
+    /// Whether any window is open at `now`, in Unix seconds.
+    pub fn is_active(&self, now: u64) -> bool {
+        let local = now as i64 + self.utc_offset * 60;
+        let days = local.div_euclid(86400);
+        // 1970-01-01 was a Thursday
+        let day = (days + 3).rem_euclid(7) as usize;
+        let minute = (local.rem_euclid(86400) / 60) as u32;
+
+        self.windows.iter().any(|w| w.contains(day, minute))
+    }
+}
+
+/// A window opening on some days of the week. It closes the next day if it
+/// closes before it opens, and lasts the whole day if both times are equal.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Window {
+    /// Bit `n` is set if the window opens on day `n`, Monday being 0.
+    days: u8,
+    /// Minutes since midnight.
+    from: u32,
+    to: u32,
+}
+
+impl Window {
+    fn opens_on(&self, day: usize) -> bool {
+        self.days & (1 << day) != 0
+    }
+
+    fn contains(&self, day: usize, minute: u32) -> bool {
+        let previous = (day + 6) % 7;
+        if self.from < self.to {
+            self.opens_on(day) && (self.from..self.to).contains(&minute)
+        } else if self.from > self.to {
+            (self.opens_on(day) && minute >= self.from)
+                || (self.opens_on(previous) && minute < self.to)
+        } else {
+            self.opens_on(day)
+        }
+    }
+}
+
+/// Parses a `HH:MM` time into minutes since midnight.
+fn parse_time(time: &str) -> Option<u32> {
+    let (hours, minutes) = time.split_once(':')?;
+    let (hours, minutes): (u32, u32) =
+        (hours.parse().ok()?, minutes.parse().ok()?);
+    (hours < 24 && minutes < 60).then_some(hours * 60 + minutes)
+}
+
+impl Deserialize for Window {
+    fn deserialize<'a>(input: Value) -> crate::Result<Self> {
+        let mut input = input.take_map().ok_or("Invalid schedule rule")?;
+
+        let days = match input.remove("days").and_then(|v| v.take_array()) {
+            Some(days) => days.iter().try_fold(0, |mask, day| {
+                let day = day.as_str().unwrap_or_default();
+                DAYS.iter()
+                    .position(|d| *d == day)
+                    .map(|n| mask | (1 << n))
+                    .ok_or_else(|| format!("Unknown day: {}", day))
+            })?,
+            None => 0x7f,
+        };
+        let mut time = |field: &str| match input.remove(field) {
+            Some(v) => v
+                .as_str()
+                .and_then(parse_time)
+                .ok_or_else(|| format!("Invalid '{}' time", field)),
+            None => Ok(0),
+        };
+        let from = time("from")?;
+        let to = time("to")?;
+
+        Ok(Window { days, from, to })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Monday 2024-01-01 00:00 UTC.
+    const MONDAY: u64 = 1704067200;
+
+    fn at(day: u64, hours: u64, minutes: u64) -> u64 {
+        MONDAY + day * 86400 + hours * 3600 + minutes * 60
+    }
+
+    fn window(days: &[usize], from: &str, to: &str) -> Window {
+        Window {
+            days: days.iter().fold(0, |mask, d| mask | (1 << d)),
+            from: parse_time(from).unwrap(),
+            to: parse_time(to).unwrap(),
+        }
+    }
+
+    fn schedule(windows: Vec<Window>) -> Schedule {
+        Schedule {
+            utc_offset: 0,
+            windows,
+        }
+    }
+
+    #[test]
+    fn parses_times() {
+        assert_eq!(parse_time("09:30"), Some(570));
+        assert_eq!(parse_time("23:59"), Some(1439));
+        assert_eq!(parse_time("24:00"), None);
+        assert_eq!(parse_time("9"), None);
+    }
+
+    #[test]
+    fn windows_open_on_their_days() {
+        let schedule =
+            schedule(vec![window(&[0, 1, 2, 3, 4], "09:00", "17:00")]);
+
+        assert!(schedule.is_active(at(0, 9, 0)));
+        assert!(schedule.is_active(at(4, 16, 59)));
+        assert!(!schedule.is_active(at(0, 17, 0)));
+        assert!(!schedule.is_active(at(0, 8, 59)));
+        assert!(!schedule.is_active(at(5, 12, 0)));
+    }
+
+    #[test]
+    fn windows_close_the_next_day() {
+        let schedule = schedule(vec![window(&[4], "22:00", "07:00")]);
+
+        assert!(schedule.is_active(at(4, 23, 0)));
+        assert!(schedule.is_active(at(5, 6, 59)));
+        assert!(!schedule.is_active(at(5, 7, 0)));
+        assert!(!schedule.is_active(at(4, 6, 0)));
+    }
+
+    #[test]
+    fn equal_times_cover_the_day() {
+        let schedule = schedule(vec![window(&[6], "00:00", "00:00")]);
+
+        assert!(schedule.is_active(at(6, 0, 0)));
+        assert!(schedule.is_active(at(6, 23, 59)));
+        assert!(!schedule.is_active(at(0, 0, 0)));
+    }
+
+    #[test]
+    fn offset_moves_to_local_time() {
+        let mut schedule = schedule(vec![window(&[0], "09:00", "10:00")]);
+        schedule.utc_offset = 120;
+        assert!(schedule.is_active(at(0, 7, 30)));
+        assert!(!schedule.is_active(at(0, 9, 30)));
+
+        schedule.utc_offset = -60;
+        assert!(schedule.is_active(at(0, 10, 0)));
+    }
+
+    #[test]
+    fn resuming_overrides_the_open_window() {
+        let mut pause = Pause::default();
+        pause.configure(schedule(vec![window(&[0], "09:00", "10:00")]));
+
+        pause.tick(at(0, 9, 0));
+        assert!(pause.is_paused());
+        pause.resume();
+        assert!(!pause.is_paused());
+        pause.tick(at(0, 9, 30));
+        assert!(!pause.is_paused());
+
+        pause.tick(at(0, 10, 0));
+        pause.tick(at(7, 9, 0));
+        assert!(pause.is_paused());
+    }
+
+    #[test]
+    fn manual_pause_outlasts_windows() {
+        let mut pause = Pause::default();
+        pause.configure(schedule(vec![window(&[0], "09:00", "10:00")]));
+
+        pause.pause();
+        pause.tick(at(0, 9, 0));
+        pause.tick(at(0, 10, 0));
+        assert!(pause.is_paused());
+        pause.resume();
+        assert!(!pause.is_paused());
+    }
+}
@@ -0,0 +1,342 @@
+use std::fmt::Display;
+
+use crate::protocol::msgpack::{Deserialize, Value};
+use crate::util::glob;
+
+/// Decides which session's activity is displayed when several sessions have
+/// one. Sessions that are not idle always win over idle ones.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum SelectionPolicy {
+    /// The session that updated its activity most recently.
+    #[default]
+    MostRecent,
+    /// The session that gained focus most recently, or the most recent one
+    /// if no session reported focus.
+    Focused,
+    /// The session whose workspace matches the highest priority glob.
+    Priority(Vec<(String, i64)>),
+    /// The session being displayed keeps the presence until it goes idle,
+    /// clears its activity or disconnects.
+    Pinned,
+}
+
+/// What the policy knows about a session that has an activity.
+#[derive(Debug, Clone, Default)]
+pub struct Candidate<'a> {
+    pub id: u32,
+    pub is_idle: bool,
+    pub last_updated: u128,
+    /// When the session last gained focus, 0 if it never reported focus.
+    pub last_focused: u128,
+    pub workspace: Option<&'a str>,
+}
+
+impl SelectionPolicy {
+    /// Picks the session to display among `candidates`, `current` being the
+    /// one displayed so far. A `pinned` session with an activity always wins,
+    /// whatever the policy.
+    pub fn select(
+        &self,
+        candidates: &[Candidate],
+        current: Option<u32>,
+        pinned: Option<u32>,
+    ) -> Option<u32> {
+        if let Some(pinned) = pinned
+            && candidates.iter().any(|c| c.id == pinned)
+        {
+            return Some(pinned);
+        }
+
+        if *self == SelectionPolicy::Pinned
+            && let Some(current) = current
+            && candidates.iter().any(|c| c.id == current && !c.is_idle)
+        {
+            return Some(current);
+        }
+
+        candidates
+            .iter()
+            .max_by_key(|c| {
+                let rank = match self {
+                    SelectionPolicy::Focused => c.last_focused,
+                    SelectionPolicy::Priority(rules) => {
+                        Self::priority(rules, c.workspace) as u128
+                    }
+                    _ => 0,
+                };
+
+                (!c.is_idle, rank, c.last_updated, c.id)
+            })
+            .map(|c| c.id)
+    }
+
+    /// Highest priority among the rules matching `workspace`, offset so that
+    /// it orders correctly as an unsigned value.
+    fn priority(rules: &[(String, i64)], workspace: Option<&str>) -> u64 {
+        let priority = workspace
+            .and_then(|workspace| {
+                rules
+                    .iter()
+                    .filter(|(pattern, _)| glob::matches(pattern, workspace))
+                    .map(|(_, priority)| *priority)
+                    .max()
+            })
+            .unwrap_or(0);
+
+        (priority as u64) ^ (1 << 63)
+    }
+}
+
+impl Display for SelectionPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MostRecent => write!(f, "most_recent"),
+            Self::Focused => write!(f, "focused"),
+            Self::Priority(_) => write!(f, "priority"),
+            Self::Pinned => write!(f, "pinned"),
+        }
+    }
+}
+
+impl Deserialize for SelectionPolicy {
+    fn deserialize<'a>(input: Value) -> crate::Result<Self> {
+        let mut input = input.take_map().ok_or("Invalid selection config")?;
+
+        let policy = input
+            .remove("policy")
+            .and_then(|v| v.take_string())
+            .unwrap_or_else(|| "most_recent".to_string());
+
+        Ok(match policy.as_str() {
+            "most_recent" => SelectionPolicy::MostRecent,
+            "focused" => SelectionPolicy::Focused,
+            "pinned" => SelectionPolicy::Pinned,
+            "priority" => {
+                let rules = input
+                    .remove("priorities")
+                    .and_then(|v| v.take_map())
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter_map(|(pattern, priority)| {
+                        let priority = priority.as_integer()?;
+                        Some((glob::expand_home(pattern), priority))
+                    })
+                    .collect();
+
+                SelectionPolicy::Priority(rules)
+            }
+            policy => {
+                return Err(
+                    format!("Unknown selection policy: {}", policy).into()
+                );
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(
+        id: u32,
+        is_idle: bool,
+        last_updated: u128,
+    ) -> Candidate<'static> {
+        Candidate {
+            id,
+            is_idle,
+            last_updated,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn nothing_to_select() {
+        assert_eq!(
+            SelectionPolicy::MostRecent.select(&[], Some(1), None),
+            None
+        );
+        assert_eq!(SelectionPolicy::Pinned.select(&[], Some(1), None), None);
+    }
+
+    #[test]
+    fn most_recent_prefers_latest_update() {
+        let candidates = [candidate(1, false, 10), candidate(2, false, 20)];
+        assert_eq!(
+            SelectionPolicy::MostRecent.select(&candidates, Some(1), None),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn active_sessions_beat_idle_ones() {
+        let candidates = [candidate(1, false, 10), candidate(2, true, 20)];
+        assert_eq!(
+            SelectionPolicy::MostRecent.select(&candidates, None, None),
+            Some(1)
+        );
+
+        let candidates = [candidate(1, true, 10), candidate(2, true, 20)];
+        assert_eq!(
+            SelectionPolicy::MostRecent.select(&candidates, None, None),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn focused_prefers_latest_focus() {
+        let candidates = [
+            Candidate {
+                last_focused: 30,
+                ..candidate(1, false, 10)
+            },
+            Candidate {
+                last_focused: 5,
+                ..candidate(2, false, 20)
+            },
+        ];
+        assert_eq!(
+            SelectionPolicy::Focused.select(&candidates, None, None),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn focused_falls_back_to_most_recent() {
+        let candidates = [candidate(1, false, 10), candidate(2, false, 20)];
+        assert_eq!(
+            SelectionPolicy::Focused.select(&candidates, None, None),
+            Some(2)
+        );
+
+        let candidates = [
+            Candidate {
+                last_focused: 30,
+                ..candidate(1, true, 10)
+            },
+            candidate(2, false, 20),
+        ];
+        assert_eq!(
+            SelectionPolicy::Focused.select(&candidates, None, None),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn priority_prefers_highest_matching_glob() {
+        let policy = SelectionPolicy::Priority(vec![
+            ("/home/me/work/**".to_string(), 10),
+            ("/home/me/work/scratch".to_string(), -5),
+            ("/home/me/*".to_string(), 1),
+        ]);
+        let candidates = [
+            Candidate {
+                workspace: Some("/home/me/work/api/src"),
+                ..candidate(1, false, 10)
+            },
+            Candidate {
+                workspace: Some("/home/me/dotfiles"),
+                ..candidate(2, false, 20)
+            },
+            Candidate {
+                workspace: None,
+                ..candidate(3, false, 30)
+            },
+        ];
+        assert_eq!(policy.select(&candidates, None, None), Some(1));
+        assert_eq!(policy.select(&candidates[1..], None, None), Some(2));
+    }
+
+    #[test]
+    fn priority_handles_negative_priorities() {
+        let policy = SelectionPolicy::Priority(vec![(
+            "/home/me/work/scratch".to_string(),
+            -5,
+        )]);
+        let candidates = [
+            Candidate {
+                workspace: Some("/home/me/work/scratch"),
+                ..candidate(1, false, 20)
+            },
+            Candidate {
+                workspace: Some("/home/me/notes"),
+                ..candidate(2, false, 10)
+            },
+        ];
+        assert_eq!(policy.select(&candidates, None, None), Some(2));
+    }
+
+    #[test]
+    fn priority_ties_fall_back_to_most_recent() {
+        let policy =
+            SelectionPolicy::Priority(vec![("/home/me/*".to_string(), 1)]);
+        let candidates = [
+            Candidate {
+                workspace: Some("/home/me/a"),
+                ..candidate(1, false, 10)
+            },
+            Candidate {
+                workspace: Some("/home/me/b"),
+                ..candidate(2, false, 20)
+            },
+        ];
+        assert_eq!(policy.select(&candidates, None, None), Some(2));
+    }
+
+    #[test]
+    fn pinned_keeps_current_session() {
+        let candidates = [candidate(1, false, 10), candidate(2, false, 20)];
+        assert_eq!(
+            SelectionPolicy::Pinned.select(&candidates, Some(1), None),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn pinned_releases_idle_or_missing_session() {
+        let candidates = [candidate(1, true, 10), candidate(2, false, 20)];
+        assert_eq!(
+            SelectionPolicy::Pinned.select(&candidates, Some(1), None),
+            Some(2)
+        );
+        assert_eq!(
+            SelectionPolicy::Pinned.select(&candidates, Some(3), None),
+            Some(2)
+        );
+        assert_eq!(
+            SelectionPolicy::Pinned.select(&candidates, None, None),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn pin_overrides_every_policy() {
+        let candidates = [
+            Candidate {
+                last_focused: 30,
+                workspace: Some("/home/me/work"),
+                ..candidate(1, false, 20)
+            },
+            candidate(2, true, 10),
+        ];
+        let policies = [
+            SelectionPolicy::MostRecent,
+            SelectionPolicy::Focused,
+            SelectionPolicy::Priority(vec![("/home/me/*".to_string(), 10)]),
+            SelectionPolicy::Pinned,
+        ];
+        for policy in policies {
+            assert_eq!(policy.select(&candidates, Some(1), Some(2)), Some(2));
+        }
+    }
+
+    #[test]
+    fn pin_without_activity_is_ignored() {
+        let candidates = [candidate(1, false, 10), candidate(2, false, 20)];
+        assert_eq!(
+            SelectionPolicy::MostRecent.select(&candidates, None, Some(3)),
+            Some(2)
+        );
+    }
+}
@@ -0,0 +1,195 @@
+use std::time::{Duration, Instant};
+
+use crate::session::policy::Candidate;
+
+/// Rotation never switches sessions more often than this, whatever the
+/// configured interval.
+pub const MIN_ROTATION_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Cycles the displayed activity across non-idle sessions. Rotation only
+/// runs while at least two sessions are active and no session is pinned.
+#[derive(Debug, Default)]
+pub struct Rotation {
+    interval: Option<Duration>,
+    /// The session rotation currently displays.
+    current: Option<u32>,
+    last_rotated: Option<Instant>,
+}
+
+impl Rotation {
+    /// Enables rotation every `interval`, or disables it.
+    pub fn set_interval(&mut self, interval: Option<Duration>) {
+        self.interval = interval.map(|i| i.max(MIN_ROTATION_INTERVAL));
+        if self.interval.is_none() {
+            self.stop();
+        }
+    }
+
+    pub fn interval(&self) -> Option<Duration> {
+        self.interval
+    }
+
+    pub fn is_rotating(&self) -> bool {
+        self.current.is_some()
+    }
+
+    /// Whether the displayed session has been shown for a whole interval.
+    pub fn is_due(&self) -> bool {
+        match (self.interval, self.last_rotated) {
+            (Some(interval), Some(at)) => {
+                self.current.is_some() && at.elapsed() >= interval
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns the session to display instead of `selected`, the one chosen
+    /// by the selection policy. Starts rotating from `selected` when a
+    /// second session becomes active and stops when only one remains.
+    pub fn resolve(
+        &mut self,
+        candidates: &[Candidate],
+        selected: Option<u32>,
+        pinned: bool,
+    ) -> Option<u32> {
+        let active = Self::active(candidates);
+        if self.interval.is_none() || pinned || active.len() < 2 {
+            self.stop();
+            return selected;
+        }
+
+        if let Some(current) = self.current
+            && active.contains(&current)
+        {
+            return Some(current);
+        }
+
+        let start = selected
+            .filter(|id| active.contains(id))
+            .unwrap_or(active[0]);
+        self.current = Some(start);
+        self.last_rotated = Some(Instant::now());
+
+        Some(start)
+    }
+
+    /// Moves on to the next active session, returning whether it did.
+    pub fn advance(&mut self, candidates: &[Candidate]) -> bool {
+        let active = Self::active(candidates);
+        if self.interval.is_none() || active.len() < 2 {
+            self.stop();
+            return false;
+        }
+
+        let next = self
+            .current
+            .and_then(|current| active.iter().find(|id| **id > current))
+            .unwrap_or(&active[0]);
+        self.current = Some(*next);
+        self.last_rotated = Some(Instant::now());
+
+        true
+    }
+
+    pub fn stop(&mut self) {
+        self.current = None;
+        self.last_rotated = None;
+    }
+
+    fn active(candidates: &[Candidate]) -> Vec<u32> {
+        let mut active: Vec<u32> = candidates
+            .iter()
+            .filter(|c| !c.is_idle)
+            .map(|c| c.id)
+            .collect();
+        active.sort_unstable();
+
+        active
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidates(sessions: &[(u32, bool)]) -> Vec<Candidate<'static>> {
+        sessions
+            .iter()
+            .map(|(id, is_idle)| Candidate {
+                id: *id,
+                is_idle: *is_idle,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    fn rotation() -> Rotation {
+        let mut rotation = Rotation::default();
+        rotation.set_interval(Some(Duration::from_secs(30)));
+        rotation
+    }
+
+    #[test]
+    fn disabled_rotation_keeps_selection() {
+        let mut rotation = Rotation::default();
+        let candidates = candidates(&[(1, false), (2, false)]);
+        assert_eq!(rotation.resolve(&candidates, Some(2), false), Some(2));
+        assert!(!rotation.advance(&candidates));
+        assert!(!rotation.is_rotating());
+    }
+
+    #[test]
+    fn starts_from_selected_session() {
+        let mut rotation = rotation();
+        let candidates = candidates(&[(1, false), (2, false)]);
+        assert_eq!(rotation.resolve(&candidates, Some(2), false), Some(2));
+        assert!(rotation.is_rotating());
+        assert!(!rotation.is_due());
+
+        assert_eq!(rotation.resolve(&candidates, Some(1), false), Some(2));
+    }
+
+    #[test]
+    fn cycles_through_active_sessions() {
+        let mut rotation = rotation();
+        let candidates =
+            candidates(&[(3, false), (1, false), (2, true), (5, false)]);
+        rotation.resolve(&candidates, Some(1), false);
+
+        let mut shown = Vec::new();
+        for _ in 0..4 {
+            assert!(rotation.advance(&candidates));
+            shown.push(rotation.resolve(&candidates, Some(1), false).unwrap());
+        }
+        assert_eq!(shown, vec![3, 5, 1, 3]);
+    }
+
+    #[test]
+    fn stops_with_a_single_active_session() {
+        let mut rotation = rotation();
+        let both = candidates(&[(1, false), (2, false)]);
+        rotation.resolve(&both, Some(1), false);
+        assert!(rotation.is_rotating());
+
+        let one = candidates(&[(1, true), (2, false)]);
+        assert_eq!(rotation.resolve(&one, Some(2), false), Some(2));
+        assert!(!rotation.is_rotating());
+        assert!(!rotation.advance(&one));
+    }
+
+    #[test]
+    fn pin_stops_rotation() {
+        let mut rotation = rotation();
+        let candidates = candidates(&[(1, false), (2, false)]);
+        rotation.resolve(&candidates, Some(1), false);
+        assert_eq!(rotation.resolve(&candidates, Some(2), true), Some(2));
+        assert!(!rotation.is_rotating());
+    }
+
+    #[test]
+    fn interval_is_clamped() {
+        let mut rotation = Rotation::default();
+        rotation.set_interval(Some(Duration::from_millis(100)));
+        assert_eq!(rotation.interval(), Some(MIN_ROTATION_INTERVAL));
+    }
+}
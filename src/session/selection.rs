@@ -0,0 +1,515 @@
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+use std::time::Instant;
+
+use crate::ipc::pipe::PipeServerImpl;
+use crate::messages::events::event::{EventContext, OnEvent};
+use crate::messages::events::server::{
+    ActivityOutcome, ConfirmShareEvent, ErrorEvent, PresenceChangedEvent,
+    PresenceReason,
+};
+use crate::presence::activity::ActivityTimestamps;
+use crate::protocol::msgpack::MsgPack;
+use crate::session::Session;
+use crate::session::policy::Candidate;
+use crate::session::sharing::Sharing;
+use crate::session::stats;
+use crate::util::now;
+use crate::{debug, trace, warn};
+
+/// Presence updates rotation leaves to the sessions themselves within
+/// Discord's rate limit.
+const ROTATION_RESERVE: usize = 2;
+
+/// Selects the session to display according to the configured policy, or
+/// the rotation when it is enabled, and sends its activity to Discord, or
+/// clears the presence if no session has one. In aggregate mode, a summary
+/// of every session is sent instead. Override layers are composed
+/// on top of the selected activity, `{stats.*}` placeholders are replaced
+/// by the time tracked so far, and privacy rules are matched against
+/// the selected session's workspace. Discord is only updated when the
+/// displayed activity changes, unless `force` is set.
+pub fn refresh_presence(
+    ctx: &mut EventContext,
+    reason: PresenceReason,
+    force: bool,
+) -> crate::Result<ActivityOutcome> {
+    let manager = &ctx.cord.session_manager;
+    if manager.away.read().unwrap().is_away() {
+        return show_away(ctx, reason, force);
+    }
+    let policy = manager.policy.read().unwrap().clone();
+    confirm_shares(ctx);
+
+    let manager = &ctx.cord.session_manager;
+    let selected = {
+        let sessions = manager.sessions.read().unwrap();
+        let sharing = manager.sharing.read().unwrap();
+        let candidates = candidates(&sessions, &sharing);
+        let pinned = manager
+            .pinned_session()
+            .filter(|id| candidates.iter().any(|c| c.id == *id));
+
+        let selected =
+            policy.select(&candidates, manager.active_session(), pinned);
+        let selected = manager.rotation.write().unwrap().resolve(
+            &candidates,
+            selected,
+            pinned.is_some(),
+        );
+
+        selected.and_then(|id| {
+            let session = sessions.get(&id)?;
+            let activity = match manager.aggregate.read().unwrap().as_ref() {
+                Some(aggregate) => aggregate.build(&sessions, id, &sharing)?,
+                None => session.last_activity.clone()?,
+            };
+            Some((id, activity, session.workspace.clone()))
+        })
+    };
+
+    let Some((id, mut activity, workspace)) = selected else {
+        manager.set_active_session(None);
+        let cleared = manager.last_activity.write().unwrap().take().is_some();
+        let mut sent = false;
+        if cleared {
+            debug!(ctx.client_id, "No session has an activity, clearing");
+            sent = ctx.cord.activity_manager.clear()?;
+            PresenceChangedEvent::new(None, None, reason).on_event(ctx)?;
+        }
+
+        return Ok(ActivityOutcome::new(sent, None));
+    };
+
+    trace!(
+        ctx.client_id,
+        "Selected session {} using the {} policy", id, policy
+    );
+
+    if let Some(scope) = ctx.cord.config.shared_timestamps
+        && let Some(start) = manager.get_session(id).and_then(|session| {
+            manager
+                .timestamps
+                .read()
+                .unwrap()
+                .get(&session.timestamp_key(id, scope))
+        })
+    {
+        activity
+            .timestamps
+            .get_or_insert(ActivityTimestamps {
+                start: None,
+                end: None,
+            })
+            .start = Some(start);
+    }
+
+    {
+        let overrides = manager.overrides.read().unwrap();
+        if !overrides.is_empty() {
+            trace!(
+                ctx.client_id,
+                "Composing {} override layer(s)",
+                overrides.layers().len()
+            );
+            activity = overrides.compose(activity);
+        }
+    }
+
+    {
+        let stats = manager.stats.read().unwrap();
+        if stats.is_enabled()
+            && let Some(session) = manager.get_session(id)
+        {
+            stats.render(
+                &mut activity,
+                session.current(),
+                stats.day(now().as_secs()),
+            );
+        }
+    }
+
+    manager.set_active_session(Some(id));
+    let changed = {
+        let mut last_activity = manager.last_activity.write().unwrap();
+        let changed = last_activity.as_ref() != Some(&activity);
+        if changed {
+            *last_activity = Some(activity.clone());
+        }
+        changed
+    };
+
+    let outcome = if changed || force {
+        debug!(
+            ctx.client_id,
+            "Updating activity: session={}, is_idle={}", id, activity.is_idle
+        );
+        ctx.cord.activity_manager.update(
+            activity,
+            Some(id),
+            workspace.as_deref(),
+        )?
+    } else {
+        trace!(ctx.client_id, "Skipping activity update: no change");
+        ActivityOutcome::new(
+            false,
+            ctx.cord
+                .activity_manager
+                .preview(activity, workspace.as_deref()),
+        )
+    };
+
+    if changed {
+        let reason = match reason {
+            PresenceReason::Update if id != ctx.client_id => {
+                PresenceReason::IdleTakeover
+            }
+            reason => reason,
+        };
+        PresenceChangedEvent::new(outcome.activity.clone(), Some(id), reason)
+            .on_event(ctx)?;
+    }
+
+    Ok(outcome)
+}
+
+/// Shows the away activity instead of any session's, or clears the presence
+/// if none is configured.
+fn show_away(
+    ctx: &mut EventContext,
+    reason: PresenceReason,
+    force: bool,
+) -> crate::Result<ActivityOutcome> {
+    let manager = &ctx.cord.session_manager;
+    manager.set_active_session(None);
+    let activity = manager.away.read().unwrap().activity();
+    let changed = {
+        let mut last_activity = manager.last_activity.write().unwrap();
+        let changed = *last_activity != activity;
+        if changed {
+            last_activity.clone_from(&activity);
+        }
+        changed
+    };
+
+    let outcome = if changed || (force && activity.is_some()) {
+        debug!(ctx.client_id, "Showing away presence: {:?}", activity);
+        match activity {
+            Some(activity) => {
+                ctx.cord.activity_manager.update(activity, None, None)?
+            }
+            None => {
+                ActivityOutcome::new(ctx.cord.activity_manager.clear()?, None)
+            }
+        }
+    } else {
+        ActivityOutcome::new(
+            false,
+            activity.and_then(|a| ctx.cord.activity_manager.preview(a, None)),
+        )
+    };
+    if changed {
+        PresenceChangedEvent::new(outcome.activity.clone(), None, reason)
+            .on_event(ctx)?;
+    }
+
+    Ok(outcome)
+}
+
+/// Records an event from the sending session, returning whether it brought
+/// the sessions back from away. The presence is then refreshed by the
+/// caller, once the event is handled.
+pub fn record_event(ctx: &mut EventContext) -> bool {
+    let manager = &ctx.cord.session_manager;
+    if let Some(mut session) = manager.get_session_mut(ctx.client_id) {
+        session.record_event();
+    }
+
+    let back = manager.away.write().unwrap().leave();
+    if back {
+        debug!(ctx.client_id, "Session is back, leaving away");
+    }
+
+    back
+}
+
+/// Goes away once no session had an event within the away timeout.
+pub fn check_away(ctx: &mut EventContext) -> crate::Result<()> {
+    let manager = &ctx.cord.session_manager;
+    let due = {
+        let sessions = manager.sessions.read().unwrap();
+        manager
+            .away
+            .read()
+            .unwrap()
+            .is_due(sessions.values().map(|s| s.last_event))
+    };
+    if !due {
+        return Ok(());
+    }
+
+    debug!("No session had an event for a while, going away");
+    manager.away.write().unwrap().enter(now().as_secs());
+    refresh_presence(ctx, PresenceReason::Away, true)?;
+
+    Ok(())
+}
+
+/// Lets the session that stayed focused for the grace period take over the
+/// presence.
+pub fn settle_focus(ctx: &mut EventContext) -> crate::Result<()> {
+    let settled = {
+        let mut sessions = ctx.cord.session_manager.sessions.write().unwrap();
+        let due = Instant::now();
+        sessions
+            .iter_mut()
+            .find(|(_, s)| s.focus_deadline.is_some_and(|d| d <= due))
+            .map(|(id, s)| {
+                s.focus_deadline = None;
+                s.last_focused = now().as_nanos();
+                *id
+            })
+    };
+    let Some(id) = settled else {
+        return Ok(());
+    };
+
+    debug!(id, "Focus settled, taking over the presence");
+    refresh_presence(ctx, PresenceReason::Focus, false)?;
+
+    Ok(())
+}
+
+/// Credits the most recently updated session that is not idle with the time
+/// since the last check, unless every session is away, and refreshes the
+/// presence if the displayed text shows tracked time.
+pub fn track_time(ctx: &mut EventContext) -> crate::Result<()> {
+    let manager = &ctx.cord.session_manager;
+    let (credited, shows_stats) = {
+        let sessions = manager.sessions.read().unwrap();
+        let away = manager.away.read().unwrap().is_away();
+        let current = sessions
+            .values()
+            .filter(|s| {
+                !away && s.last_activity.as_ref().is_some_and(|a| !a.is_idle)
+            })
+            .max_by_key(|s| s.last_updated)
+            .map(Session::current);
+        let credited = manager.stats.write().unwrap().tick(
+            Instant::now(),
+            now().as_secs(),
+            current,
+        );
+        let shows_stats = manager
+            .active_session()
+            .and_then(|id| sessions.get(&id)?.last_activity.as_ref())
+            .is_some_and(stats::uses_placeholders);
+
+        (credited, shows_stats)
+    };
+    ctx.cord.save_stats(false);
+
+    if credited && shows_stats {
+        refresh_presence(ctx, PresenceReason::Stats, false)?;
+    }
+
+    Ok(())
+}
+
+/// Pauses or resumes the presence if whether it should be paused changed.
+/// Discord is cleared while paused and the presence is sent again on resume.
+pub fn apply_pause(ctx: &mut EventContext) -> crate::Result<()> {
+    let paused = ctx.cord.session_manager.pause.read().unwrap().is_paused();
+    if paused == ctx.cord.activity_manager.is_paused() {
+        return Ok(());
+    }
+
+    if paused {
+        if let Err(e) = ctx.cord.activity_manager.pause() {
+            debug!("Failed to clear activity on pause: {}", e);
+        }
+        PresenceChangedEvent::new(None, None, PresenceReason::Pause)
+            .on_event(ctx)?;
+    } else {
+        ctx.cord.activity_manager.resume();
+        // Forget what was displayed before pausing so that it is sent and
+        // announced again
+        ctx.cord
+            .session_manager
+            .last_activity
+            .write()
+            .unwrap()
+            .take();
+        refresh_presence(ctx, PresenceReason::Pause, true)?;
+    }
+
+    Ok(())
+}
+
+/// Shows the activity restored from a previous server, unless a session
+/// already has one displayed. Called once Discord is ready.
+pub fn show_restored(ctx: &mut EventContext) -> crate::Result<()> {
+    let manager = &ctx.cord.session_manager;
+    let (activity, workspace) = {
+        let mut restored = manager.restored.write().unwrap();
+        (restored.activity.take(), restored.workspace.take())
+    };
+    let Some(activity) = activity else {
+        return Ok(());
+    };
+    if manager.last_activity.read().unwrap().is_some() {
+        return Ok(());
+    }
+
+    debug!(ctx.client_id, "Showing the activity restored from last run");
+    *manager.last_activity.write().unwrap() = Some(activity.clone());
+    let outcome = ctx.cord.activity_manager.update(
+        activity,
+        None,
+        workspace.as_deref(),
+    )?;
+    PresenceChangedEvent::new(outcome.activity, None, PresenceReason::Restore)
+        .on_event(ctx)
+}
+
+/// Sends the presence again once Discord is ready after a reconnect, which
+/// leaves it empty, rather than waiting for every client to send its
+/// activity again. Does nothing while the reconnect is still in progress.
+pub fn resend_presence(ctx: &mut EventContext) -> crate::Result<()> {
+    let manager = &ctx.cord.session_manager;
+    let has_activity = manager
+        .sessions
+        .read()
+        .unwrap()
+        .values()
+        .any(|s| s.last_activity.is_some());
+    let is_ready = ctx
+        .cord
+        .activity_manager
+        .client
+        .read()
+        .unwrap()
+        .is_ready
+        .load(Ordering::SeqCst);
+    if !has_activity || !is_ready || ctx.cord.reconnect_state.in_progress {
+        return Ok(());
+    }
+
+    debug!(
+        ctx.client_id,
+        "Sending the presence again after reconnecting"
+    );
+    manager.last_activity.write().unwrap().take();
+    refresh_presence(ctx, PresenceReason::Reconnect, true)?;
+
+    Ok(())
+}
+
+/// Shows the next active session if rotation is due, as long as Discord's
+/// rate limit leaves room for the sessions' own updates.
+pub fn rotate(ctx: &mut EventContext) -> crate::Result<()> {
+    let manager = &ctx.cord.session_manager;
+    if !manager.rotation.read().unwrap().is_due() {
+        return Ok(());
+    }
+
+    let client = ctx.cord.activity_manager.client.read().unwrap();
+    if !client.is_ready.load(Ordering::SeqCst) {
+        return Ok(());
+    }
+    if client.update_budget() < ROTATION_RESERVE {
+        trace!("Postponing rotation: close to Discord's rate limit");
+        return Ok(());
+    }
+    drop(client);
+
+    let advanced = {
+        let sessions = manager.sessions.read().unwrap();
+        let sharing = manager.sharing.read().unwrap();
+        manager
+            .rotation
+            .write()
+            .unwrap()
+            .advance(&candidates(&sessions, &sharing))
+    };
+    if advanced {
+        refresh_presence(ctx, PresenceReason::Rotation, false)?;
+    }
+
+    Ok(())
+}
+
+/// Asks sessions whether their workspace may be displayed, if sharing must
+/// be confirmed and it was never decided on. Their activity is held back
+/// until a decision is made, unless no session that can handle the question
+/// has that workspace, in which case it is displayed. Sessions whose
+/// workspace cannot be saved are told so with an error.
+fn confirm_shares(ctx: &mut EventContext) {
+    let manager = &ctx.cord.session_manager;
+    let review = {
+        let sessions = manager.sessions.read().unwrap();
+        manager.sharing.write().unwrap().review(
+            sessions
+                .iter()
+                .filter(|(_, s)| s.last_activity.is_some())
+                .filter_map(|(id, s)| {
+                    Some((
+                        *id,
+                        s.workspace.as_deref()?,
+                        s.has_capability("confirm_share"),
+                    ))
+                }),
+        )
+    };
+
+    for workspace in review.unattended {
+        warn!(
+            "No instance can confirm sharing workspace {}, showing it",
+            workspace
+        );
+    }
+    for (id, workspace) in review.ask {
+        debug!(id, "Asking whether to share workspace {}", workspace);
+        if let Err(e) = ConfirmShareEvent::new(id, workspace).on_event(ctx) {
+            debug!(id, "Failed to ask for confirmation: {}", e);
+        }
+    }
+    for (id, workspace) in review.rejected {
+        warn!(id, "Workspace {:?} cannot be shared", workspace);
+        if let Err(e) = report_unshareable(ctx, id, &workspace) {
+            debug!(id, "Failed to report unshareable workspace: {}", e);
+        }
+    }
+}
+
+fn report_unshareable(
+    ctx: &EventContext,
+    id: u32,
+    workspace: &str,
+) -> crate::Result<()> {
+    let error = ErrorEvent::unshareable_workspace(workspace);
+    ctx.cord.pipe.write_to(id, &MsgPack::serialize(&error)?)?;
+
+    Ok(())
+}
+
+/// The sessions that have an activity and may be displayed.
+fn candidates<'a>(
+    sessions: &'a HashMap<u32, Session>,
+    sharing: &Sharing,
+) -> Vec<Candidate<'a>> {
+    sessions
+        .iter()
+        .filter(|(_, s)| sharing.is_allowed(s.workspace.as_deref()))
+        .filter_map(|(id, s)| {
+            let activity = s.last_activity.as_ref()?;
+            Some(Candidate {
+                id: *id,
+                is_idle: activity.is_idle,
+                last_updated: s.last_updated,
+                last_focused: s.last_focused,
+                workspace: s.workspace.as_deref(),
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,331 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+
+/// Which workspaces may be displayed when sharing must be confirmed. A
+/// workspace is held back until a session allows it, and the decision is
+/// kept in a file so that it is only asked for once.
+#[derive(Debug, Default)]
+pub struct Sharing {
+    enabled: bool,
+    path: Option<PathBuf>,
+    /// Whether each decided workspace is allowed.
+    decisions: HashMap<String, bool>,
+    /// Workspaces awaiting a decision, with the session that was asked.
+    pending: HashMap<String, u32>,
+    /// Undecided workspaces that no session can be asked about, which are
+    /// displayed until one can.
+    unattended: HashSet<String>,
+    /// Workspaces that cannot be saved, with the session that was told.
+    rejected: HashMap<String, u32>,
+}
+
+/// What to do about the undecided workspaces of the sessions.
+#[derive(Debug, Default, PartialEq)]
+pub struct Review {
+    /// Sessions to ask whether their workspace may be displayed.
+    pub ask: Vec<(u32, String)>,
+    /// Workspaces now displayed as no session can be asked about them.
+    pub unattended: Vec<String>,
+    /// Sessions to tell that their workspace cannot be shared.
+    pub rejected: Vec<(u32, String)>,
+}
+
+impl Sharing {
+    /// Reads the decisions saved at `path`, if any.
+    pub fn load(path: PathBuf) -> crate::Result<Self> {
+        let decisions = match fs::read_to_string(&path) {
+            Ok(text) => parse(&text),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                HashMap::new()
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(Self {
+            path: Some(path),
+            decisions,
+            ..Default::default()
+        })
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.pending.clear();
+            self.unattended.clear();
+            self.rejected.clear();
+        }
+    }
+
+    /// Whether an activity of `workspace` may be displayed. Sessions that
+    /// report no workspace are always shown.
+    pub fn is_allowed(&self, workspace: Option<&str>) -> bool {
+        !self.enabled
+            || workspace.is_none_or(|w| match self.decisions.get(w) {
+                Some(allowed) => *allowed,
+                None => self.unattended.contains(w),
+            })
+    }
+
+    /// Holds the undecided workspaces of `sessions`, given as their id,
+    /// workspace and whether they can be asked, back until they are decided
+    /// on. Each workspace is asked for once at a time. One that no session
+    /// can be asked about is displayed instead, and one that cannot be saved
+    /// stays hidden, which its sessions are told once.
+    pub fn review<'a>(
+        &mut self,
+        sessions: impl IntoIterator<Item = (u32, &'a str, bool)>,
+    ) -> Review {
+        let mut review = Review::default();
+        if !self.enabled {
+            return review;
+        }
+
+        let mut attended = HashSet::new();
+        let mut unattended = HashSet::new();
+        for (id, workspace, can_ask) in sessions {
+            if self.decisions.contains_key(workspace) {
+                continue;
+            }
+            if !is_savable(workspace) {
+                if !self.rejected.contains_key(workspace) {
+                    self.rejected.insert(workspace.to_string(), id);
+                    review.rejected.push((id, workspace.to_string()));
+                }
+                continue;
+            }
+            if !can_ask {
+                unattended.insert(workspace.to_string());
+                continue;
+            }
+
+            attended.insert(workspace);
+            if !self.pending.contains_key(workspace) {
+                self.pending.insert(workspace.to_string(), id);
+                review.ask.push((id, workspace.to_string()));
+            }
+        }
+
+        unattended.retain(|w| !attended.contains(w.as_str()));
+        review.unattended =
+            unattended.difference(&self.unattended).cloned().collect();
+        self.unattended = unattended;
+
+        review
+    }
+
+    /// Records whether `workspace` may be displayed and saves the decisions.
+    pub fn decide(
+        &mut self,
+        workspace: String,
+        allowed: bool,
+    ) -> crate::Result<()> {
+        if !is_savable(&workspace) {
+            return Err(format!(
+                "Workspace contains control characters: {:?}",
+                workspace
+            )
+            .into());
+        }
+        self.pending.remove(&workspace);
+        self.unattended.remove(&workspace);
+        self.decisions.insert(workspace, allowed);
+
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, format(&self.decisions))?;
+        fs::rename(&tmp, path)?;
+
+        Ok(())
+    }
+
+    /// Forgets the questions asked to `session`, so that another session
+    /// with the same workspace is asked instead.
+    pub fn remove_session(&mut self, session: u32) {
+        self.pending.retain(|_, asked| *asked != session);
+        self.rejected.retain(|_, told| *told != session);
+    }
+}
+
+/// Whether `workspace` fits on a line of the decisions file. Control
+/// characters could end the line and inject other decisions.
+fn is_savable(workspace: &str) -> bool {
+    !workspace.chars().any(char::is_control)
+}
+
+/// Parses `allow <workspace>` and `deny <workspace>` lines, ignoring blank
+/// lines, comments starting with `#` and anything else. The workspace is
+/// everything after the first space, as paths may start or end with one.
+fn parse(text: &str) -> HashMap<String, bool> {
+    text.lines()
+        .filter_map(|line| {
+            let (decision, workspace) = line.split_once(' ')?;
+            let allowed = match decision {
+                "allow" => true,
+                "deny" => false,
+                _ => return None,
+            };
+
+            (!workspace.is_empty()).then(|| (workspace.to_string(), allowed))
+        })
+        .collect()
+}
+
+fn format(decisions: &HashMap<String, bool>) -> String {
+    let mut lines: Vec<String> = decisions
+        .iter()
+        .map(|(workspace, allowed)| {
+            let decision = if *allowed { "allow" } else { "deny" };
+            format!("{} {}\n", decision, workspace)
+        })
+        .collect();
+    lines.sort_unstable();
+
+    lines.concat()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn enabled() -> Sharing {
+        let mut sharing = Sharing::default();
+        sharing.set_enabled(true);
+        sharing
+    }
+
+    fn ask(id: u32, workspace: &str) -> Review {
+        Review {
+            ask: vec![(id, workspace.to_string())],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn disabled_allows_everything() {
+        let mut sharing = Sharing::default();
+        assert!(sharing.is_allowed(Some("/work/acme")));
+        assert_eq!(
+            sharing.review([(1, "/work/acme", true)]),
+            Review::default()
+        );
+    }
+
+    #[test]
+    fn workspaces_are_held_until_allowed() {
+        let mut sharing = enabled();
+        assert!(sharing.is_allowed(None));
+
+        assert_eq!(
+            sharing.review([(1, "/work/acme", true), (2, "/work/acme", true)]),
+            ask(1, "/work/acme")
+        );
+        assert!(!sharing.is_allowed(Some("/work/acme")));
+        assert_eq!(
+            sharing.review([(2, "/work/acme", true)]),
+            Review::default()
+        );
+
+        sharing.decide("/work/acme".to_string(), true).unwrap();
+        assert!(sharing.is_allowed(Some("/work/acme")));
+        assert_eq!(
+            sharing.review([(1, "/work/acme", true)]),
+            Review::default()
+        );
+
+        sharing.decide("/work/acme".to_string(), false).unwrap();
+        assert!(!sharing.is_allowed(Some("/work/acme")));
+        assert_eq!(
+            sharing.review([(1, "/work/acme", true)]),
+            Review::default()
+        );
+    }
+
+    #[test]
+    fn another_session_is_asked_once_the_first_is_gone() {
+        let mut sharing = enabled();
+        assert_eq!(
+            sharing.review([(1, "/work/acme", true)]),
+            ask(1, "/work/acme")
+        );
+
+        sharing.remove_session(1);
+        assert_eq!(
+            sharing.review([(2, "/work/acme", true)]),
+            ask(2, "/work/acme")
+        );
+    }
+
+    #[test]
+    fn workspaces_no_session_can_answer_are_shown() {
+        let mut sharing = enabled();
+        assert_eq!(
+            sharing.review([(1, "/work/acme", false)]),
+            Review {
+                unattended: vec!["/work/acme".to_string()],
+                ..Default::default()
+            }
+        );
+        assert!(sharing.is_allowed(Some("/work/acme")));
+        assert_eq!(
+            sharing.review([(1, "/work/acme", false)]),
+            Review::default()
+        );
+
+        // Held back again once a session can be asked
+        assert_eq!(
+            sharing.review([(1, "/work/acme", false), (2, "/work/acme", true)]),
+            ask(2, "/work/acme")
+        );
+        assert!(!sharing.is_allowed(Some("/work/acme")));
+    }
+
+    #[test]
+    fn control_characters_are_rejected() {
+        let mut sharing = enabled();
+        let workspace = "/work/acme\nallow /work/secret";
+        assert_eq!(
+            sharing.review([(1, workspace, true), (1, workspace, false)]),
+            Review {
+                rejected: vec![(1, workspace.to_string())],
+                ..Default::default()
+            }
+        );
+        assert_eq!(sharing.review([(1, workspace, true)]), Review::default());
+        assert!(sharing.decide(workspace.to_string(), true).is_err());
+        assert!(!sharing.is_allowed(Some(workspace)));
+        assert!(!sharing.is_allowed(Some("/work/secret")));
+        assert_eq!(format(&sharing.decisions), "");
+    }
+    #[test]
+    fn decisions_round_trip() {
+        let text = "# decisions\nallow /home/me/src\ndeny /work/acme inc\n\n\
+                    maybe /x\nallow \n";
+        let decisions = parse(text);
+
+        assert_eq!(decisions.len(), 2);
+        assert_eq!(decisions.get("/home/me/src"), Some(&true));
+        assert_eq!(decisions.get("/work/acme inc"), Some(&false));
+        assert_eq!(
+            format(&decisions),
+            "allow /home/me/src\ndeny /work/acme inc\n"
+        );
+        assert_eq!(parse(&format(&decisions)), decisions);
+    }
+
+    #[test]
+    fn workspaces_keep_their_spaces() {
+        let mut decisions = HashMap::new();
+        decisions.insert(" /work/acme ".to_string(), true);
+        decisions.insert("/work/ inc".to_string(), false);
+
+        assert_eq!(parse(&format(&decisions)), decisions);
+        assert_eq!(parse("allow  /lead").get(" /lead"), Some(&true));
+    }
+}
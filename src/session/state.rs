@@ -0,0 +1,675 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use crate::presence::activity::{Activity, ActivityType, StatusDisplayType};
+use crate::protocol::msgpack::{
+    Deserialize, MsgPack, Serialize, SerializeFn, SerializeState, Value,
+    ValueRef,
+};
+use crate::session::overrides::Override;
+use crate::session::{Pin, SessionManager};
+use crate::util::now;
+
+/// How long an unchanged state may go without being written again, so that
+/// its age tells how recently the server was running.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Server state kept across restarts and handed over to a new server.
+/// Sessions get new ids when they reconnect, so sessions, the pin and
+/// override layers are identified by the process id of their client.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct PersistedState {
+    /// Unix timestamp, in seconds, at which the state was saved.
+    pub saved_at: u64,
+    /// Start timestamps of shared timestamps by scope key.
+    pub timestamps: Vec<(String, u64)>,
+    /// Sessions whose client reported its process id.
+    pub sessions: Vec<PersistedSession>,
+    pub pin: Option<PersistedPin>,
+    pub overrides: Vec<PersistedOverride>,
+    /// The activity Discord was displaying.
+    pub activity: Option<Activity>,
+    /// The workspace of the displayed activity, so that its privacy rules
+    /// apply when it is shown again.
+    pub workspace: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PersistedSession {
+    pub pid: u32,
+    pub workspace: Option<String>,
+    pub file: Option<String>,
+    pub activity: Option<Activity>,
+    /// Unix timestamp, in milliseconds, of the session's last update.
+    pub last_updated: u64,
+    /// Unix timestamp, in milliseconds, at which the session took over
+    /// focus, 0 if it never did.
+    pub last_focused: u64,
+    pub is_focused: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PersistedPin {
+    pub pid: u32,
+    /// Unix timestamp, in seconds, at which the pin expires.
+    pub expires_at: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PersistedOverride {
+    pub id: String,
+    /// Process id of the session that pushed the layer, if it reported one.
+    pub pid: Option<u32>,
+    pub priority: i64,
+    pub activity: Activity,
+    pub ty: Option<ActivityType>,
+    pub status_display_type: Option<StatusDisplayType>,
+    /// Unix timestamp, in seconds, at which the layer expires.
+    pub expires_at: Option<u64>,
+}
+
+/// Restored state that belonged to sessions which have not reconnected yet.
+#[derive(Debug, Default)]
+pub struct Restored {
+    sessions: Vec<PersistedSession>,
+    /// Process id of the pinned session and when the pin expires.
+    pin: Option<(u32, Option<Instant>)>,
+    /// Ids of restored override layers and the process id of the session
+    /// that pushed them.
+    overrides: Vec<(String, u32)>,
+    /// The activity Discord was displaying, shown once Discord is ready
+    /// unless a session already has an activity.
+    pub activity: Option<Activity>,
+    /// The workspace of `activity`.
+    pub workspace: Option<String>,
+}
+
+impl PersistedState {
+    /// Captures the state of `manager` along with the activity `displayed`
+    /// on Discord. The latter has privacy rules applied, so that a hidden
+    /// workspace is not shown again before its session reconnects.
+    pub fn capture(
+        manager: &SessionManager,
+        displayed: Option<Activity>,
+    ) -> Self {
+        let sessions = manager.sessions.read().unwrap();
+        let pid = |id: u32| sessions.get(&id).and_then(|s| s.client.pid);
+        let workspace = displayed.as_ref().and_then(|_| {
+            sessions.get(&manager.active_session()?)?.workspace.clone()
+        });
+
+        let mut timestamps: Vec<(String, u64)> = manager
+            .timestamps
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(key, start)| (key.to_string(), start))
+            .collect();
+        timestamps.sort_unstable();
+
+        let mut persisted_sessions: Vec<PersistedSession> = sessions
+            .values()
+            .filter_map(|s| {
+                Some(PersistedSession {
+                    pid: s.client.pid?,
+                    workspace: s.workspace.clone(),
+                    file: s.file.clone(),
+                    activity: s.last_activity.clone(),
+                    last_updated: (s.last_updated / 1_000_000) as u64,
+                    last_focused: (s.last_focused / 1_000_000) as u64,
+                    is_focused: s.is_focused,
+                })
+            })
+            .collect();
+        persisted_sessions.sort_unstable_by_key(|s| s.pid);
+
+        let pin = manager.pin.read().unwrap().and_then(|pin| {
+            Some(PersistedPin {
+                pid: pid(pin.session)?,
+                expires_at: pin.expires_at.map(to_unix),
+            })
+        });
+
+        let overrides = manager
+            .overrides
+            .read()
+            .unwrap()
+            .layers()
+            .iter()
+            .filter(|layer| !layer.is_expired())
+            .map(|layer| PersistedOverride {
+                id: layer.id.clone(),
+                pid: pid(layer.session),
+                priority: layer.priority,
+                activity: layer.activity.clone(),
+                ty: layer.ty,
+                status_display_type: layer.status_display_type,
+                expires_at: layer.expires_at.map(to_unix),
+            })
+            .collect();
+
+        Self {
+            saved_at: now().as_secs(),
+            timestamps,
+            sessions: persisted_sessions,
+            pin,
+            overrides,
+            activity: displayed,
+            workspace,
+        }
+    }
+
+    /// Restores the state into `manager`. Override layers are shown right
+    /// away and belong to no session until the one that pushed them
+    /// reconnects, sessions and the pin only apply once their client
+    /// reconnects.
+    pub fn restore(self, manager: &SessionManager) {
+        let now = now().as_secs();
+
+        let mut timestamps = manager.timestamps.write().unwrap();
+        for (key, start) in self.timestamps {
+            timestamps.restore(key, start);
+        }
+
+        let mut restored = manager.restored.write().unwrap();
+        let mut overrides = manager.overrides.write().unwrap();
+        for layer in self.overrides {
+            if layer.expires_at.is_some_and(|at| at <= now) {
+                continue;
+            }
+            if let Some(pid) = layer.pid {
+                restored.overrides.push((layer.id.clone(), pid));
+            }
+            overrides.push(Override {
+                id: layer.id,
+                session: 0,
+                priority: layer.priority,
+                activity: layer.activity,
+                ty: layer.ty,
+                status_display_type: layer.status_display_type,
+                expires_at: layer.expires_at.map(from_unix),
+            });
+        }
+
+        restored.sessions = self.sessions;
+        restored.pin = self
+            .pin
+            .filter(|pin| pin.expires_at.is_none_or(|at| at > now))
+            .map(|pin| (pin.pid, pin.expires_at.map(from_unix)));
+        restored.activity = self.activity;
+        restored.workspace = self.workspace;
+    }
+
+    /// Whether the state was saved within `freshness` of `now`, in seconds.
+    pub fn is_fresh(&self, freshness: Duration, now: u64) -> bool {
+        now.saturating_sub(self.saved_at) <= freshness.as_secs()
+    }
+
+    /// Whether both states hold the same data, regardless of when they
+    /// were saved.
+    fn same_as(&self, other: &Self) -> bool {
+        self.timestamps == other.timestamps
+            && self.sessions == other.sessions
+            && self.pin == other.pin
+            && self.overrides == other.overrides
+            && self.activity == other.activity
+            && self.workspace == other.workspace
+    }
+}
+
+impl Restored {
+    /// Hands the session data, pin and override layers that belonged to
+    /// process `pid` over to `session`. Returns whether anything was
+    /// claimed.
+    pub fn claim(
+        &mut self,
+        manager: &SessionManager,
+        session: u32,
+        pid: u32,
+    ) -> bool {
+        let mut claimed = false;
+
+        if let Some(index) = self.sessions.iter().position(|s| s.pid == pid)
+            && let Some(mut target) = manager.get_session_mut(session)
+        {
+            let restored = self.sessions.swap_remove(index);
+            target.workspace = restored.workspace;
+            target.file = restored.file;
+            target.last_activity = restored.activity;
+            target.last_updated = restored.last_updated as u128 * 1_000_000;
+            target.last_focused = restored.last_focused as u128 * 1_000_000;
+            target.is_focused = restored.is_focused;
+            claimed = true;
+        }
+
+        if let Some((_, expires_at)) =
+            self.pin.take_if(|(owner, _)| *owner == pid)
+        {
+            manager.set_pin(Some(Pin {
+                session,
+                expires_at,
+            }));
+            claimed = true;
+        }
+
+        let mut overrides = manager.overrides.write().unwrap();
+        self.overrides.retain(|(id, owner)| {
+            if *owner != pid {
+                return true;
+            }
+            claimed |= overrides.adopt(id, session);
+            false
+        });
+
+        claimed
+    }
+}
+
+/// The file the server state is saved to.
+pub struct StateFile {
+    path: PathBuf,
+    /// How old a saved state may be and still be restored.
+    freshness: Duration,
+    /// The last state written, and when.
+    last: Option<(Instant, PersistedState)>,
+}
+
+impl StateFile {
+    pub fn new(path: PathBuf, freshness: Duration) -> Self {
+        Self {
+            path,
+            freshness,
+            last: None,
+        }
+    }
+
+    /// `server-state` in the state directory.
+    pub fn default_path() -> Option<PathBuf> {
+        state_dir().map(|dir| dir.join("server-state"))
+    }
+
+    /// Reads the saved state, if there is one and it is fresh enough.
+    pub fn load(&self) -> crate::Result<Option<PersistedState>> {
+        let bytes = match fs::read(&self.path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(None);
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let state = PersistedState::deserialize(MsgPack::deserialize(&bytes)?)?;
+
+        Ok(state
+            .is_fresh(self.freshness, now().as_secs())
+            .then_some(state))
+    }
+
+    /// Writes `state` unless it is the same as the one last written and that
+    /// one is recent.
+    pub fn save(&mut self, state: PersistedState) -> crate::Result<()> {
+        if let Some((at, last)) = &self.last
+            && at.elapsed() < REFRESH_INTERVAL
+            && last.same_as(&state)
+        {
+            return Ok(());
+        }
+
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, state.to_msgpack()?)?;
+        fs::rename(&tmp, &self.path)?;
+        self.last = Some((Instant::now(), state));
+
+        Ok(())
+    }
+}
+
+/// `$XDG_STATE_HOME/cord`, falling back to `~/.local/state` or, on Windows,
+/// `%LOCALAPPDATA%` when the variable is not set.
+pub fn state_dir() -> Option<PathBuf> {
+    let dir = env::var_os("XDG_STATE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from);
+    #[cfg(target_os = "windows")]
+    let dir = dir.or_else(|| env::var_os("LOCALAPPDATA").map(PathBuf::from));
+    #[cfg(not(target_os = "windows"))]
+    let dir = dir.or_else(|| {
+        env::var_os("HOME")
+            .map(|home| PathBuf::from(home).join(".local").join("state"))
+    });
+
+    dir.map(|dir| dir.join("cord"))
+}
+
+/// Converts `at` to a Unix timestamp in seconds.
+fn to_unix(at: Instant) -> u64 {
+    now().as_secs() + at.saturating_duration_since(Instant::now()).as_secs()
+}
+
+/// Converts a Unix timestamp in seconds to an instant, which is now if the
+/// timestamp is in the past.
+fn from_unix(secs: u64) -> Instant {
+    Instant::now() + Duration::from_secs(secs.saturating_sub(now().as_secs()))
+}
+
+impl Serialize for PersistedState {
+    fn serialize<'a>(
+        &'a self,
+        f: SerializeFn<'a>,
+        state: &mut SerializeState,
+    ) -> crate::Result<()> {
+        f("saved_at", ValueRef::UInteger(self.saved_at), state)?;
+        f(
+            "timestamps",
+            ValueRef::Map(
+                self.timestamps
+                    .iter()
+                    .map(|(key, start)| {
+                        (key.as_str(), ValueRef::UInteger(*start))
+                    })
+                    .collect(),
+            ),
+            state,
+        )?;
+        f(
+            "sessions",
+            ValueRef::Array(
+                self.sessions
+                    .iter()
+                    .map(|session| ValueRef::Object(session as &dyn Serialize))
+                    .collect(),
+            ),
+            state,
+        )?;
+        if let Some(pin) = &self.pin {
+            let mut data = HashMap::new();
+            data.insert("pid", ValueRef::UInteger(pin.pid as u64));
+            if let Some(expires_at) = pin.expires_at {
+                data.insert("expires_at", ValueRef::UInteger(expires_at));
+            }
+            f("pin", ValueRef::Map(data), state)?;
+        }
+        f(
+            "overrides",
+            ValueRef::Array(
+                self.overrides
+                    .iter()
+                    .map(|layer| ValueRef::Object(layer as &dyn Serialize))
+                    .collect(),
+            ),
+            state,
+        )?;
+        if let Some(activity) = &self.activity {
+            f("activity", ValueRef::Object(activity), state)?;
+        }
+        if let Some(workspace) = &self.workspace {
+            f("workspace", ValueRef::Str(workspace), state)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Serialize for PersistedSession {
+    fn serialize<'a>(
+        &'a self,
+        f: SerializeFn<'a>,
+        state: &mut SerializeState,
+    ) -> crate::Result<()> {
+        f("pid", ValueRef::UInteger(self.pid as u64), state)?;
+        if let Some(workspace) = &self.workspace {
+            f("workspace", ValueRef::Str(workspace), state)?;
+        }
+        if let Some(file) = &self.file {
+            f("file", ValueRef::Str(file), state)?;
+        }
+        if let Some(activity) = &self.activity {
+            f("activity", ValueRef::Object(activity), state)?;
+        }
+        f("last_updated", ValueRef::UInteger(self.last_updated), state)?;
+        f("last_focused", ValueRef::UInteger(self.last_focused), state)?;
+        f("is_focused", ValueRef::Boolean(self.is_focused), state)?;
+
+        Ok(())
+    }
+}
+
+impl Serialize for PersistedOverride {
+    fn serialize<'a>(
+        &'a self,
+        f: SerializeFn<'a>,
+        state: &mut SerializeState,
+    ) -> crate::Result<()> {
+        f("id", ValueRef::Str(&self.id), state)?;
+        if let Some(pid) = self.pid {
+            f("pid", ValueRef::UInteger(pid as u64), state)?;
+        }
+        f("priority", ValueRef::Integer(self.priority), state)?;
+        f("activity", ValueRef::Object(&self.activity), state)?;
+        if let Some(ty) = self.ty {
+            f("type", ValueRef::String(ty.to_string()), state)?;
+        }
+        if let Some(status_display_type) = self.status_display_type {
+            f(
+                "status_display_type",
+                ValueRef::String(status_display_type.to_string()),
+                state,
+            )?;
+        }
+        if let Some(expires_at) = self.expires_at {
+            f("expires_at", ValueRef::UInteger(expires_at), state)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Deserialize for PersistedState {
+    fn deserialize(input: Value) -> crate::Result<Self> {
+        let mut map = input.take_map().ok_or("Invalid server state")?;
+
+        let saved_at = map
+            .remove("saved_at")
+            .and_then(|v| v.as_uinteger())
+            .ok_or("Missing or invalid 'saved_at' field")?;
+        let mut timestamps: Vec<(String, u64)> = map
+            .remove("timestamps")
+            .and_then(|v| v.take_map())
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|(key, start)| Some((key, start.as_uinteger()?)))
+            .collect();
+        timestamps.sort_unstable();
+        let sessions = map
+            .remove("sessions")
+            .and_then(|v| v.take_array())
+            .unwrap_or_default()
+            .into_iter()
+            .map(PersistedSession::deserialize)
+            .collect::<crate::Result<_>>()?;
+        let pin =
+            map.remove("pin")
+                .and_then(|v| v.take_map())
+                .and_then(|mut pin| {
+                    Some(PersistedPin {
+                        pid: pin
+                            .remove("pid")?
+                            .as_uinteger()
+                            .and_then(|pid| u32::try_from(pid).ok())?,
+                        expires_at: pin
+                            .remove("expires_at")
+                            .and_then(|v| v.as_uinteger()),
+                    })
+                });
+        let overrides = map
+            .remove("overrides")
+            .and_then(|v| v.take_array())
+            .unwrap_or_default()
+            .into_iter()
+            .map(PersistedOverride::deserialize)
+            .collect::<crate::Result<_>>()?;
+        let activity = map.remove("activity").map(Activity::deserialize);
+
+        Ok(Self {
+            saved_at,
+            timestamps,
+            sessions,
+            pin,
+            overrides,
+            activity: activity.transpose()?,
+            workspace: map.remove("workspace").and_then(|v| v.take_string()),
+        })
+    }
+}
+
+impl Deserialize for PersistedSession {
+    fn deserialize(input: Value) -> crate::Result<Self> {
+        let mut map = input.take_map().ok_or("Invalid session")?;
+
+        Ok(Self {
+            pid: map
+                .remove("pid")
+                .and_then(|v| v.as_uinteger())
+                .and_then(|pid| u32::try_from(pid).ok())
+                .ok_or("Missing or invalid 'pid' field")?,
+            workspace: map.remove("workspace").and_then(|v| v.take_string()),
+            file: map.remove("file").and_then(|v| v.take_string()),
+            activity: map
+                .remove("activity")
+                .map(Activity::deserialize)
+                .transpose()?,
+            last_updated: map
+                .remove("last_updated")
+                .and_then(|v| v.as_uinteger())
+                .unwrap_or_default(),
+            last_focused: map
+                .remove("last_focused")
+                .and_then(|v| v.as_uinteger())
+                .unwrap_or_default(),
+            is_focused: map
+                .remove("is_focused")
+                .and_then(|v| v.as_bool())
+                .unwrap_or_default(),
+        })
+    }
+}
+
+impl Deserialize for PersistedOverride {
+    fn deserialize(input: Value) -> crate::Result<Self> {
+        let mut map = input.take_map().ok_or("Invalid override layer")?;
+
+        Ok(Self {
+            id: map
+                .remove("id")
+                .and_then(|v| v.take_string())
+                .ok_or("Missing or invalid 'id' field")?,
+            pid: map
+                .remove("pid")
+                .and_then(|v| v.as_uinteger())
+                .and_then(|pid| u32::try_from(pid).ok()),
+            priority: map
+                .remove("priority")
+                .and_then(|v| v.as_integer())
+                .unwrap_or_default(),
+            activity: Activity::deserialize(
+                map.remove("activity")
+                    .ok_or("Missing or invalid 'activity' field")?,
+            )?,
+            ty: map
+                .remove("type")
+                .and_then(|v| ActivityType::from_str(v.as_str()?).ok()),
+            status_display_type: map
+                .remove("status_display_type")
+                .and_then(|v| StatusDisplayType::from_str(v.as_str()?).ok()),
+            expires_at: map.remove("expires_at").and_then(|v| v.as_uinteger()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn activity(details: &str) -> Activity {
+        Activity {
+            details: Some(details.to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn state() -> PersistedState {
+        PersistedState {
+            saved_at: 1000,
+            timestamps: vec![
+                ("global".to_string(), 100),
+                ("workspace:/a".to_string(), 200),
+            ],
+            sessions: vec![PersistedSession {
+                pid: 42,
+                workspace: Some("/a".to_string()),
+                file: None,
+                activity: Some(activity("Editing lib.rs")),
+                last_updated: 900,
+                last_focused: 0,
+                is_focused: true,
+            }],
+            pin: Some(PersistedPin {
+                pid: 42,
+                expires_at: Some(2000),
+            }),
+            overrides: vec![PersistedOverride {
+                id: "meeting".to_string(),
+                pid: None,
+                priority: -1,
+                activity: activity("In a meeting"),
+                ty: Some(ActivityType::Listening),
+                status_display_type: None,
+                expires_at: None,
+            }],
+            activity: Some(activity("Editing main.rs")),
+            workspace: Some("/a".to_string()),
+        }
+    }
+
+    #[test]
+    fn state_round_trips() {
+        let state = state();
+        let bytes = state.to_msgpack().unwrap();
+        let decoded =
+            PersistedState::deserialize(MsgPack::deserialize(&bytes).unwrap())
+                .unwrap();
+
+        assert_eq!(decoded, state);
+    }
+
+    #[test]
+    fn stale_states_are_not_fresh() {
+        let state = state();
+        let freshness = Duration::from_secs(60);
+
+        assert!(state.is_fresh(freshness, 1060));
+        assert!(!state.is_fresh(freshness, 1061));
+    }
+
+    #[test]
+    fn saving_time_does_not_change_the_state() {
+        let state = state();
+        let later = PersistedState {
+            saved_at: 2000,
+            ..state.clone()
+        };
+        let changed = PersistedState {
+            pin: None,
+            ..state.clone()
+        };
+
+        assert!(state.same_as(&later));
+        assert!(!state.same_as(&changed));
+    }
+}
@@ -0,0 +1,541 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use crate::presence::activity::Activity;
+use crate::util::glob;
+
+/// Longest time credited for a single tick, so that a stalled event loop or
+/// a suspend is not counted as activity.
+const MAX_TICK: Duration = Duration::from_secs(5);
+/// How often accumulated time is written to the file.
+const SAVE_INTERVAL: Duration = Duration::from_secs(60);
+/// Filetype recorded when the session reports none.
+const UNKNOWN: &str = "unknown";
+
+/// Placeholders replaced in the displayed text by the time they stand for.
+const PLACEHOLDERS: &[&str] = &[
+    "{stats.today}",
+    "{stats.workspace_today}",
+    "{stats.filetype_today}",
+    "{stats.workspace_total}",
+];
+
+/// Active time, in milliseconds, per local day, workspace and filetype,
+/// kept in a file across restarts.
+#[derive(Debug, Default)]
+pub struct Stats {
+    enabled: bool,
+    /// Minutes to add to UTC to get the local time days are counted in.
+    utc_offset: i64,
+    path: Option<PathBuf>,
+    totals: HashMap<Key, u64>,
+    last_tick: Option<Instant>,
+    last_save: Option<Instant>,
+    dirty: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Key {
+    /// Days since the Unix epoch, in local time.
+    pub day: i64,
+    pub workspace: String,
+    pub filetype: String,
+}
+
+/// What is being worked on, credited with the time since the last tick.
+#[derive(Debug, Clone, Copy)]
+pub struct Current<'a> {
+    pub workspace: Option<&'a str>,
+    pub filetype: Option<&'a str>,
+}
+
+/// Which totals a query sums up.
+#[derive(Debug, Default)]
+pub struct StatsFilter {
+    /// Days to include, counting back from today, 0 for all of them.
+    pub days: u64,
+    /// Glob the workspace must match.
+    pub workspace: Option<String>,
+}
+
+/// Totals of the time matching a filter, in milliseconds.
+#[derive(Debug, Default, PartialEq)]
+pub struct Summary {
+    pub today: u64,
+    pub total: u64,
+    /// Newest day first.
+    pub days: Vec<(String, u64)>,
+    /// Longest first.
+    pub workspaces: Vec<(String, u64)>,
+    pub filetypes: Vec<(String, u64)>,
+}
+
+impl Stats {
+    /// Reads the totals saved at `path`, if any.
+    pub fn load(path: PathBuf) -> crate::Result<Self> {
+        let totals = match fs::read_to_string(&path) {
+            Ok(text) => parse(&text),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                HashMap::new()
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(Self {
+            path: Some(path),
+            totals,
+            ..Default::default()
+        })
+    }
+
+    /// Reads the totals again, once another server saved them.
+    pub fn reload(&mut self) -> crate::Result<()> {
+        if let Some(path) = self.path.take() {
+            let loaded = Self::load(path)?;
+            self.path = loaded.path;
+            self.totals = loaded.totals;
+            self.dirty = false;
+        }
+
+        Ok(())
+    }
+
+    pub fn configure(&mut self, enabled: bool, utc_offset: i64) {
+        self.enabled = enabled;
+        self.utc_offset = utc_offset;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// The local day `wall` seconds after the Unix epoch falls on.
+    pub fn day(&self, wall: u64) -> i64 {
+        (wall as i64 + self.utc_offset * 60).div_euclid(86400)
+    }
+
+    /// Credits `current`, if any, with the time since the last tick.
+    /// Returns whether any time was credited.
+    pub fn tick(
+        &mut self,
+        at: Instant,
+        wall: u64,
+        current: Option<Current>,
+    ) -> bool {
+        let elapsed = self
+            .last_tick
+            .replace(at)
+            .map_or(Duration::ZERO, |last| at.saturating_duration_since(last))
+            .min(MAX_TICK);
+        let Some(current) = current.filter(|_| self.enabled) else {
+            return false;
+        };
+        if elapsed.is_zero() {
+            return false;
+        }
+
+        let key = Key {
+            day: self.day(wall),
+            workspace: current.workspace.unwrap_or_default().to_string(),
+            filetype: current.filetype.unwrap_or(UNKNOWN).to_string(),
+        };
+        *self.totals.entry(key).or_default() += elapsed.as_millis() as u64;
+        self.dirty = true;
+
+        true
+    }
+
+    /// Writes the totals if they changed and were not written for a while,
+    /// or right away if `force` is set.
+    pub fn save(&mut self, at: Instant, force: bool) -> crate::Result<()> {
+        let due = self
+            .last_save
+            .is_none_or(|last| at.duration_since(last) >= SAVE_INTERVAL);
+        if !self.dirty || !(due || force) {
+            return Ok(());
+        }
+        self.last_save = Some(at);
+        self.dirty = false;
+
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, format(&self.totals))?;
+        fs::rename(&tmp, path)?;
+
+        Ok(())
+    }
+
+    /// Sums up the time matching `filter`, `today` being the current day.
+    pub fn summary(&self, filter: &StatsFilter, today: i64) -> Summary {
+        let mut days: HashMap<i64, u64> = HashMap::new();
+        let mut workspaces: HashMap<&str, u64> = HashMap::new();
+        let mut filetypes: HashMap<&str, u64> = HashMap::new();
+
+        for (key, time) in &self.totals {
+            if (filter.days > 0 && today - key.day >= filter.days as i64)
+                || filter.workspace.as_deref().is_some_and(|pattern| {
+                    !glob::matches(pattern, &key.workspace)
+                })
+            {
+                continue;
+            }
+            *days.entry(key.day).or_default() += time;
+            *workspaces.entry(&key.workspace).or_default() += time;
+            *filetypes.entry(&key.filetype).or_default() += time;
+        }
+
+        let mut summary = Summary {
+            today: days.get(&today).copied().unwrap_or_default(),
+            total: days.values().sum(),
+            ..Default::default()
+        };
+        let mut days: Vec<_> = days.into_iter().collect();
+        days.sort_unstable_by_key(|(day, _)| Reverse(*day));
+        summary.days = days
+            .into_iter()
+            .map(|(day, time)| (date(day), time))
+            .collect();
+        summary.workspaces = by_time(workspaces);
+        summary.filetypes = by_time(filetypes);
+
+        summary
+    }
+
+    /// Replaces the `{stats.*}` placeholders in the displayed text of
+    /// `activity` by the time spent today and in `current`'s workspace.
+    pub fn render(
+        &self,
+        activity: &mut Activity,
+        current: Current,
+        today: i64,
+    ) {
+        let mut fields = vec![&mut activity.details, &mut activity.state];
+        if let Some(assets) = &mut activity.assets {
+            fields.extend([&mut assets.large_text, &mut assets.small_text]);
+        }
+        let mut fields: Vec<&mut String> = fields
+            .into_iter()
+            .flatten()
+            .filter(|text| text.contains("{stats."))
+            .collect();
+        if fields.is_empty() {
+            return;
+        }
+
+        let workspace = current.workspace.unwrap_or_default();
+        let filetype = current.filetype.unwrap_or(UNKNOWN);
+        let sum = |matches: &dyn Fn(&Key) -> bool| {
+            self.totals
+                .iter()
+                .filter(|(key, _)| matches(key))
+                .map(|(_, time)| time)
+                .sum::<u64>()
+        };
+        let values = [
+            sum(&|key| key.day == today),
+            sum(&|key| key.day == today && key.workspace == workspace),
+            sum(&|key| key.day == today && key.filetype == filetype),
+            sum(&|key| key.workspace == workspace),
+        ];
+
+        for text in fields.iter_mut() {
+            for (placeholder, time) in PLACEHOLDERS.iter().zip(values) {
+                if text.contains(placeholder) {
+                    **text = text.replace(placeholder, &format_duration(time));
+                }
+            }
+        }
+    }
+}
+
+/// Whether `activity` shows any of the `{stats.*}` placeholders.
+pub fn uses_placeholders(activity: &Activity) -> bool {
+    let assets = activity.assets.as_ref();
+    [
+        activity.details.as_deref(),
+        activity.state.as_deref(),
+        assets.and_then(|a| a.large_text.as_deref()),
+        assets.and_then(|a| a.small_text.as_deref()),
+    ]
+    .into_iter()
+    .flatten()
+    .any(|text| PLACEHOLDERS.iter().any(|p| text.contains(p)))
+}
+
+/// The key of the large image, e.g. `rust` for `.../icons/rust.png`, used
+/// as the filetype of clients that do not report one.
+pub fn asset_key(activity: &Activity) -> Option<&str> {
+    let image = activity.assets.as_ref()?.large_image.as_deref()?;
+    let name = image.rsplit('/').next()?;
+    let key = name.split(['?', '#']).next()?;
+    let key = key.rsplit_once('.').map_or(key, |(stem, _)| stem);
+
+    (!key.is_empty()).then_some(key)
+}
+
+/// Formats milliseconds as hours and minutes, e.g. `2h 14m` or `5m`.
+pub fn format_duration(ms: u64) -> String {
+    let minutes = ms / 60_000;
+    match minutes / 60 {
+        0 => format!("{}m", minutes),
+        hours => format!("{}h {}m", hours, minutes % 60),
+    }
+}
+
+fn by_time(totals: HashMap<&str, u64>) -> Vec<(String, u64)> {
+    let mut totals: Vec<_> = totals
+        .into_iter()
+        .map(|(key, time)| (key.to_string(), time))
+        .collect();
+    totals.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    totals
+}
+
+/// Formats days since the Unix epoch as `YYYY-MM-DD`.
+fn date(day: i64) -> String {
+    // Howard Hinnant's civil_from_days
+    let z = day + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + i64::from(m <= 2);
+
+    format!("{:04}-{:02}-{:02}", y, m, d)
+}
+
+/// Parses `<day> <milliseconds> <filetype> <workspace>` lines, separated by
+/// tabs, ignoring blank lines, comments starting with `#` and anything
+/// else.
+fn parse(text: &str) -> HashMap<Key, u64> {
+    let mut totals = HashMap::new();
+    for line in text.lines() {
+        let mut fields = line.splitn(4, '\t');
+        let (Some(day), Some(time), Some(filetype), Some(workspace)) =
+            (fields.next(), fields.next(), fields.next(), fields.next())
+        else {
+            continue;
+        };
+        let (Ok(day), Ok(time)) = (day.parse(), time.parse::<u64>()) else {
+            continue;
+        };
+
+        let key = Key {
+            day,
+            workspace: workspace.to_string(),
+            filetype: filetype.to_string(),
+        };
+        *totals.entry(key).or_default() += time;
+    }
+
+    totals
+}
+
+fn format(totals: &HashMap<Key, u64>) -> String {
+    let mut lines: Vec<_> = totals.iter().collect();
+    lines.sort_unstable_by(|a, b| {
+        (a.0.day, &a.0.workspace, &a.0.filetype).cmp(&(
+            b.0.day,
+            &b.0.workspace,
+            &b.0.filetype,
+        ))
+    });
+
+    let mut text = String::from("# day\tmilliseconds\tfiletype\tworkspace\n");
+    for (key, time) in lines {
+        text.push_str(&format!(
+            "{}\t{}\t{}\t{}\n",
+            key.day, time, key.filetype, key.workspace
+        ));
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::presence::activity::ActivityAssets;
+
+    const DAY: u64 = 86400;
+
+    fn current<'a>(workspace: &'a str, filetype: &'a str) -> Current<'a> {
+        Current {
+            workspace: Some(workspace),
+            filetype: Some(filetype),
+        }
+    }
+
+    fn enabled() -> Stats {
+        let mut stats = Stats::default();
+        stats.configure(true, 0);
+        stats
+    }
+
+    #[test]
+    fn credits_ticks_with_a_cap() {
+        let mut stats = enabled();
+        let start = Instant::now();
+        let rust = Some(current("/work/acme", "rust"));
+
+        assert!(!stats.tick(start, 10 * DAY, rust));
+        assert!(stats.tick(start + Duration::from_secs(3), 10 * DAY, rust));
+        stats.tick(start + Duration::from_secs(600), 10 * DAY, rust);
+        assert!(!stats.tick(start + Duration::from_secs(601), 10 * DAY, None));
+        stats.tick(start + Duration::from_secs(603), 11 * DAY, rust);
+
+        let summary = stats.summary(&StatsFilter::default(), 11);
+        assert_eq!(summary.today, 2000);
+        assert_eq!(summary.total, 10_000);
+        assert_eq!(
+            summary.days,
+            [
+                ("1970-01-12".to_string(), 2000),
+                ("1970-01-11".to_string(), 8000)
+            ]
+        );
+
+        let mut disabled = Stats::default();
+        disabled.tick(start, 0, rust);
+        disabled.tick(start + Duration::from_secs(1), 0, rust);
+        assert_eq!(disabled.summary(&StatsFilter::default(), 0).total, 0);
+    }
+
+    #[test]
+    fn summarizes_with_filters() {
+        let mut stats = enabled();
+        for (day, workspace, filetype, time) in [
+            (20, "/work/acme", "rust", 3_600_000),
+            (20, "/work/acme", "lua", 600_000),
+            (19, "/home/me/dots", "lua", 1_200_000),
+            (10, "/work/acme", "rust", 60_000),
+        ] {
+            stats.totals.insert(
+                Key {
+                    day,
+                    workspace: workspace.to_string(),
+                    filetype: filetype.to_string(),
+                },
+                time,
+            );
+        }
+
+        let week = StatsFilter {
+            days: 7,
+            ..Default::default()
+        };
+        let summary = stats.summary(&week, 20);
+        assert_eq!(summary.today, 4_200_000);
+        assert_eq!(summary.total, 5_400_000);
+        assert_eq!(
+            summary.filetypes,
+            [
+                ("rust".to_string(), 3_600_000),
+                ("lua".to_string(), 1_800_000)
+            ]
+        );
+
+        let work = StatsFilter {
+            workspace: Some("/work/**".to_string()),
+            ..Default::default()
+        };
+        let summary = stats.summary(&work, 20);
+        assert_eq!(summary.total, 4_260_000);
+        assert_eq!(summary.workspaces, [("/work/acme".to_string(), 4_260_000)]);
+
+        let mut activity = Activity {
+            details: Some("{stats.today} today".to_string()),
+            state: Some("{stats.workspace_total} on acme".to_string()),
+            assets: Some(ActivityAssets {
+                large_image: None,
+                large_text: Some("{stats.filetype_today} of {x}".to_string()),
+                large_url: None,
+                small_image: None,
+                small_text: None,
+                small_url: None,
+            }),
+            ..Default::default()
+        };
+        assert!(uses_placeholders(&activity));
+        stats.render(&mut activity, current("/work/acme", "lua"), 20);
+        assert_eq!(activity.details.as_deref(), Some("1h 10m today"));
+        assert_eq!(activity.state.as_deref(), Some("1h 11m on acme"));
+        assert_eq!(
+            activity.assets.unwrap().large_text.as_deref(),
+            Some("10m of {x}")
+        );
+    }
+
+    #[test]
+    fn totals_round_trip() {
+        let mut totals = HashMap::new();
+        totals.insert(
+            Key {
+                day: 20000,
+                workspace: "/work/acme inc".to_string(),
+                filetype: "rust".to_string(),
+            },
+            90_000,
+        );
+        totals.insert(
+            Key {
+                day: 19999,
+                workspace: String::new(),
+                filetype: UNKNOWN.to_string(),
+            },
+            1,
+        );
+
+        let text = format(&totals);
+        assert_eq!(
+            text,
+            "# day\tmilliseconds\tfiletype\tworkspace\n\
+             19999\t1\tunknown\t\n\
+             20000\t90000\trust\t/work/acme inc\n"
+        );
+        assert_eq!(parse(&text), totals);
+        assert_eq!(parse("x\t1\trust\t/a\n20000\t5\n"), HashMap::new());
+    }
+
+    #[test]
+    fn formats_dates_and_durations() {
+        assert_eq!(date(0), "1970-01-01");
+        assert_eq!(date(20_745), "2026-10-19");
+        assert_eq!(date(-1), "1969-12-31");
+        assert_eq!(format_duration(59_999), "0m");
+        assert_eq!(format_duration(8_040_000), "2h 14m");
+    }
+
+    #[test]
+    fn extracts_asset_keys() {
+        let with_image = |image: &str| Activity {
+            assets: Some(ActivityAssets {
+                large_image: Some(image.to_string()),
+                large_text: None,
+                large_url: None,
+                small_image: None,
+                small_text: None,
+                small_url: None,
+            }),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            asset_key(&with_image("https://x.dev/icons/rust.png?v=2")),
+            Some("rust")
+        );
+        assert_eq!(asset_key(&with_image("lua")), Some("lua"));
+        assert_eq!(asset_key(&Activity::default()), None);
+    }
+}
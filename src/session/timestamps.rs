@@ -0,0 +1,241 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// Which sessions share one start timestamp when timestamps are shared.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TimestampScope {
+    /// Every session.
+    #[default]
+    Global,
+    /// Sessions opened on the same workspace.
+    Workspace,
+    /// Each session on its own.
+    Session,
+    /// Sessions editing the same file.
+    File,
+}
+
+impl TimestampScope {
+    /// Key of the timestamp a session uses. Sessions that did not report a
+    /// workspace or file fall back to the next narrower scope.
+    pub fn key(
+        self,
+        session: u32,
+        workspace: Option<&str>,
+        file: Option<&str>,
+    ) -> String {
+        match (self, workspace, file) {
+            (TimestampScope::Global, _, _) => "global".to_string(),
+            (TimestampScope::File, _, Some(file)) => format!("file:{}", file),
+            (
+                TimestampScope::Workspace | TimestampScope::File,
+                Some(workspace),
+                _,
+            ) => format!("workspace:{}", workspace),
+            _ => format!("session:{}", session),
+        }
+    }
+}
+
+impl FromStr for TimestampScope {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "global" => Ok(TimestampScope::Global),
+            "workspace" => Ok(TimestampScope::Workspace),
+            "session" => Ok(TimestampScope::Session),
+            "file" => Ok(TimestampScope::File),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Display for TimestampScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Global => write!(f, "global"),
+            Self::Workspace => write!(f, "workspace"),
+            Self::Session => write!(f, "session"),
+            Self::File => write!(f, "file"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    start: u64,
+    /// Sessions that used the timestamp; it is dropped once they are gone.
+    sessions: HashSet<u32>,
+}
+
+/// Start timestamps, in seconds, shared by the sessions of each scope key.
+#[derive(Debug, Default)]
+pub struct SharedTimestamps {
+    entries: HashMap<String, Entry>,
+}
+
+impl SharedTimestamps {
+    /// Returns the start timestamp of `key` for `session`, starting it at
+    /// `now` if no session uses it yet. A `start` reported by the session
+    /// replaces the shared one.
+    pub fn start(
+        &mut self,
+        key: String,
+        session: u32,
+        start: Option<u64>,
+        now: u64,
+    ) -> u64 {
+        let entry = self.entries.entry(key).or_insert_with(|| Entry {
+            start: start.unwrap_or(now),
+            sessions: HashSet::new(),
+        });
+        if let Some(start) = start {
+            entry.start = start;
+        }
+        entry.sessions.insert(session);
+
+        entry.start
+    }
+
+    pub fn get(&self, key: &str) -> Option<u64> {
+        self.entries.get(key).map(|e| e.start)
+    }
+
+    /// Start timestamps by key.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, u64)> {
+        self.entries.iter().map(|(key, e)| (key.as_str(), e.start))
+    }
+
+    /// Sets the start of `key` before any session uses it, as when restoring
+    /// it from a previous server.
+    pub fn restore(&mut self, key: String, start: u64) {
+        self.entries.entry(key).or_insert_with(|| Entry {
+            start,
+            sessions: HashSet::new(),
+        });
+    }
+
+    /// Forgets `session`, dropping the timestamps it used that no remaining
+    /// session uses. Returns the keys that were dropped.
+    pub fn release(&mut self, session: u32) -> Vec<String> {
+        let mut released = Vec::new();
+        self.entries.retain(|key, entry| {
+            let keep =
+                !entry.sessions.remove(&session) || !entry.sessions.is_empty();
+            if !keep {
+                released.push(key.clone());
+            }
+            keep
+        });
+
+        released
+    }
+
+    /// Moves every start by `secs`, as when leaving out the time the
+    /// machine was suspended for.
+    pub fn shift(&mut self, secs: i64) {
+        for entry in self.entries.values_mut() {
+            entry.start = entry.start.saturating_add_signed(secs);
+        }
+    }
+
+    /// Restarts every timestamp at `now`.
+    pub fn reset(&mut self, now: u64) {
+        for entry in self.entries.values_mut() {
+            entry.start = now;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_fall_back_to_narrower_scopes() {
+        let key = |scope: TimestampScope, ws, file| scope.key(7, ws, file);
+        assert_eq!(key(TimestampScope::Global, Some("/a"), None), "global");
+        assert_eq!(
+            key(TimestampScope::Workspace, Some("/a"), Some("/a/b.rs")),
+            "workspace:/a"
+        );
+        assert_eq!(key(TimestampScope::Workspace, None, None), "session:7");
+        assert_eq!(
+            key(TimestampScope::File, Some("/a"), Some("/a/b.rs")),
+            "file:/a/b.rs"
+        );
+        assert_eq!(key(TimestampScope::File, Some("/a"), None), "workspace:/a");
+        assert_eq!(key(TimestampScope::Session, Some("/a"), None), "session:7");
+    }
+
+    #[test]
+    fn sessions_of_a_scope_share_the_first_start() {
+        let mut timestamps = SharedTimestamps::default();
+        let key = || "workspace:/a".to_string();
+        assert_eq!(timestamps.start(key(), 1, None, 100), 100);
+        assert_eq!(timestamps.start(key(), 2, None, 200), 100);
+        assert_eq!(
+            timestamps.start("workspace:/b".to_string(), 3, None, 300),
+            300
+        );
+    }
+
+    #[test]
+    fn reported_start_replaces_shared_one() {
+        let mut timestamps = SharedTimestamps::default();
+        timestamps.start("global".to_string(), 1, None, 100);
+        assert_eq!(
+            timestamps.start("global".to_string(), 2, Some(50), 200),
+            50
+        );
+        assert_eq!(timestamps.get("global"), Some(50));
+    }
+
+    #[test]
+    fn timestamps_reset_when_last_session_leaves() {
+        let mut timestamps = SharedTimestamps::default();
+        let key = || "workspace:/a".to_string();
+        timestamps.start(key(), 1, None, 100);
+        timestamps.start(key(), 2, None, 200);
+
+        assert!(timestamps.release(1).is_empty());
+        assert_eq!(timestamps.get("workspace:/a"), Some(100));
+        assert_eq!(timestamps.release(2), vec![key()]);
+        assert_eq!(timestamps.start(key(), 3, None, 300), 300);
+    }
+
+    #[test]
+    fn restored_timestamps_wait_for_a_session() {
+        let mut timestamps = SharedTimestamps::default();
+        timestamps.restore("workspace:/a".to_string(), 50);
+        timestamps.start("workspace:/b".to_string(), 1, None, 100);
+
+        assert_eq!(timestamps.release(1), vec!["workspace:/b".to_string()]);
+        assert_eq!(
+            timestamps.start("workspace:/a".to_string(), 2, None, 200),
+            50
+        );
+    }
+
+    #[test]
+    fn shifting_and_resetting_keep_sessions() {
+        let mut timestamps = SharedTimestamps::default();
+        timestamps.start("workspace:/a".to_string(), 1, None, 100);
+        timestamps.start("workspace:/b".to_string(), 2, None, 200);
+
+        timestamps.shift(3600);
+        assert_eq!(timestamps.get("workspace:/a"), Some(3700));
+        timestamps.shift(-5000);
+        assert_eq!(timestamps.get("workspace:/b"), Some(0));
+
+        timestamps.reset(4000);
+        assert_eq!(timestamps.get("workspace:/a"), Some(4000));
+        assert_eq!(timestamps.release(1), vec!["workspace:/a".to_string()]);
+    }
+}
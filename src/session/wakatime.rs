@@ -0,0 +1,569 @@
+use std::collections::{HashMap, VecDeque};
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::debug;
+use crate::protocol::json::{self, Json};
+use crate::types::config::WakaTimeConfig;
+use crate::util::glob;
+use crate::util::http::{self, Url};
+
+/// Seconds after which a heartbeat is sent again for the same file, as
+/// WakaTime's own plugins do.
+const RESEND_INTERVAL: f64 = 120.0;
+/// Endpoint, relative to the API URL, that takes a batch of heartbeats.
+const BULK_PATH: &str = "/users/current/heartbeats.bulk";
+
+/// A WakaTime heartbeat: a moment at which a file was being worked on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Heartbeat {
+    pub entity: String,
+    pub project: Option<String>,
+    pub language: Option<String>,
+    /// Unix timestamp, in seconds.
+    pub time: f64,
+    pub is_write: bool,
+    pub user_agent: String,
+}
+
+/// Decides when a session's heartbeats are due.
+#[derive(Debug, Default)]
+struct Tracker {
+    last: Option<(String, f64, Option<SystemTime>)>,
+}
+
+/// Sends heartbeats to the configured sink from a thread of its own,
+/// batching them and keeping those that could not be sent for later.
+#[derive(Debug)]
+pub struct Exporter {
+    config: WakaTimeConfig,
+    tx: Option<Sender<Request>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+#[derive(Debug)]
+enum Request {
+    /// A session worked on a file, which is exported if a heartbeat is due.
+    Work(u32, Heartbeat),
+    /// A session is gone.
+    Forget(u32),
+}
+
+#[derive(Debug)]
+enum Sink {
+    File(PathBuf),
+    Http { url: Url, api_key: Option<String> },
+}
+
+impl Heartbeat {
+    /// The user agent WakaTime-compatible servers read the editor and
+    /// operating system from.
+    pub fn user_agent(editor: Option<&str>) -> String {
+        format!(
+            "wakatime/unset ({}-unknown) cord/{} {}-wakatime/{}",
+            std::env::consts::OS,
+            crate::cord::VERSION,
+            match editor {
+                Some("nvim") | None => "neovim",
+                Some(editor) => editor,
+            },
+            crate::cord::VERSION
+        )
+    }
+
+    /// The project a workspace stands for, its directory name.
+    pub fn project(workspace: &str) -> Option<String> {
+        Path::new(workspace)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+    }
+}
+
+impl Tracker {
+    /// Returns whether a heartbeat is due for working on `entity`, last
+    /// modified at `modified`, at `time`, and if so whether the file was
+    /// written to since the previous one.
+    fn next(
+        &mut self,
+        entity: &str,
+        modified: Option<SystemTime>,
+        time: f64,
+    ) -> Option<bool> {
+        let (is_write, due) = match &self.last {
+            Some((last, at, last_modified)) if last == entity => {
+                let is_write =
+                    last_modified.is_some() && modified != *last_modified;
+                (is_write, is_write || time - at >= RESEND_INTERVAL)
+            }
+            _ => (false, true),
+        };
+        if due {
+            self.last = Some((entity.to_string(), time, modified));
+        }
+
+        due.then_some(is_write)
+    }
+}
+
+impl Exporter {
+    /// Starts the thread heartbeats are sent from. Heartbeats that could
+    /// not be sent are kept at `queue`, if any. The thread takes over from
+    /// `previous` once it stopped, so that it reads what it left there.
+    pub fn new(
+        config: WakaTimeConfig,
+        queue: Option<PathBuf>,
+        previous: Option<Exporter>,
+    ) -> crate::Result<Self> {
+        let sink = match (&config.file, &config.api_url) {
+            (Some(file), _) => {
+                Sink::File(PathBuf::from(glob::expand_home(file.clone())))
+            }
+            (None, Some(api_url)) => Sink::Http {
+                url: Url::parse(api_url)?.join(BULK_PATH),
+                api_key: config.api_key.clone(),
+            },
+            (None, None) => return Err("No heartbeat sink configured".into()),
+        };
+
+        let (tx, rx) = mpsc::channel();
+        let mut queue = Queue {
+            heartbeats: VecDeque::new(),
+            path: queue,
+            batch_size: config.batch_size.max(1),
+            max_size: config.max_queue,
+            saved: 0,
+            stale: false,
+        };
+        let interval = Duration::from_millis(config.flush_interval);
+        let thread = thread::spawn(move || {
+            if let Some(previous) = previous {
+                previous.stop();
+            }
+            queue.load();
+            run(rx, sink, queue, interval)
+        });
+
+        Ok(Self {
+            config,
+            tx: Some(tx),
+            thread: Some(thread),
+        })
+    }
+
+    pub fn config(&self) -> &WakaTimeConfig {
+        &self.config
+    }
+
+    /// Queues `heartbeat`, for a file `session` is working on, without
+    /// waiting for it to be sent. It is dropped if none is due, which is
+    /// decided on the exporter's thread as it looks the file up.
+    pub fn send(&self, session: u32, heartbeat: Heartbeat) {
+        self.request(Request::Work(session, heartbeat));
+    }
+
+    /// Forgets the heartbeats sent for `session`.
+    pub fn forget(&self, session: u32) {
+        self.request(Request::Forget(session));
+    }
+
+    fn request(&self, request: Request) {
+        if let Some(tx) = &self.tx
+            && let Err(e) = tx.send(request)
+        {
+            debug!("Heartbeat exporter is gone, dropping {:?}", e.0);
+        }
+    }
+
+    /// Stops the thread once it saved the heartbeats left in its queue.
+    /// Dropping the exporter stops it too, without waiting.
+    pub fn stop(mut self) {
+        self.tx.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Heartbeats waiting to be sent, oldest first. Those that could not be
+/// sent are also kept in a file, so that they survive a restart.
+#[derive(Debug)]
+struct Queue {
+    heartbeats: VecDeque<Heartbeat>,
+    /// Where heartbeats that could not be sent are kept across restarts.
+    path: Option<PathBuf>,
+    /// Most heartbeats sent at once.
+    batch_size: usize,
+    /// Most heartbeats kept, the oldest being dropped first.
+    max_size: usize,
+    /// How many heartbeats at the front of the queue are in the file.
+    saved: usize,
+    /// Whether the file holds heartbeats that left the queue since.
+    stale: bool,
+}
+
+/// Sends batches of heartbeats once full or after `interval`. Sending is not
+/// retried before the next `interval` once it failed.
+fn run(
+    rx: Receiver<Request>,
+    sink: Sink,
+    mut queue: Queue,
+    interval: Duration,
+) {
+    let mut trackers: HashMap<u32, Tracker> = HashMap::new();
+    let mut last_flush = Instant::now();
+    let mut failing = false;
+
+    loop {
+        let wait = interval.saturating_sub(last_flush.elapsed());
+        match rx.recv_timeout(wait) {
+            Ok(Request::Work(session, mut heartbeat)) => {
+                let modified = fs::metadata(&heartbeat.entity)
+                    .and_then(|m| m.modified())
+                    .ok();
+                let due = trackers.entry(session).or_default().next(
+                    &heartbeat.entity,
+                    modified,
+                    heartbeat.time,
+                );
+                let Some(is_write) = due else {
+                    continue;
+                };
+
+                heartbeat.is_write = is_write;
+                queue.push(heartbeat);
+                if failing || queue.heartbeats.len() < queue.batch_size {
+                    continue;
+                }
+            }
+            Ok(Request::Forget(session)) => {
+                trackers.remove(&session);
+                continue;
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+
+        failing = !queue.flush(&sink);
+        if failing {
+            queue.save();
+        }
+        last_flush = Instant::now();
+    }
+
+    // Only a file is written to on exit, a server could hold up shutdown
+    if matches!(sink, Sink::File(_)) {
+        queue.flush(&sink);
+    }
+    queue.save();
+}
+
+impl Queue {
+    fn push(&mut self, heartbeat: Heartbeat) {
+        self.heartbeats.push_back(heartbeat);
+        self.truncate();
+    }
+
+    /// Adds the heartbeats saved by a previous server, or by the exporter
+    /// this one replaced, in front of the queue. They stay in the file until
+    /// they are sent.
+    fn load(&mut self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        match load(path) {
+            Ok(loaded) => {
+                self.saved += loaded.len();
+                for heartbeat in loaded.into_iter().rev() {
+                    self.heartbeats.push_front(heartbeat);
+                }
+                self.truncate();
+            }
+            Err(e) => debug!("Failed to load saved heartbeats: {}", e),
+        }
+    }
+
+    /// Writes every queued heartbeat to `sink`, a batch at a time. Returns
+    /// whether all of them were written.
+    fn flush(&mut self, sink: &Sink) -> bool {
+        while !self.heartbeats.is_empty() {
+            let len = self.heartbeats.len().min(self.batch_size);
+            let batch = self.heartbeats.range(..len).collect::<Vec<_>>();
+            if let Err(e) = sink.write(&batch) {
+                debug!("Failed to export {} heartbeat(s): {}", len, e);
+                return false;
+            }
+            self.heartbeats.drain(..len);
+            self.forget_saved(len);
+        }
+
+        if self.stale {
+            self.save();
+        }
+
+        true
+    }
+
+    /// Makes the file hold the queued heartbeats: those added since it was
+    /// last written are appended to it, or it is rewritten if some left the
+    /// queue since.
+    fn save(&mut self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let result = if self.stale {
+            rewrite(path, self.heartbeats.iter())
+        } else if self.saved < self.heartbeats.len() {
+            append(path, self.heartbeats.range(self.saved..))
+        } else {
+            Ok(())
+        };
+
+        match result {
+            Ok(()) => {
+                self.saved = self.heartbeats.len();
+                self.stale = false;
+            }
+            Err(e) => debug!(
+                "Failed to save {} heartbeat(s): {}",
+                self.heartbeats.len(),
+                e
+            ),
+        }
+    }
+
+    fn truncate(&mut self) {
+        if self.heartbeats.len() > self.max_size {
+            let dropped = self.heartbeats.len() - self.max_size;
+            self.heartbeats.drain(..dropped);
+            self.forget_saved(dropped);
+            debug!("Heartbeat queue is full, dropped {} heartbeat(s)", dropped);
+        }
+    }
+
+    /// Accounts for `count` heartbeats that left the front of the queue.
+    fn forget_saved(&mut self, count: usize) {
+        if self.saved > 0 {
+            self.saved = self.saved.saturating_sub(count);
+            self.stale = true;
+        }
+    }
+}
+
+impl Sink {
+    fn write(&self, batch: &[&Heartbeat]) -> crate::Result<()> {
+        match self {
+            Sink::File(path) => append(path, batch.iter().copied()),
+            Sink::Http { url, api_key } => {
+                let body = batch
+                    .iter()
+                    .map(|heartbeat| Json::serialize(*heartbeat))
+                    .collect::<crate::Result<Vec<_>>>()?
+                    .join(",");
+                let auth = api_key.as_deref().map(http::basic_auth);
+                let mut headers =
+                    vec![("User-Agent", batch[0].user_agent.as_str())];
+                if let Some(auth) = &auth {
+                    headers.push(("Authorization", auth));
+                }
+
+                match http::post_json(url, &headers, &format!("[{}]", body))? {
+                    200..300 => Ok(()),
+                    status => {
+                        Err(format!("Server answered with {}", status).into())
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Appends heartbeats to `path`, one JSON object per line.
+fn append<'a>(
+    path: &Path,
+    heartbeats: impl Iterator<Item = &'a Heartbeat>,
+) -> crate::Result<()> {
+    let mut text = String::new();
+    for heartbeat in heartbeats {
+        text.push_str(&Json::serialize(heartbeat)?);
+        text.push('\n');
+    }
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(text.as_bytes())?;
+
+    Ok(())
+}
+
+/// Replaces the heartbeats saved at `path`, removing the file if there are
+/// none.
+fn rewrite<'a>(
+    path: &Path,
+    mut heartbeats: impl ExactSizeIterator<Item = &'a Heartbeat>,
+) -> crate::Result<()> {
+    if heartbeats.len() == 0 {
+        return match fs::remove_file(path) {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        };
+    }
+
+    let tmp = path.with_extension("tmp");
+    let _ = fs::remove_file(&tmp);
+    append(&tmp, heartbeats.by_ref())?;
+    fs::rename(&tmp, path)?;
+
+    Ok(())
+}
+
+/// Reads the heartbeats saved at `path`.
+fn load(path: &Path) -> crate::Result<Vec<Heartbeat>> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    Ok(text.lines().filter_map(parse).collect())
+}
+
+fn parse(line: &str) -> Option<Heartbeat> {
+    let map = Json::deserialize(line).ok()?;
+    let text = |key| map.get(key).and_then(|v| v.as_string());
+
+    Some(Heartbeat {
+        entity: text("entity")?,
+        project: text("project"),
+        language: text("language"),
+        time: map.get("time")?.as_number()?,
+        is_write: map.get("is_write")?.as_bool()?,
+        user_agent: text("user_agent").unwrap_or_default(),
+    })
+}
+
+impl json::Serialize for Heartbeat {
+    fn serialize<'a>(
+        &'a self,
+        f: json::SerializeFn<'a>,
+        state: &mut json::SerializeState,
+    ) -> crate::Result<()> {
+        let text = |value: &'a Option<String>| {
+            value
+                .as_deref()
+                .map_or(json::ValueRef::Null, json::ValueRef::String)
+        };
+
+        f("entity", json::ValueRef::String(&self.entity), state)?;
+        f("type", json::ValueRef::String("file"), state)?;
+        f("category", json::ValueRef::String("coding"), state)?;
+        f("time", json::ValueRef::Number(self.time), state)?;
+        f("project", text(&self.project), state)?;
+        f("language", text(&self.language), state)?;
+        f("is_write", json::ValueRef::Boolean(self.is_write), state)?;
+        f(
+            "user_agent",
+            json::ValueRef::String(&self.user_agent),
+            state,
+        )?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn heartbeat(time: f64) -> Heartbeat {
+        Heartbeat {
+            entity: "/work/acme/src/main.rs".to_string(),
+            project: Heartbeat::project("/work/acme"),
+            language: Some("rust".to_string()),
+            time,
+            is_write: false,
+            user_agent: Heartbeat::user_agent(Some("neovim")),
+        }
+    }
+
+    #[test]
+    fn sends_heartbeats_when_due() {
+        let mut tracker = Tracker::default();
+        let saved = Some(SystemTime::UNIX_EPOCH);
+        let written = Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1));
+
+        assert_eq!(tracker.next("a.rs", saved, 0.0), Some(false));
+        assert_eq!(tracker.next("a.rs", saved, 60.0), None);
+        assert_eq!(tracker.next("a.rs", written, 70.0), Some(true));
+        assert_eq!(tracker.next("a.rs", written, 180.0), None);
+        assert_eq!(tracker.next("b.rs", None, 181.0), Some(false));
+        assert_eq!(tracker.next("b.rs", None, 301.0), Some(false));
+    }
+
+    #[test]
+    fn heartbeats_round_trip() {
+        let mut written = heartbeat(1_760_000_000.5);
+        written.is_write = true;
+        written.project = None;
+
+        for heartbeat in [heartbeat(1_760_000_000.25), written] {
+            let line = Json::serialize(&heartbeat).unwrap();
+            assert_eq!(parse(&line), Some(heartbeat));
+        }
+        assert_eq!(heartbeat(0.0).project.as_deref(), Some("acme"));
+    }
+
+    #[test]
+    fn keeps_unsent_heartbeats_saved() {
+        let dir = std::env::temp_dir()
+            .join(format!("cord-wakatime-{}", std::process::id()));
+        let path = dir.join("queue");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let queue = |max_size| Queue {
+            heartbeats: VecDeque::new(),
+            path: Some(path.clone()),
+            batch_size: 2,
+            max_size,
+            saved: 0,
+            stale: false,
+        };
+        let times = || {
+            load(&path)
+                .unwrap()
+                .iter()
+                .map(|h| h.time)
+                .collect::<Vec<_>>()
+        };
+
+        // A directory cannot be appended to
+        let mut failing = queue(10);
+        failing.push(heartbeat(1.0));
+        failing.push(heartbeat(2.0));
+        assert!(!failing.flush(&Sink::File(dir.clone())));
+        failing.save();
+        failing.push(heartbeat(3.0));
+        failing.save();
+        assert_eq!(times(), [1.0, 2.0, 3.0]);
+
+        let mut full = queue(2);
+        full.load();
+        full.save();
+        assert_eq!(times(), [2.0, 3.0]);
+
+        let mut sent = queue(10);
+        sent.load();
+        assert!(sent.flush(&Sink::File(dir.join("sent"))));
+        assert!(!path.exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,7 +1,15 @@
 #![allow(dead_code)]
 
-use crate::protocol::msgpack::Value;
+use crate::presence::activity::Activity;
+use crate::presence::privacy::Privacy;
 use crate::protocol::msgpack::deserialize::Deserialize;
+use crate::protocol::msgpack::{
+    Serialize, SerializeFn, SerializeState, Value, ValueRef,
+};
+use crate::session::pause::Window;
+use crate::session::policy::SelectionPolicy;
+use crate::session::timestamps::TimestampScope;
+use crate::util::http::Url;
 use crate::util::logger::LogLevel;
 use crate::{remove_field, remove_field_or_none};
 
@@ -10,11 +18,117 @@ pub struct PluginConfig {
     pub log_level: LogLevel,
     pub timestamp: TimestampConfig,
     pub advanced: AdvancedConfig,
+    pub client: ClientInfo,
+}
+
+/// Describes the editor instance behind a session. Every field is optional
+/// on the wire so that clients other than Neovim can fill in what they know.
+#[derive(Debug, Clone, Default)]
+pub struct ClientInfo {
+    pub name: Option<String>,
+    pub version: Option<String>,
+    pub plugin_version: Option<String>,
+    pub pid: Option<u32>,
+    /// When the process started, as the clock ticks since boot found in
+    /// `/proc/<pid>/stat`, so that a reused PID is not taken for it.
+    pub start_time: Option<u64>,
+    pub tty: Option<String>,
+    pub hostname: Option<String>,
+    pub multiplexer: Option<String>,
+    pub pane: Option<String>,
+}
+
+impl ClientInfo {
+    /// Short label used to tell sessions apart in logs, e.g. `nvim 1234 tmux:%3`.
+    pub fn label(&self) -> Option<String> {
+        let mut parts = Vec::new();
+        if let Some(name) = &self.name {
+            parts.push(name.clone());
+        }
+        if let Some(pid) = self.pid {
+            parts.push(pid.to_string());
+        }
+        match (&self.multiplexer, &self.pane) {
+            (Some(multiplexer), Some(pane)) => {
+                parts.push(format!("{}:{}", multiplexer, pane))
+            }
+            (Some(multiplexer), None) => parts.push(multiplexer.clone()),
+            _ => {}
+        }
+
+        (!parts.is_empty()).then(|| parts.join(" "))
+    }
+}
+
+impl Deserialize for ClientInfo {
+    fn deserialize<'a>(input: Value) -> crate::Result<Self> {
+        let mut input = input.take_map().ok_or("Invalid client info")?;
+
+        let name = remove_field_or_none!(input, "name", |v| v.take_string());
+        let version =
+            remove_field_or_none!(input, "version", |v| v.take_string());
+        let plugin_version =
+            remove_field_or_none!(input, "plugin_version", |v| v.take_string());
+        let pid = remove_field_or_none!(input, "pid", |v| v.as_uinteger())
+            .and_then(|pid| u32::try_from(pid).ok());
+        let start_time =
+            remove_field_or_none!(input, "start_time", |v| v.as_uinteger());
+        let tty = remove_field_or_none!(input, "tty", |v| v.take_string());
+        let hostname =
+            remove_field_or_none!(input, "hostname", |v| v.take_string());
+        let multiplexer =
+            remove_field_or_none!(input, "multiplexer", |v| v.take_string());
+        let pane = remove_field_or_none!(input, "pane", |v| v.take_string());
+
+        Ok(ClientInfo {
+            name,
+            version,
+            plugin_version,
+            pid,
+            start_time,
+            tty,
+            hostname,
+            multiplexer,
+            pane,
+        })
+    }
+}
+
+impl Serialize for ClientInfo {
+    fn serialize<'a>(
+        &'a self,
+        f: SerializeFn<'a>,
+        state: &mut SerializeState,
+    ) -> crate::Result<()> {
+        let fields = [
+            ("name", &self.name),
+            ("version", &self.version),
+            ("plugin_version", &self.plugin_version),
+            ("tty", &self.tty),
+            ("hostname", &self.hostname),
+            ("multiplexer", &self.multiplexer),
+            ("pane", &self.pane),
+        ];
+        for (key, value) in fields {
+            if let Some(value) = value {
+                f(key, ValueRef::Str(value), state)?;
+            }
+        }
+        if let Some(pid) = self.pid {
+            f("pid", ValueRef::UInteger(pid as u64), state)?;
+        }
+        if let Some(start_time) = self.start_time {
+            f("start_time", ValueRef::UInteger(start_time), state)?;
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone, Default)]
 pub struct TimestampConfig {
     pub shared: bool,
+    pub scope: TimestampScope,
 }
 
 impl Deserialize for TimestampConfig {
@@ -22,13 +136,21 @@ impl Deserialize for TimestampConfig {
         let mut input = input.take_map().ok_or("Invalid config")?;
 
         let shared = remove_field!(input, "shared", |v| v.as_bool());
+        let scope =
+            match remove_field_or_none!(input, "scope", |v| v.take_string()) {
+                Some(scope) => scope.parse().map_err(|_| {
+                    format!("Unknown timestamp scope: {}", scope)
+                })?,
+                None => TimestampScope::default(),
+            };
 
-        Ok(TimestampConfig { shared })
+        Ok(TimestampConfig { shared, scope })
     }
 }
 
 #[derive(Debug, Clone, Default)]
 pub struct AdvancedConfig {
+    pub server: AdvancedServerConfig,
     pub discord: AdvancedDiscordConfig,
 }
 
@@ -36,29 +158,572 @@ impl Deserialize for AdvancedConfig {
     fn deserialize<'a>(input: Value) -> crate::Result<Self> {
         let mut input = input.take_map().ok_or("Invalid config")?;
 
-        let discord = remove_field!(input, "discord", |v| {
-            AdvancedDiscordConfig::deserialize(v).ok()
+        // Errors are reported so that invalid privacy rules or handshake
+        // fields are not ignored
+        let server = match input.remove("server") {
+            Some(v) => AdvancedServerConfig::deserialize(v)?,
+            None => AdvancedServerConfig::default(),
+        };
+        let discord = match input.remove("discord") {
+            Some(v) => AdvancedDiscordConfig::deserialize(v)?,
+            None => AdvancedDiscordConfig::default(),
+        };
+
+        Ok(AdvancedConfig { server, discord })
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AdvancedServerConfig {
+    pub heartbeat: HeartbeatConfig,
+    pub selection: SelectionConfig,
+    pub away: AwayConfig,
+    pub resume: ResumeConfig,
+    pub dnd: DndConfig,
+    pub privacy: Privacy,
+    pub confirm_share: ConfirmShareConfig,
+    pub history: HistoryConfig,
+    pub stats: StatsConfig,
+    pub wakatime: WakaTimeConfig,
+}
+
+impl Deserialize for AdvancedServerConfig {
+    fn deserialize<'a>(input: Value) -> crate::Result<Self> {
+        let mut input = input.take_map().ok_or("Invalid config")?;
+
+        let heartbeat = remove_field_or_none!(input, "heartbeat", |v| {
+            HeartbeatConfig::deserialize(v).ok()
+        })
+        .unwrap_or_default();
+        let selection = remove_field_or_none!(input, "selection", |v| {
+            SelectionConfig::deserialize(v).ok()
+        })
+        .unwrap_or_default();
+        let away = remove_field_or_none!(input, "away", |v| {
+            AwayConfig::deserialize(v).ok()
+        })
+        .unwrap_or_default();
+        let resume = remove_field_or_none!(input, "resume", |v| {
+            ResumeConfig::deserialize(v).ok()
+        })
+        .unwrap_or_default();
+        let dnd = remove_field_or_none!(input, "dnd", |v| {
+            DndConfig::deserialize(v).ok()
+        })
+        .unwrap_or_default();
+        let privacy = match input.remove("privacy") {
+            Some(v) => Privacy::deserialize(v)?,
+            None => Privacy::default(),
+        };
+
+        let confirm_share =
+            remove_field_or_none!(input, "confirm_share", |v| {
+                ConfirmShareConfig::deserialize(v).ok()
+            })
+            .unwrap_or_default();
+        let history = remove_field_or_none!(input, "history", |v| {
+            HistoryConfig::deserialize(v).ok()
+        })
+        .unwrap_or_default();
+        let stats = remove_field_or_none!(input, "stats", |v| {
+            StatsConfig::deserialize(v).ok()
+        })
+        .unwrap_or_default();
+        let wakatime = match input.remove("wakatime") {
+            Some(v) => WakaTimeConfig::deserialize(v)?,
+            None => WakaTimeConfig::default(),
+        };
+
+        Ok(AdvancedServerConfig {
+            heartbeat,
+            selection,
+            away,
+            resume,
+            dnd,
+            privacy,
+            confirm_share,
+            history,
+            stats,
+            wakatime,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SelectionConfig {
+    pub policy: SelectionPolicy,
+    /// Milliseconds a session must stay focused before it takes over.
+    pub focus_grace: u64,
+    pub rotation: RotationConfig,
+}
+
+impl Default for SelectionConfig {
+    fn default() -> Self {
+        Self {
+            policy: SelectionPolicy::default(),
+            focus_grace: 1000,
+            rotation: RotationConfig::default(),
+        }
+    }
+}
+
+impl Deserialize for SelectionConfig {
+    fn deserialize<'a>(input: Value) -> crate::Result<Self> {
+        let mut input = input.take_map().ok_or("Invalid config")?;
+
+        let focus_grace = remove_field_or_none!(input, "focus_grace", |v| v
+            .as_uinteger())
+        .unwrap_or(1000);
+        let rotation = remove_field_or_none!(input, "rotation", |v| {
+            RotationConfig::deserialize(v).ok()
+        })
+        .unwrap_or_default();
+        let policy = SelectionPolicy::deserialize(Value::Map(input))?;
+
+        Ok(SelectionConfig {
+            policy,
+            focus_grace,
+            rotation,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RotationConfig {
+    pub enabled: bool,
+    /// Milliseconds each active session is displayed for.
+    pub interval: u64,
+}
+
+impl Default for RotationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval: 30000,
+        }
+    }
+}
+
+impl Deserialize for RotationConfig {
+    fn deserialize<'a>(input: Value) -> crate::Result<Self> {
+        let mut input = input.take_map().ok_or("Invalid config")?;
+
+        let enabled = remove_field!(input, "enabled", |v| v.as_bool());
+        let interval = remove_field!(input, "interval", |v| v.as_uinteger());
+
+        Ok(RotationConfig { enabled, interval })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AwayConfig {
+    pub enabled: bool,
+    /// Milliseconds every session must go without events before the
+    /// presence is replaced.
+    pub timeout: u64,
+    /// Shown while away, the presence is cleared if there is none.
+    pub activity: Option<Activity>,
+}
+
+impl Default for AwayConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            timeout: 900000,
+            activity: None,
+        }
+    }
+}
+
+impl Deserialize for AwayConfig {
+    fn deserialize<'a>(input: Value) -> crate::Result<Self> {
+        let mut input = input.take_map().ok_or("Invalid config")?;
+
+        let enabled = remove_field!(input, "enabled", |v| v.as_bool());
+        let timeout = remove_field!(input, "timeout", |v| v.as_uinteger());
+        let activity = remove_field_or_none!(input, "activity", |v| {
+            Activity::deserialize(v).ok()
+        });
+
+        Ok(AwayConfig {
+            enabled,
+            timeout,
+            activity,
+        })
+    }
+}
+
+/// What happens to shared timestamps after a suspend or clock jump.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ResumeTimestamps {
+    /// Keep them, so that they include the time the machine was suspended.
+    #[default]
+    Keep,
+    /// Move them by the time the clock jumped.
+    Shift,
+    /// Restart them on resume.
+    Reset,
+}
+
+#[derive(Debug, Clone)]
+pub struct ResumeConfig {
+    /// Whether to reconnect to Discord, whose connection is often dead after
+    /// a suspend.
+    pub reconnect: bool,
+    pub timestamps: ResumeTimestamps,
+}
+
+impl Default for ResumeConfig {
+    fn default() -> Self {
+        Self {
+            reconnect: true,
+            timestamps: ResumeTimestamps::default(),
+        }
+    }
+}
+
+impl Deserialize for ResumeConfig {
+    fn deserialize<'a>(input: Value) -> crate::Result<Self> {
+        let mut input = input.take_map().ok_or("Invalid config")?;
+
+        let reconnect = remove_field!(input, "reconnect", |v| v.as_bool());
+        let timestamps = remove_field!(input, "timestamps", |v| {
+            v.as_str().and_then(|s| match s {
+                "keep" => Some(ResumeTimestamps::Keep),
+                "shift" => Some(ResumeTimestamps::Shift),
+                "reset" => Some(ResumeTimestamps::Reset),
+                _ => None,
+            })
         });
 
-        Ok(AdvancedConfig { discord })
+        Ok(ResumeConfig {
+            reconnect,
+            timestamps,
+        })
     }
 }
 
 #[derive(Debug, Clone, Default)]
+pub struct DndConfig {
+    pub enabled: bool,
+    /// Minutes to add to UTC to get the local time the rules are in.
+    pub utc_offset: i64,
+    /// Windows during which the presence is paused.
+    pub rules: Vec<Window>,
+}
+
+impl Deserialize for DndConfig {
+    fn deserialize<'a>(input: Value) -> crate::Result<Self> {
+        let mut input = input.take_map().ok_or("Invalid config")?;
+
+        let enabled = remove_field!(input, "enabled", |v| v.as_bool());
+        let utc_offset = remove_field_or_none!(input, "utc_offset", |v| v
+            .as_integer())
+        .unwrap_or_default();
+        let rules = remove_field_or_none!(input, "rules", |v| v.take_array())
+            .unwrap_or_default()
+            .into_iter()
+            .map(Window::deserialize)
+            .collect::<crate::Result<_>>()?;
+
+        Ok(DndConfig {
+            enabled,
+            utc_offset,
+            rules,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ConfirmShareConfig {
+    /// Whether workspaces are held back until a session allows them.
+    pub enabled: bool,
+}
+
+impl Deserialize for ConfirmShareConfig {
+    fn deserialize<'a>(input: Value) -> crate::Result<Self> {
+        let mut input = input.take_map().ok_or("Invalid config")?;
+
+        let enabled = remove_field!(input, "enabled", |v| v.as_bool());
+
+        Ok(ConfirmShareConfig { enabled })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HistoryConfig {
+    /// Whether the activities sent to Discord are logged.
+    pub enabled: bool,
+    /// Bytes the log may grow to before it is rotated.
+    pub max_size: u64,
+    /// Rotated logs kept besides the current one.
+    pub max_files: usize,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_size: 1024 * 1024,
+            max_files: 3,
+        }
+    }
+}
+
+impl Deserialize for HistoryConfig {
+    fn deserialize<'a>(input: Value) -> crate::Result<Self> {
+        let mut input = input.take_map().ok_or("Invalid config")?;
+
+        let enabled = remove_field!(input, "enabled", |v| v.as_bool());
+        let max_size = remove_field!(input, "max_size", |v| v.as_uinteger());
+        let max_files = remove_field!(input, "max_files", |v| v
+            .as_uinteger()
+            .map(|n| n as usize));
+
+        Ok(HistoryConfig {
+            enabled,
+            max_size,
+            max_files,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct StatsConfig {
+    /// Whether active time is tracked.
+    pub enabled: bool,
+    /// Minutes to add to UTC to get the local time days are counted in.
+    pub utc_offset: i64,
+}
+
+impl Deserialize for StatsConfig {
+    fn deserialize<'a>(input: Value) -> crate::Result<Self> {
+        let mut input = input.take_map().ok_or("Invalid config")?;
+
+        let enabled = remove_field!(input, "enabled", |v| v.as_bool());
+        let utc_offset = remove_field_or_none!(input, "utc_offset", |v| v
+            .as_integer())
+        .unwrap_or_default();
+
+        Ok(StatsConfig {
+            enabled,
+            utc_offset,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WakaTimeConfig {
+    /// Whether heartbeats are exported.
+    pub enabled: bool,
+    /// Base URL of a WakaTime-compatible API, e.g.
+    /// `http://localhost:3000/api` for a local Wakapi.
+    pub api_url: Option<String>,
+    pub api_key: Option<String>,
+    /// File heartbeats are appended to instead of being sent to the API.
+    pub file: Option<String>,
+    /// Most heartbeats sent at once.
+    pub batch_size: usize,
+    /// Milliseconds after which queued heartbeats are sent anyway.
+    pub flush_interval: u64,
+    /// Most heartbeats kept while they cannot be sent.
+    pub max_queue: usize,
+}
+
+impl Default for WakaTimeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            api_url: None,
+            api_key: None,
+            file: None,
+            batch_size: 25,
+            flush_interval: 30000,
+            max_queue: 1000,
+        }
+    }
+}
+
+impl Deserialize for WakaTimeConfig {
+    fn deserialize<'a>(input: Value) -> crate::Result<Self> {
+        let mut input = input.take_map().ok_or("Invalid config")?;
+
+        let enabled = remove_field!(input, "enabled", |v| v.as_bool());
+        let api_url =
+            remove_field_or_none!(input, "api_url", |v| v.take_string());
+        let api_key =
+            remove_field_or_none!(input, "api_key", |v| v.take_string());
+        let file = remove_field_or_none!(input, "file", |v| v.take_string());
+        let batch_size = remove_field!(input, "batch_size", |v| v
+            .as_uinteger()
+            .map(|n| n as usize));
+        let flush_interval =
+            remove_field!(input, "flush_interval", |v| v.as_uinteger());
+        let max_queue = remove_field!(input, "max_queue", |v| v
+            .as_uinteger()
+            .map(|n| n as usize));
+
+        // Errors are reported so that heartbeats are not silently dropped
+        if enabled && file.is_none() {
+            let api_url = api_url.as_deref().ok_or(
+                "Heartbeat export needs either 'api_url' or 'file' to be set",
+            )?;
+            Url::parse(api_url)?;
+        }
+
+        Ok(WakaTimeConfig {
+            enabled,
+            api_url,
+            api_key,
+            file,
+            batch_size,
+            flush_interval,
+            max_queue,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HeartbeatConfig {
+    pub enabled: bool,
+    /// Milliseconds without a heartbeat after which the session is dropped.
+    pub timeout: u64,
+}
+
+impl Default for HeartbeatConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            timeout: 30000,
+        }
+    }
+}
+
+impl Deserialize for HeartbeatConfig {
+    fn deserialize<'a>(input: Value) -> crate::Result<Self> {
+        let mut input = input.take_map().ok_or("Invalid config")?;
+
+        let enabled = remove_field!(input, "enabled", |v| v.as_bool());
+        let timeout = remove_field!(input, "timeout", |v| v.as_uinteger());
+
+        Ok(HeartbeatConfig { enabled, timeout })
+    }
+}
+
+/// Milliseconds a write to Discord may take before the connection is
+/// considered lost.
+pub const DEFAULT_WRITE_TIMEOUT: u64 = 5000;
+
+#[derive(Debug, Clone)]
 pub struct AdvancedDiscordConfig {
     pub pipe_paths: Vec<String>,
+    pub handshake: HandshakeConfig,
+    pub write_timeout: u64,
     pub sync: SyncConfig,
 }
 
+impl Default for AdvancedDiscordConfig {
+    fn default() -> Self {
+        Self {
+            pipe_paths: Vec::new(),
+            handshake: HandshakeConfig::default(),
+            write_timeout: DEFAULT_WRITE_TIMEOUT,
+            sync: SyncConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HandshakeConfig {
+    pub version: u32,
+    pub fields: Vec<(String, HandshakeValue)>,
+    pub timeout: u64,
+}
+
+impl Default for HandshakeConfig {
+    fn default() -> Self {
+        Self {
+            version: 1,
+            fields: Vec::new(),
+            timeout: 10000,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
+pub enum HandshakeValue {
+    String(String),
+    Number(f64),
+    Boolean(bool),
+}
+
+impl Deserialize for HandshakeValue {
+    fn deserialize<'a>(input: Value) -> crate::Result<Self> {
+        Ok(match input {
+            Value::String(s) => HandshakeValue::String(s),
+            Value::Boolean(b) => HandshakeValue::Boolean(b),
+            Value::Integer(n) => HandshakeValue::Number(n as f64),
+            Value::UInteger(n) => HandshakeValue::Number(n as f64),
+            Value::Float(n) => HandshakeValue::Number(n),
+            _ => return Err("Invalid handshake field value".into()),
+        })
+    }
+}
+
+impl Deserialize for HandshakeConfig {
+    fn deserialize<'a>(input: Value) -> crate::Result<Self> {
+        let mut input = input.take_map().ok_or("Invalid config")?;
+        let default = HandshakeConfig::default();
+
+        let version = match input.remove("version") {
+            Some(v) => v
+                .as_uinteger()
+                .and_then(|v| u32::try_from(v).ok())
+                .ok_or("Invalid handshake version")?,
+            None => default.version,
+        };
+        let mut fields = match input.remove("fields") {
+            Some(v) => v
+                .take_map()
+                .ok_or("Invalid handshake fields")?
+                .into_iter()
+                .map(|(key, value)| match HandshakeValue::deserialize(value) {
+                    Ok(value) => Ok((key, value)),
+                    Err(_) => Err(format!(
+                        "Invalid value for handshake field '{}'",
+                        key
+                    )),
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            None => default.fields,
+        };
+        fields.sort_by(|a, b| a.0.cmp(&b.0));
+        let timeout = match input.remove("timeout") {
+            Some(v) => v.as_uinteger().ok_or("Invalid handshake timeout")?,
+            None => default.timeout,
+        };
+
+        Ok(HandshakeConfig {
+            version,
+            fields,
+            timeout,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
 pub enum SyncMode {
+    #[default]
     Periodic,
     Defer,
 }
 
-impl Default for SyncMode {
-    fn default() -> Self {
-        Self::Periodic
+impl std::fmt::Display for SyncMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Periodic => write!(f, "periodic"),
+            Self::Defer => write!(f, "defer"),
+        }
     }
 }
 
@@ -83,6 +748,26 @@ impl Default for SyncConfig {
     }
 }
 
+impl Serialize for SyncConfig {
+    fn serialize<'a>(
+        &'a self,
+        f: SerializeFn<'a>,
+        state: &mut SerializeState,
+    ) -> crate::Result<()> {
+        f("enabled", ValueRef::Boolean(self.enabled), state)?;
+        f("mode", ValueRef::String(self.mode.to_string()), state)?;
+        f("interval", ValueRef::UInteger(self.interval), state)?;
+        f(
+            "reset_on_update",
+            ValueRef::Boolean(self.reset_on_update),
+            state,
+        )?;
+        f("pad", ValueRef::Boolean(self.pad), state)?;
+
+        Ok(())
+    }
+}
+
 impl Deserialize for SyncConfig {
     fn deserialize<'a>(input: Value) -> crate::Result<Self> {
         let mut input = input.take_map().ok_or("Invalid config")?;
@@ -127,12 +812,26 @@ impl Deserialize for AdvancedDiscordConfig {
         })
         .unwrap_or_default();
 
+        let handshake = match input.remove("handshake") {
+            Some(v) => HandshakeConfig::deserialize(v)?,
+            None => HandshakeConfig::default(),
+        };
+
+        let write_timeout =
+            remove_field_or_none!(input, "write_timeout", |v| v.as_uinteger())
+                .unwrap_or(DEFAULT_WRITE_TIMEOUT);
+
         let sync = remove_field_or_none!(input, "sync", |v| {
             SyncConfig::deserialize(v).ok()
         })
         .unwrap_or_default();
 
-        Ok(AdvancedDiscordConfig { pipe_paths, sync })
+        Ok(AdvancedDiscordConfig {
+            pipe_paths,
+            handshake,
+            write_timeout,
+            sync,
+        })
     }
 }
 
@@ -147,8 +846,12 @@ impl Deserialize for PluginConfig {
             TimestampConfig::deserialize(v).ok()
         })
         .unwrap_or_default();
-        let advanced = remove_field_or_none!(input, "advanced", |v| {
-            AdvancedConfig::deserialize(v).ok()
+        let advanced = match input.remove("advanced") {
+            Some(v) => AdvancedConfig::deserialize(v)?,
+            None => AdvancedConfig::default(),
+        };
+        let client = remove_field_or_none!(input, "client", |v| {
+            ClientInfo::deserialize(v).ok()
         })
         .unwrap_or_default();
 
@@ -156,6 +859,7 @@ impl Deserialize for PluginConfig {
             log_level,
             timestamp,
             advanced,
+            client,
         })
     }
 }
@@ -0,0 +1,144 @@
+#[cfg(not(target_os = "windows"))]
+use std::sync::OnceLock;
+use std::time::Duration;
+#[cfg(not(target_os = "windows"))]
+use std::time::Instant;
+
+#[cfg(target_os = "windows")]
+use crate::ipc::bindings::QueryUnbiasedInterruptTime;
+
+/// A jump of the wall clock relative to the monotonic clock, as seen after
+/// the machine resumes from suspend or the system time is changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockJump {
+    /// Seconds the wall clock moved on top of the monotonic clock; negative
+    /// if it was set back.
+    pub skipped: i64,
+}
+
+/// Time the machine has been awake since the first call, which does not
+/// advance while it is suspended. `Instant` stops during a suspend on Linux
+/// and macOS but keeps counting on Windows, where the unbiased interrupt
+/// time is used instead.
+#[cfg(not(target_os = "windows"))]
+pub fn awake() -> Duration {
+    static START: OnceLock<Instant> = OnceLock::new();
+    START.get_or_init(Instant::now).elapsed()
+}
+
+/// Time the machine has been awake since it booted, which does not advance
+/// while it is suspended.
+#[cfg(target_os = "windows")]
+pub fn awake() -> Duration {
+    let mut time = 0u64;
+    // Counted in units of 100ns
+    unsafe { QueryUnbiasedInterruptTime(&mut time) };
+    Duration::from_nanos(time.saturating_mul(100))
+}
+
+/// Compares how far the awake time and the wall clock moved between two
+/// ticks. The awake time does not advance while the machine is suspended,
+/// so the wall clock runs ahead of it after a resume.
+#[derive(Debug)]
+pub struct ClockWatch {
+    threshold: Duration,
+    /// Awake time and wall clock, in Unix time, at the last tick.
+    last: Option<(Duration, Duration)>,
+}
+
+impl ClockWatch {
+    /// Reports jumps of at least `threshold`.
+    pub fn new(threshold: Duration) -> Self {
+        Self {
+            threshold,
+            last: None,
+        }
+    }
+
+    /// Records a tick at the given awake time, as returned by `awake`, and
+    /// wall clock time, returning the jump since the last tick, if any.
+    pub fn tick(&mut self, at: Duration, wall: Duration) -> Option<ClockJump> {
+        let (last_at, last_wall) = self.last.replace((at, wall))?;
+        let elapsed = at.saturating_sub(last_at);
+
+        let (skipped, ahead) = if wall >= last_wall {
+            let wall_elapsed = wall - last_wall;
+            match wall_elapsed.checked_sub(elapsed) {
+                Some(skipped) => (skipped, true),
+                None => (elapsed - wall_elapsed, false),
+            }
+        } else {
+            (elapsed + (last_wall - wall), false)
+        };
+        if skipped < self.threshold {
+            return None;
+        }
+
+        let secs = skipped.as_secs() as i64;
+        Some(ClockJump {
+            skipped: if ahead { secs } else { -secs },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const THRESHOLD: Duration = Duration::from_secs(30);
+
+    fn secs(secs: u64) -> Duration {
+        Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn first_tick_reports_nothing() {
+        let mut watch = ClockWatch::new(THRESHOLD);
+        assert_eq!(watch.tick(awake(), secs(1000)), None);
+    }
+
+    #[test]
+    fn clocks_moving_together_report_nothing() {
+        let mut watch = ClockWatch::new(THRESHOLD);
+        let start = awake();
+        watch.tick(start, secs(1000));
+
+        assert_eq!(watch.tick(start + secs(1), secs(1001)), None);
+        assert_eq!(watch.tick(start + secs(2), secs(1010)), None);
+    }
+
+    #[test]
+    fn suspend_is_reported_as_skipped_time() {
+        let mut watch = ClockWatch::new(THRESHOLD);
+        let start = awake();
+        watch.tick(start, secs(1000));
+
+        assert_eq!(
+            watch.tick(start + secs(1), secs(4601)),
+            Some(ClockJump { skipped: 3600 })
+        );
+        assert_eq!(watch.tick(start + secs(2), secs(4602)), None);
+    }
+
+    #[test]
+    fn awake_time_advances() {
+        let start = awake();
+        std::thread::sleep(Duration::from_millis(20));
+        let elapsed = awake() - start;
+
+        assert!(elapsed >= Duration::from_millis(15), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_secs(5), "{:?}", elapsed);
+    }
+
+    #[test]
+    fn clock_set_back_is_reported() {
+        let mut watch = ClockWatch::new(THRESHOLD);
+        let start = awake();
+        watch.tick(start, secs(1000));
+
+        assert_eq!(
+            watch.tick(start + secs(1), secs(901)),
+            Some(ClockJump { skipped: -100 })
+        );
+    }
+}
@@ -0,0 +1,63 @@
+/// Matches `text` against a glob `pattern`. `*` matches within a path
+/// segment, `**` matches across segments and `?` matches a single character.
+pub fn matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    matches_from(&pattern, &text)
+}
+
+/// Replaces a leading `~` with the home directory.
+pub fn expand_home(pattern: String) -> String {
+    let home = std::env::var("HOME")
+        .or_else(|_| std::env::var("USERPROFILE"))
+        .ok();
+    match (pattern.strip_prefix('~'), home) {
+        (Some(rest), Some(home)) => format!("{}{}", home, rest),
+        _ => pattern,
+    }
+}
+
+fn matches_from(pattern: &[char], text: &[char]) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some('*') if pattern.get(1) == Some(&'*') => {
+            let rest = &pattern[2..];
+            (0..=text.len()).any(|i| matches_from(rest, &text[i..]))
+        }
+        Some('*') => {
+            let rest = &pattern[1..];
+            for i in 0..=text.len() {
+                if matches_from(rest, &text[i..]) {
+                    return true;
+                }
+                if text.get(i) == Some(&'/') {
+                    break;
+                }
+            }
+            false
+        }
+        Some('?') => {
+            text.first().is_some_and(|c| *c != '/')
+                && matches_from(&pattern[1..], &text[1..])
+        }
+        Some(c) => {
+            text.first() == Some(c) && matches_from(&pattern[1..], &text[1..])
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_matching() {
+        assert!(matches("/home/*/src", "/home/me/src"));
+        assert!(!matches("/home/*", "/home/me/src"));
+        assert!(matches("/home/**", "/home/me/src"));
+        assert!(matches("/home/**/src", "/home/me/a/b/src"));
+        assert!(matches("/tmp/?", "/tmp/a"));
+        assert!(!matches("/tmp/?", "/tmp/ab"));
+    }
+}
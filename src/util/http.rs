@@ -0,0 +1,184 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+/// How long connecting, sending the request and reading the answer may
+/// take each.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A plain `http://` URL. TLS is not supported, so this is only meant for
+/// servers on the local machine or network.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Url {
+    host: String,
+    port: u16,
+    path: String,
+}
+
+impl Url {
+    pub fn parse(url: &str) -> crate::Result<Self> {
+        let rest = url.strip_prefix("http://").ok_or_else(|| {
+            format!("Unsupported URL '{}': only http:// is supported", url)
+        })?;
+        let (authority, path) = match rest.find('/') {
+            Some(i) => rest.split_at(i),
+            None => (rest, "/"),
+        };
+        // IPv6 addresses are enclosed in brackets, e.g. `[::1]:8080`
+        let port_start = match authority.rfind(']') {
+            Some(end) => authority[end..].find(':').map(|i| end + i),
+            None => authority.rfind(':'),
+        };
+        let (host, port) = match port_start {
+            Some(i) => (
+                &authority[..i],
+                authority[i + 1..]
+                    .parse()
+                    .map_err(|_| format!("Invalid port in URL '{}'", url))?,
+            ),
+            None => (authority, 80),
+        };
+        if host.is_empty() {
+            return Err(format!("Missing host in URL '{}'", url).into());
+        }
+
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+        })
+    }
+
+    /// The URL with `path` appended to its own.
+    pub fn join(&self, path: &str) -> Self {
+        Self {
+            path: format!("{}{}", self.path.trim_end_matches('/'), path),
+            ..self.clone()
+        }
+    }
+}
+
+/// Posts `body` as JSON to `url` and returns the status code of the answer.
+pub fn post_json(
+    url: &Url,
+    headers: &[(&str, &str)],
+    body: &str,
+) -> crate::Result<u16> {
+    let addr = (url.host.trim_matches(['[', ']']), url.port)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| format!("Could not resolve '{}'", url.host))?;
+    let mut stream = TcpStream::connect_timeout(&addr, TIMEOUT)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+
+    let mut request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}:{}\r\nContent-Type: application/json\r\n\
+         Content-Length: {}\r\nConnection: close\r\n",
+        url.path,
+        url.host,
+        url.port,
+        body.len()
+    );
+    for (name, value) in headers {
+        request.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    request.push_str("\r\n");
+    request.push_str(body);
+    stream.write_all(request.as_bytes())?;
+
+    let mut status = String::new();
+    BufReader::new(stream).read_line(&mut status)?;
+
+    status
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| {
+            format!("Invalid HTTP response: {}", status.trim()).into()
+        })
+}
+
+/// The value of an `Authorization` header for HTTP basic authentication.
+pub fn basic_auth(credentials: &str) -> String {
+    const ALPHABET: &[u8] =
+        b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::from("Basic ");
+    for chunk in credentials.as_bytes().chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded
+                    .push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::net::TcpListener;
+
+    #[test]
+    fn parses_urls() {
+        let url = Url::parse("http://localhost:3000/api").unwrap();
+        assert_eq!(
+            url.join("/users/current/heartbeats.bulk"),
+            Url {
+                host: "localhost".to_string(),
+                port: 3000,
+                path: "/api/users/current/heartbeats.bulk".to_string(),
+            }
+        );
+        assert_eq!(Url::parse("http://[::1]").unwrap().port, 80);
+        assert_eq!(Url::parse("http://[::1]:8080/").unwrap().host, "[::1]");
+        assert!(Url::parse("https://wakatime.com/api").is_err());
+        assert!(Url::parse("http://host:port/").is_err());
+    }
+
+    #[test]
+    fn encodes_basic_auth() {
+        assert_eq!(basic_auth(""), "Basic ");
+        assert_eq!(basic_auth("a"), "Basic YQ==");
+        assert_eq!(basic_auth("ab"), "Basic YWI=");
+        assert_eq!(basic_auth("waka_0123-abcd"), "Basic d2FrYV8wMTIzLWFiY2Q=");
+    }
+
+    #[test]
+    fn posts_json() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = Url::parse(&format!(
+            "http://{}/api",
+            listener.local_addr().unwrap()
+        ))
+        .unwrap();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0; 1024];
+            while !request.ends_with(b"[1]") {
+                let n = stream.read(&mut buf).unwrap();
+                request.extend_from_slice(&buf[..n]);
+            }
+            stream.write_all(b"HTTP/1.1 201 Created\r\n\r\n").unwrap();
+            String::from_utf8(request).unwrap()
+        });
+
+        let status = post_json(&url, &[("X-Test", "yes")], "[1]").unwrap();
+        let request = server.join().unwrap();
+        assert_eq!(status, 201);
+        assert!(request.starts_with("POST /api HTTP/1.1\r\n"));
+        assert!(request.contains("Content-Length: 3\r\n"));
+        assert!(request.contains("X-Test: yes\r\n\r\n[1]"));
+    }
+}
@@ -1,7 +1,8 @@
 use std::fs::{File, OpenOptions};
 use std::io::{Error, ErrorKind, Result};
 use std::path::PathBuf;
-use std::{env, fs};
+use std::time::{Duration, Instant};
+use std::{env, fs, thread};
 
 pub struct ServerLock {
     path: PathBuf,
@@ -24,6 +25,22 @@ impl ServerLock {
         Ok(ServerLock { path, _file: file })
     }
 
+    /// Waits up to `timeout` for the instance holding the lock to exit.
+    pub fn wait(timeout: Duration) -> Result<Self> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            match Self::new() {
+                Err(e)
+                    if e.kind() == ErrorKind::AlreadyExists
+                        && Instant::now() < deadline =>
+                {
+                    thread::sleep(Duration::from_millis(50));
+                }
+                result => return result,
+            }
+        }
+    }
+
     fn get_lock_path() -> Result<PathBuf> {
         let mut path = env::temp_dir();
         path.push("cord-server.lock");
@@ -1,8 +1,14 @@
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+pub mod clock;
+pub mod glob;
+pub mod http;
 pub mod lockfile;
 pub mod logger;
 pub mod macros;
+pub mod process;
+pub mod regex;
+pub mod template;
 
 struct Lcg {
     state: u64,
@@ -0,0 +1,52 @@
+/// Reports whether a process with the given PID exists on this machine, or
+/// `None` where that cannot be determined. If `start_time` is known, a
+/// process that started at another time reuses the PID of one that exited.
+#[cfg(target_os = "linux")]
+pub fn is_alive(pid: u32, start_time: Option<u64>) -> Option<bool> {
+    let Ok(stat) = std::fs::read_to_string(format!("/proc/{}/stat", pid))
+    else {
+        return Some(false);
+    };
+
+    Some(start_time.is_none_or(|time| parse_start_time(&stat) == Some(time)))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn is_alive(_pid: u32, _start_time: Option<u64>) -> Option<bool> {
+    None
+}
+
+/// Reads the start time, field 22, from the contents of `/proc/<pid>/stat`.
+/// The command name in field 2 may hold spaces and parentheses, so fields
+/// are counted from the last `)`, which ends it.
+#[cfg(any(target_os = "linux", test))]
+fn parse_start_time(stat: &str) -> Option<u64> {
+    let (_, fields) = stat.rsplit_once(')')?;
+    fields.split_whitespace().nth(19)?.parse().ok()
+}
+
+/// Name of the machine the server runs on.
+#[cfg(target_os = "linux")]
+pub fn hostname() -> Option<String> {
+    std::fs::read_to_string("/proc/sys/kernel/hostname")
+        .ok()
+        .map(|s| s.trim().to_string())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn hostname() -> Option<String> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn start_time_follows_the_command_name() {
+        let stat = "4242 (nvim (x) y) S 1 4242 4242 34816 4242 4194304 \
+                    1234 0 0 0 12 3 0 0 20 0 1 0 987654 0 0";
+        assert_eq!(parse_start_time(stat), Some(987654));
+        assert_eq!(parse_start_time("4242 (nvim) S 1"), None);
+    }
+}
@@ -0,0 +1,681 @@
+//! A small regular expression engine, enough for redaction rules: literals,
+//! `.`, character classes (`[a-z]`, `[^/]`, `\d`, `\w`, `\s` and their
+//! negations), anchors (`^`, `$`, `\b`), groups with alternation and greedy
+//! or lazy quantifiers (`*`, `+`, `?`, `{n,m}`). A leading `(?i)` makes the
+//! whole pattern case-insensitive.
+//!
+//! Patterns are compiled to a program that is run on every alternative at
+//! once (a Pike VM), so matching takes time linear in the text whatever the
+//! pattern, where backtracking could take exponential time on `(a*)*b`.
+
+use std::fmt::Display;
+
+#[derive(Debug, Clone)]
+pub struct Regex {
+    source: String,
+    program: Vec<Inst>,
+    ignore_case: bool,
+}
+
+/// Most instructions a pattern compiles to, as bounded repetitions are
+/// spelled out.
+const MAX_PROGRAM: usize = 10_000;
+
+#[derive(Debug, Clone)]
+enum Inst {
+    Char(char),
+    Any,
+    Class(Class),
+    Start,
+    End,
+    WordBoundary,
+    /// Continues at both targets, the first taking precedence.
+    Split(usize, usize),
+    Jump(usize),
+    Match,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Empty,
+    Char(char),
+    Any,
+    Class(Class),
+    Start,
+    End,
+    WordBoundary,
+    Concat(Vec<Node>),
+    Alt(Vec<Node>),
+    Repeat {
+        node: Box<Node>,
+        min: usize,
+        max: Option<usize>,
+        greedy: bool,
+    },
+}
+
+#[derive(Debug, Clone)]
+struct Class {
+    negated: bool,
+    items: Vec<ClassItem>,
+}
+
+#[derive(Debug, Clone)]
+enum ClassItem {
+    Range(char, char),
+    /// A predefined class, negated if the flag is set.
+    Digit(bool),
+    Word(bool),
+    Space(bool),
+}
+
+impl ClassItem {
+    fn matches(&self, c: char, ignore_case: bool) -> bool {
+        match *self {
+            ClassItem::Range(from, to) => {
+                (from..=to).contains(&c)
+                    || (ignore_case
+                        && c.to_lowercase()
+                            .chain(c.to_uppercase())
+                            .any(|c| (from..=to).contains(&c)))
+            }
+            ClassItem::Digit(negated) => c.is_ascii_digit() != negated,
+            ClassItem::Word(negated) => is_word(c) != negated,
+            ClassItem::Space(negated) => c.is_whitespace() != negated,
+        }
+    }
+}
+
+impl Class {
+    fn matches(&self, c: char, ignore_case: bool) -> bool {
+        self.items.iter().any(|item| item.matches(c, ignore_case))
+            != self.negated
+    }
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+impl Regex {
+    pub fn new(pattern: &str) -> Result<Self, String> {
+        let (ignore_case, rest) = match pattern.strip_prefix("(?i)") {
+            Some(rest) => (true, rest),
+            None => (false, pattern),
+        };
+        let mut parser = Parser {
+            chars: rest.chars().collect(),
+            pos: 0,
+        };
+        let node = parser.parse_alt()?;
+        if parser.pos < parser.chars.len() {
+            return Err(parser.error("unmatched ')'"));
+        }
+        let mut program = Vec::new();
+        compile(&node, &mut program)?;
+        program.push(Inst::Match);
+
+        Ok(Self {
+            source: pattern.to_string(),
+            program,
+            ignore_case,
+        })
+    }
+
+    /// Replaces every match in `text` with `replacement`.
+    pub fn replace_all(&self, text: &str, replacement: &str) -> String {
+        let chars: Vec<char> = text.chars().collect();
+        let mut result = String::with_capacity(text.len());
+        let mut pos = 0;
+
+        while let Some((start, end)) = self.find_at(&chars, pos) {
+            result.extend(&chars[pos..start]);
+            result.push_str(replacement);
+            if end == start {
+                // Move past empty matches so that they are not found again
+                if let Some(c) = chars.get(end) {
+                    result.push(*c);
+                }
+                pos = end + 1;
+            } else {
+                pos = end;
+            }
+            if pos > chars.len() {
+                return result;
+            }
+        }
+        result.extend(&chars[pos..]);
+
+        result
+    }
+
+    /// Finds the leftmost match starting at or after `from`, preferring
+    /// alternatives and repetitions the way a backtracking engine would.
+    fn find_at(&self, text: &[char], from: usize) -> Option<(usize, usize)> {
+        let vm = Vm {
+            program: &self.program,
+            text,
+            ignore_case: self.ignore_case,
+        };
+        // Threads are kept by precedence, as (instruction, match start)
+        let mut current = Vec::new();
+        let mut next = Vec::new();
+        let mut added = vec![usize::MAX; self.program.len()];
+        let mut found = None;
+
+        for pos in from..=text.len() {
+            if found.is_none() {
+                vm.add(&mut current, &mut added, 0, pos, pos);
+            }
+            if current.is_empty() {
+                if found.is_some() {
+                    break;
+                }
+                continue;
+            }
+
+            for &(pc, start) in &current {
+                let c = text.get(pos).copied();
+                let step = match &self.program[pc] {
+                    Inst::Match => {
+                        // Threads after this one have lower precedence
+                        found = Some((start, pos));
+                        break;
+                    }
+                    Inst::Char(expected) => {
+                        c.is_some_and(|c| vm.char_eq(*expected, c))
+                    }
+                    Inst::Any => c.is_some(),
+                    Inst::Class(class) => {
+                        c.is_some_and(|c| class.matches(c, self.ignore_case))
+                    }
+                    _ => unreachable!("only consuming instructions are queued"),
+                };
+                if step {
+                    vm.add(&mut next, &mut added, pc + 1, start, pos + 1);
+                }
+            }
+
+            std::mem::swap(&mut current, &mut next);
+            next.clear();
+        }
+
+        found
+    }
+}
+
+impl Display for Regex {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+/// Appends the instructions matching `node` to `program`.
+fn compile(node: &Node, program: &mut Vec<Inst>) -> Result<(), String> {
+    if program.len() > MAX_PROGRAM {
+        return Err("pattern is too large".to_string());
+    }
+
+    match node {
+        Node::Empty => {}
+        Node::Char(c) => program.push(Inst::Char(*c)),
+        Node::Any => program.push(Inst::Any),
+        Node::Class(class) => program.push(Inst::Class(class.clone())),
+        Node::Start => program.push(Inst::Start),
+        Node::End => program.push(Inst::End),
+        Node::WordBoundary => program.push(Inst::WordBoundary),
+        Node::Concat(nodes) => {
+            for node in nodes {
+                compile(node, program)?;
+            }
+        }
+        Node::Alt(alts) => {
+            let mut jumps = Vec::new();
+            for (i, alt) in alts.iter().enumerate() {
+                if i == alts.len() - 1 {
+                    compile(alt, program)?;
+                    break;
+                }
+                let split = program.len();
+                program.push(Inst::Split(split + 1, 0));
+                compile(alt, program)?;
+                jumps.push(program.len());
+                program.push(Inst::Jump(0));
+                program[split] = Inst::Split(split + 1, program.len());
+            }
+            let end = program.len();
+            for jump in jumps {
+                program[jump] = Inst::Jump(end);
+            }
+        }
+        Node::Repeat {
+            node,
+            min,
+            max,
+            greedy,
+        } => {
+            let split = |body: usize, exit: usize| {
+                if *greedy {
+                    Inst::Split(body, exit)
+                } else {
+                    Inst::Split(exit, body)
+                }
+            };
+            for _ in 0..*min {
+                compile(node, program)?;
+            }
+            match max {
+                None => {
+                    let start = program.len();
+                    program.push(Inst::Jump(0));
+                    compile(node, program)?;
+                    program.push(Inst::Jump(start));
+                    program[start] = split(start + 1, program.len());
+                }
+                Some(max) => {
+                    let mut starts = Vec::new();
+                    for _ in *min..*max {
+                        starts.push(program.len());
+                        program.push(Inst::Jump(0));
+                        compile(node, program)?;
+                    }
+                    let end = program.len();
+                    for start in starts {
+                        program[start] = split(start + 1, end);
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+struct Vm<'a> {
+    program: &'a [Inst],
+    text: &'a [char],
+    ignore_case: bool,
+}
+
+impl Vm<'_> {
+    fn char_eq(&self, a: char, b: char) -> bool {
+        a == b || (self.ignore_case && a.to_lowercase().eq(b.to_lowercase()))
+    }
+
+    fn is_word_at(&self, pos: usize) -> bool {
+        self.text.get(pos).is_some_and(|c| is_word(*c))
+    }
+
+    /// Queues the thread at `pc` on `threads`, following jumps and
+    /// assertions at `pos` up to the instructions that consume a character.
+    /// Instructions already reached at `pos` are skipped, as a thread with
+    /// higher precedence got there first.
+    fn add(
+        &self,
+        threads: &mut Vec<(usize, usize)>,
+        added: &mut [usize],
+        pc: usize,
+        start: usize,
+        pos: usize,
+    ) {
+        let mut stack = vec![pc];
+        while let Some(pc) = stack.pop() {
+            if added[pc] == pos {
+                continue;
+            }
+            added[pc] = pos;
+
+            let holds = match self.program[pc] {
+                Inst::Jump(to) => {
+                    stack.push(to);
+                    continue;
+                }
+                Inst::Split(first, second) => {
+                    stack.push(second);
+                    stack.push(first);
+                    continue;
+                }
+                Inst::Start => pos == 0,
+                Inst::End => pos == self.text.len(),
+                Inst::WordBoundary => {
+                    let before = pos > 0 && self.is_word_at(pos - 1);
+                    before != self.is_word_at(pos)
+                }
+                _ => {
+                    threads.push((pc, start));
+                    continue;
+                }
+            };
+            if holds {
+                stack.push(pc + 1);
+            }
+        }
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn error(&self, message: &str) -> String {
+        format!("{} at position {}", message, self.pos)
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        let found = self.peek() == Some(c);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn parse_alt(&mut self) -> Result<Node, String> {
+        let mut alts = vec![self.parse_concat()?];
+        while self.eat('|') {
+            alts.push(self.parse_concat()?);
+        }
+
+        Ok(if alts.len() == 1 {
+            alts.remove(0)
+        } else {
+            Node::Alt(alts)
+        })
+    }
+
+    fn parse_concat(&mut self) -> Result<Node, String> {
+        let mut nodes = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let atom = self.parse_atom()?;
+            nodes.push(self.parse_quantifier(atom)?);
+        }
+
+        Ok(match nodes.len() {
+            0 => Node::Empty,
+            1 => nodes.remove(0),
+            _ => Node::Concat(nodes),
+        })
+    }
+
+    fn parse_atom(&mut self) -> Result<Node, String> {
+        let c = self.peek().ok_or_else(|| self.error("unexpected end"))?;
+        self.pos += 1;
+
+        Ok(match c {
+            '(' => {
+                if self.eat('?') && !self.eat(':') {
+                    return Err(self.error("unsupported group"));
+                }
+                let node = self.parse_alt()?;
+                if !self.eat(')') {
+                    return Err(self.error("missing ')'"));
+                }
+                node
+            }
+            '[' => Node::Class(self.parse_class()?),
+            '.' => Node::Any,
+            '^' => Node::Start,
+            '$' => Node::End,
+            '\\' => match self.parse_escape()? {
+                Escape::Char(c) => Node::Char(c),
+                Escape::Class(item) => Node::Class(Class {
+                    negated: false,
+                    items: vec![item],
+                }),
+                Escape::WordBoundary => Node::WordBoundary,
+            },
+            '*' | '+' | '?' | '{' => {
+                self.pos -= 1;
+                return Err(self.error("nothing to repeat"));
+            }
+            c => Node::Char(c),
+        })
+    }
+
+    fn parse_escape(&mut self) -> Result<Escape, String> {
+        let c = self.peek().ok_or_else(|| self.error("trailing '\\'"))?;
+        self.pos += 1;
+
+        Ok(match c {
+            'd' => Escape::Class(ClassItem::Digit(false)),
+            'D' => Escape::Class(ClassItem::Digit(true)),
+            'w' => Escape::Class(ClassItem::Word(false)),
+            'W' => Escape::Class(ClassItem::Word(true)),
+            's' => Escape::Class(ClassItem::Space(false)),
+            'S' => Escape::Class(ClassItem::Space(true)),
+            'b' => Escape::WordBoundary,
+            'n' => Escape::Char('\n'),
+            't' => Escape::Char('\t'),
+            c if c.is_alphanumeric() => {
+                self.pos -= 1;
+                return Err(self.error("unsupported escape"));
+            }
+            c => Escape::Char(c),
+        })
+    }
+
+    fn parse_class(&mut self) -> Result<Class, String> {
+        let negated = self.eat('^');
+        let mut items = Vec::new();
+        let mut first = true;
+
+        loop {
+            let c = self.peek().ok_or_else(|| self.error("missing ']'"))?;
+            self.pos += 1;
+            if c == ']' && !first {
+                break;
+            }
+            first = false;
+
+            let from = match c {
+                '\\' => match self.parse_escape()? {
+                    Escape::Char(c) => c,
+                    Escape::Class(item) => {
+                        items.push(item);
+                        continue;
+                    }
+                    Escape::WordBoundary => '\u{8}',
+                },
+                c => c,
+            };
+
+            let is_range = self.peek() == Some('-')
+                && self.chars.get(self.pos + 1).is_some_and(|c| *c != ']');
+            if !is_range {
+                items.push(ClassItem::Range(from, from));
+                continue;
+            }
+
+            self.pos += 1;
+            let to = match self.peek() {
+                Some('\\') => {
+                    self.pos += 1;
+                    match self.parse_escape()? {
+                        Escape::Char(c) => c,
+                        _ => return Err(self.error("invalid range")),
+                    }
+                }
+                Some(c) => {
+                    self.pos += 1;
+                    c
+                }
+                None => return Err(self.error("missing ']'")),
+            };
+            if to < from {
+                return Err(self.error("invalid range"));
+            }
+            items.push(ClassItem::Range(from, to));
+        }
+
+        Ok(Class { negated, items })
+    }
+
+    fn parse_quantifier(&mut self, node: Node) -> Result<Node, String> {
+        let (min, max) = match self.peek() {
+            Some('*') => (0, None),
+            Some('+') => (1, None),
+            Some('?') => (0, Some(1)),
+            Some('{') => {
+                let start = self.pos;
+                self.pos += 1;
+                match self.parse_bounds() {
+                    Some(bounds) => {
+                        self.pos -= 1;
+                        bounds
+                    }
+                    None => {
+                        self.pos = start;
+                        return Err(self.error("invalid repetition"));
+                    }
+                }
+            }
+            _ => return Ok(node),
+        };
+        self.pos += 1;
+        if matches!(node, Node::Start | Node::End | Node::WordBoundary) {
+            return Err(self.error("nothing to repeat"));
+        }
+        let greedy = !self.eat('?');
+
+        Ok(Node::Repeat {
+            node: Box::new(node),
+            min,
+            max,
+            greedy,
+        })
+    }
+
+    /// Parses `n}`, `n,}` or `n,m}`, leaving the position after the `}`.
+    fn parse_bounds(&mut self) -> Option<(usize, Option<usize>)> {
+        let min = self.parse_number()?;
+        let max = if self.eat(',') {
+            if self.peek() == Some('}') {
+                None
+            } else {
+                Some(self.parse_number()?)
+            }
+        } else {
+            Some(min)
+        };
+        if !self.eat('}') || max.is_some_and(|max| max < min) {
+            return None;
+        }
+
+        Some((min, max))
+    }
+
+    fn parse_number(&mut self) -> Option<usize> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos]
+            .iter()
+            .collect::<String>()
+            .parse()
+            .ok()
+    }
+}
+
+enum Escape {
+    Char(char),
+    Class(ClassItem),
+    WordBoundary,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replace(pattern: &str, text: &str) -> String {
+        Regex::new(pattern).unwrap().replace_all(text, "#")
+    }
+
+    #[test]
+    fn literals_and_classes() {
+        assert_eq!(replace("acme", "acme-api and acme"), "#-api and #");
+        assert_eq!(replace("a.c", "abc a/c ac"), "# # ac");
+        assert_eq!(replace("[a-c]+", "abcd cab"), "#d #");
+        assert_eq!(replace("[^a-c ]", "abcd cab"), "abc# cab");
+        assert_eq!(replace(r"\d+", "v1.23"), "v#.#");
+        assert_eq!(replace(r"\w+\.rs", "main.rs, lib.rs"), "#, #");
+        assert_eq!(replace(r"[\w-]+", "foo-bar baz"), "# #");
+        assert_eq!(replace(r"\s", "a b\tc"), "a#b#c");
+    }
+
+    #[test]
+    fn anchors_and_boundaries() {
+        assert_eq!(replace("^a", "aaa"), "#aa");
+        assert_eq!(replace("a$", "aaa"), "aa#");
+        assert_eq!(replace(r"\bcat\b", "cat concat cat."), "# concat #.");
+    }
+
+    #[test]
+    fn groups_and_alternation() {
+        assert_eq!(replace("(foo|bar)+", "foobarbaz"), "#baz");
+        assert_eq!(replace("a(?:b|c)d", "abd acd aed"), "# # aed");
+        assert_eq!(replace("client-(x|y)z?", "client-x client-yz"), "# #");
+    }
+
+    #[test]
+    fn quantifiers() {
+        assert_eq!(replace("a{2}", "aaaaa"), "##a");
+        assert_eq!(replace("a{2,}", "a aa aaaa"), "a # #");
+        assert_eq!(replace("a{1,2}", "aaa"), "##");
+        assert_eq!(replace("<.+>", "<a><b>"), "#");
+        assert_eq!(replace("<.+?>", "<a><b>"), "##");
+        assert_eq!(replace("(a*)*b", "aab"), "#");
+        assert_eq!(replace("a??b", "ab"), "#");
+        assert_eq!(replace("(a|ab)(c|bcd)", "abcd"), "#");
+    }
+
+    #[test]
+    fn pathological_patterns_match_in_linear_time() {
+        let text = "a".repeat(20_000);
+        let started = std::time::Instant::now();
+
+        assert_eq!(replace("(a*)*b", &text), text);
+        assert_eq!(replace("(a|aa)+$", &format!("{}!", text)), text + "!");
+        assert_eq!(replace("(a+)+", &"a".repeat(20_000)), "#");
+        assert!(started.elapsed() < std::time::Duration::from_secs(10));
+    }
+
+    #[test]
+    fn oversized_patterns_are_rejected() {
+        assert!(Regex::new("(a{100}){100}{100}").is_err());
+    }
+
+    #[test]
+    fn empty_matches_do_not_loop() {
+        assert_eq!(replace("x*", "ab"), "#a#b#");
+        assert_eq!(replace("", "ab"), "#a#b#");
+    }
+
+    #[test]
+    fn case_insensitive() {
+        assert_eq!(replace("(?i)acme", "ACME Acme acme"), "# # #");
+        assert_eq!(replace("(?i)[a-c]+", "ABC"), "#");
+        assert_eq!(replace("acme", "ACME"), "ACME");
+    }
+
+    #[test]
+    fn invalid_patterns() {
+        for pattern in ["(a", "a)", "[a", "*a", "a{2,1}", r"\q", "(?=a)"] {
+            assert!(Regex::new(pattern).is_err(), "{}", pattern);
+        }
+        assert!(Regex::new("a{").is_err());
+    }
+
+    #[test]
+    fn anchored_patterns_match_once() {
+        assert_eq!(replace(r"^/home/\w+/", "/home/me/home/you/"), "#home/you/");
+    }
+}
@@ -0,0 +1,204 @@
+use std::fmt::Display;
+
+/// A string with `{field}` placeholders.
+///
+/// * `{field}` is replaced by the value of `field`.
+/// * `{field:noun}` renders a count followed by `noun`, pluralized with an
+///   `s` unless the count is 1, e.g. `3 editors`.
+/// * `{field|fallback}` renders `fallback` when `field` has no value.
+/// * `{{` and `}}` render literal braces.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    source: String,
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Literal(String),
+    Field {
+        name: String,
+        noun: Option<String>,
+        fallback: Option<String>,
+    },
+}
+
+impl Template {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut chars = source.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut placeholder = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => placeholder.push(c),
+                            None => {
+                                return Err(format!(
+                                    "Unclosed placeholder in '{}'",
+                                    source
+                                ));
+                            }
+                        }
+                    }
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(std::mem::take(
+                            &mut literal,
+                        )));
+                    }
+                    segments.push(Self::parse_field(&placeholder)?);
+                }
+                '}' => {
+                    return Err(format!("Unmatched '}}' in '{}'", source));
+                }
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+
+        Ok(Self {
+            source: source.to_string(),
+            segments,
+        })
+    }
+
+    fn parse_field(placeholder: &str) -> Result<Segment, String> {
+        let (field, fallback) = match placeholder.split_once('|') {
+            Some((field, fallback)) => (field, Some(fallback.to_string())),
+            None => (placeholder, None),
+        };
+        let (name, noun) = match field.split_once(':') {
+            Some((name, noun)) => (name.trim(), Some(noun.trim().to_string())),
+            None => (field.trim(), None),
+        };
+        if name.is_empty() {
+            return Err(format!("Empty placeholder '{{{}}}'", placeholder));
+        }
+
+        Ok(Segment::Field {
+            name: name.to_string(),
+            noun,
+            fallback,
+        })
+    }
+
+    /// Names of the fields the template refers to.
+    pub fn fields(&self) -> impl Iterator<Item = &str> {
+        self.segments.iter().filter_map(|s| match s {
+            Segment::Field { name, .. } => Some(name.as_str()),
+            Segment::Literal(_) => None,
+        })
+    }
+
+    /// Renders the template, looking fields up with `value`.
+    pub fn render(&self, value: impl Fn(&str) -> Option<String>) -> String {
+        let mut out = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(text) => out.push_str(text),
+                Segment::Field {
+                    name,
+                    noun,
+                    fallback,
+                } => match (value(name), noun) {
+                    (Some(value), Some(noun)) => {
+                        out.push_str(&value);
+                        out.push(' ');
+                        out.push_str(noun);
+                        if value != "1" {
+                            out.push('s');
+                        }
+                    }
+                    (Some(value), None) => out.push_str(&value),
+                    (None, _) => {
+                        if let Some(fallback) = fallback {
+                            out.push_str(fallback);
+                        }
+                    }
+                },
+            }
+        }
+
+        out
+    }
+}
+
+impl Display for Template {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(template: &str, fields: &[(&str, &str)]) -> String {
+        Template::parse(template).unwrap().render(|name| {
+            fields
+                .iter()
+                .find(|(field, _)| *field == name)
+                .map(|(_, value)| value.to_string())
+        })
+    }
+
+    #[test]
+    fn replaces_fields() {
+        assert_eq!(
+            render(
+                "Editing {file} in {project}",
+                &[("file", "main.rs"), ("project", "cord")]
+            ),
+            "Editing main.rs in cord"
+        );
+    }
+
+    #[test]
+    fn pluralizes_counts() {
+        let template = "{editors:editor} · {projects:project}";
+        assert_eq!(
+            render(template, &[("editors", "3"), ("projects", "1")]),
+            "3 editors · 1 project"
+        );
+    }
+
+    #[test]
+    fn falls_back_on_missing_fields() {
+        assert_eq!(render("{file|Idle}", &[]), "Idle");
+        assert_eq!(render("[{file}]", &[]), "[]");
+        assert_eq!(render("{file|Idle}", &[("file", "a.rs")]), "a.rs");
+    }
+
+    #[test]
+    fn escapes_braces() {
+        assert_eq!(render("{{{file}}}", &[("file", "a.rs")]), "{a.rs}");
+    }
+
+    #[test]
+    fn rejects_malformed_templates() {
+        assert!(Template::parse("{file").is_err());
+        assert!(Template::parse("file}").is_err());
+        assert!(Template::parse("{}").is_err());
+    }
+
+    #[test]
+    fn lists_fields() {
+        let template = Template::parse("{a} {b:thing|none} c").unwrap();
+        assert_eq!(template.fields().collect::<Vec<_>>(), vec!["a", "b"]);
+        assert_eq!(template.to_string(), "{a} {b:thing|none} c");
+    }
+}